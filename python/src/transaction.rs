@@ -8,7 +8,7 @@ use lance::dataset::transaction::{
 };
 use lance::datatypes::Schema;
 use lance_table::format::{DataFile, Fragment, Index};
-use pyo3::exceptions::PyValueError;
+use pyo3::exceptions::{PyNotImplementedError, PyValueError};
 use pyo3::types::PySet;
 use pyo3::{intern, prelude::*};
 use pyo3::{Bound, FromPyObject, PyAny, PyResult, Python};
@@ -22,7 +22,10 @@ impl FromPyObject<'_> for PyLance<DataReplacementGroup> {
         let fragment_id = ob.getattr("fragment_id")?.extract::<u64>()?;
         let new_file = &ob.getattr("new_file")?.extract::<PyLance<DataFile>>()?;
 
-        Ok(Self(DataReplacementGroup(fragment_id, new_file.0.clone())))
+        Ok(Self(DataReplacementGroup::new(
+            fragment_id,
+            new_file.0.clone(),
+        )))
     }
 }
 
@@ -162,7 +165,10 @@ impl FromPyObject<'_> for PyLance<Operation> {
             "DataReplacement" => {
                 let replacements = extract_vec(&ob.getattr("replacements")?)?;
 
-                let op = Operation::DataReplacement { replacements };
+                let op = Operation::DataReplacement {
+                    replacements,
+                    new_index: None,
+                };
 
                 Ok(Self(op))
             }
@@ -234,7 +240,17 @@ impl<'py> IntoPyObject<'py> for PyLance<&Operation> {
                     fields_modified,
                 ))
             }
-            Operation::DataReplacement { replacements } => {
+            Operation::DataReplacement {
+                replacements,
+                new_index,
+            } => {
+                // The Python operation has no index, so don't silently drop it
+                if let Some(new_index) = new_index {
+                    return Err(PyNotImplementedError::new_err(format!(
+                        "DataReplacement with a new index ({}) is not supported in Python",
+                        new_index.name
+                    )));
+                }
                 let replacements = export_vec(py, replacements.as_slice())?;
                 let cls = namespace
                     .getattr("DataReplacement")
@@ -19,10 +19,11 @@ mod schema;
 
 use crate::{Error, Result};
 pub use field::{
-    Encoding, Field, NullabilityComparison, OnTypeMismatch, SchemaCompareOptions, StorageClass,
-    LANCE_STORAGE_CLASS_SCHEMA_META_KEY,
+    is_reserved_metadata_key, DiffCategory, Encoding, Field, NullabilityComparison, OnTypeMismatch,
+    SchemaCompareOptions, StorageClass, LANCE_SCHEMA_META_KEY_PREFIX,
+    LANCE_STORAGE_CLASS_SCHEMA_META_KEY, LANCE_UNENFORCED_PRIMARY_KEY,
 };
-pub use schema::{OnMissing, Projectable, Projection, Schema};
+pub use schema::{ColumnSelection, OnMissing, Projectable, Projection, ResolveError, Schema};
 
 pub const COMPRESSION_META_KEY: &str = "lance-encoding:compression";
 pub const COMPRESSION_LEVEL_META_KEY: &str = "lance-encoding:compression-level";
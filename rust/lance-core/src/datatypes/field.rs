@@ -5,7 +5,7 @@
 
 use std::{
     cmp::{max, Ordering},
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet},
     fmt::{self, Display},
     str::FromStr,
     sync::Arc,
@@ -29,6 +29,9 @@ use super::{
 };
 use crate::{Error, Result};
 
+/// Prefix of the metadata keys reserved by Lance for schema-level settings.
+pub const LANCE_SCHEMA_META_KEY_PREFIX: &str = "lance-schema:";
+
 pub const LANCE_STORAGE_CLASS_SCHEMA_META_KEY: &str = "lance-schema:storage-class";
 
 /// Use this config key in Arrow field metadata to indicate a column is a part of the primary key.
@@ -39,6 +42,11 @@ pub const LANCE_STORAGE_CLASS_SCHEMA_META_KEY: &str = "lance-schema:storage-clas
 /// (3) The field must not be within a list type.
 pub const LANCE_UNENFORCED_PRIMARY_KEY: &str = "lance-schema:unenforced-primary-key";
 
+/// Returns true if `key` belongs to the metadata namespace reserved by Lance.
+pub fn is_reserved_metadata_key(key: &str) -> bool {
+    key.starts_with(LANCE_SCHEMA_META_KEY_PREFIX)
+}
+
 #[derive(Debug, Default)]
 pub enum NullabilityComparison {
     // If the nullabilities don't match then the fields don't match
@@ -68,7 +76,34 @@ pub struct SchemaCompareOptions {
     pub allow_missing_if_nullable: bool,
     /// Allow out of order fields (default false)
     pub ignore_field_order: bool,
+    /// Ignore the metadata of individual fields, even if `compare_metadata` is
+    /// set, so that only the schema metadata is compared (default false)
+    pub ignore_field_metadata: bool,
+    /// Allow fields in the schema being tested that are not in the expected
+    /// schema, at any nesting level (default false)
+    pub allow_extra_fields: bool,
+}
+
+/// A category of difference found when comparing schemas, see
+/// [`super::Schema::difference_categories`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffCategory {
+    /// Fields are missing, unexpected, or have a different name
+    Names,
+    /// Fields are in a different order
+    Order,
+    /// Fields have a different id
+    FieldIds,
+    /// Fields have a different type
+    Types,
+    /// Fields have a different nullability
+    Nullability,
+    /// Fields have a different dictionary
+    Dictionary,
+    /// Fields, or the schema itself, have different metadata
+    Metadata,
 }
+
 /// Encoding enum.
 #[derive(Debug, Clone, PartialEq, Eq, DeepSizeOf)]
 pub enum Encoding {
@@ -83,7 +118,7 @@ pub enum Encoding {
 }
 
 /// Describes the rate at which a column should be compacted
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, DeepSizeOf)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, DeepSizeOf)]
 pub enum StorageClass {
     /// Default storage class (stored in primary dataset)
     #[default]
@@ -283,6 +318,7 @@ impl Field {
         expected: &Self,
         options: &SchemaCompareOptions,
         path: Option<&str>,
+        categories: &mut HashSet<DiffCategory>,
     ) -> Vec<String> {
         let mut differences = Vec::new();
         let self_name = path
@@ -306,42 +342,52 @@ impl Field {
                 "expected name '{}' but name was '{}'",
                 expected_path, self_name
             ));
+            categories.insert(DiffCategory::Names);
         }
         if options.compare_field_ids && self.id != expected.id {
             differences.push(format!(
                 "`{}` should have id {} but id was {}",
                 self_name, expected.id, self.id
             ));
+            categories.insert(DiffCategory::FieldIds);
         }
         if self.logical_type != expected.logical_type {
             differences.push(format!(
                 "`{}` should have type {} but type was {}",
                 self_name, expected.logical_type, self.logical_type
             ));
+            categories.insert(DiffCategory::Types);
         }
         if !Self::compare_nullability(expected.nullable, self.nullable, options) {
             differences.push(format!(
                 "`{}` should have nullable={} but nullable={}",
                 self_name, expected.nullable, self.nullable
-            ))
+            ));
+            categories.insert(DiffCategory::Nullability);
         }
         if options.compare_dictionary && self.dictionary != expected.dictionary {
             differences.push(format!(
                 "dictionary for `{}` did not match expected dictionary",
                 self_name
             ));
+            categories.insert(DiffCategory::Dictionary);
         }
-        if options.compare_metadata && self.metadata != expected.metadata {
+        if options.compare_metadata
+            && !options.ignore_field_metadata
+            && self.metadata != expected.metadata
+        {
             differences.push(format!(
                 "metadata for `{}` did not match expected metadata",
                 self_name
             ));
+            categories.insert(DiffCategory::Metadata);
         }
         let children_differences = explain_fields_difference(
             &self.children,
             &expected.children,
             options,
             Some(&self_name),
+            categories,
         );
         if !children_differences.is_empty() {
             let children_differences = format!(
@@ -359,7 +405,7 @@ impl Field {
         expected: &Self,
         options: &SchemaCompareOptions,
     ) -> Option<String> {
-        let differences = self.explain_differences(expected, options, None);
+        let differences = self.explain_differences(expected, options, None, &mut HashSet::new());
         if differences.is_empty() {
             None
         } else {
@@ -386,7 +432,9 @@ impl Field {
             && compare_fields(&self.children, &expected.children, options)
             && (!options.compare_field_ids || self.id == expected.id)
             && (!options.compare_dictionary || self.dictionary == expected.dictionary)
-            && (!options.compare_metadata || self.metadata == expected.metadata)
+            && (!options.compare_metadata
+                || options.ignore_field_metadata
+                || self.metadata == expected.metadata)
     }
 
     pub fn extension_name(&self) -> Option<&str> {
@@ -494,7 +542,23 @@ impl Field {
         }
     }
 
+    /// Project the field to the given path of child names.
+    ///
+    /// Children that don't exist are skipped, leaving a field without children.
+    /// Returns an error if the path goes through a field that has no children,
+    /// such as a scalar.
     pub fn project(&self, path_components: &[&str]) -> Result<Self> {
+        if let Some(first) = path_components.first() {
+            if self.is_leaf() {
+                return Err(Error::Schema {
+                    message: format!(
+                        "cannot select nested field '{}' because '{}' is not a struct",
+                        first, self.name
+                    ),
+                    location: location!(),
+                });
+            }
+        }
         let mut f = Self {
             name: self.name.clone(),
             id: self.id,
@@ -667,23 +731,6 @@ impl Field {
         }
     }
 
-    pub(crate) fn resolve<'a>(
-        &'a self,
-        split: &mut VecDeque<&str>,
-        fields: &mut Vec<&'a Self>,
-    ) -> bool {
-        fields.push(self);
-        if split.is_empty() {
-            return true;
-        }
-        let first = split.pop_front().unwrap();
-        if let Some(child) = self.children.iter().find(|c| c.name == first) {
-            child.resolve(split, fields)
-        } else {
-            false
-        }
-    }
-
     pub(crate) fn do_intersection(&self, other: &Self, ignore_types: bool) -> Result<Self> {
         if self.name != other.name {
             return Err(Error::Arrow {
@@ -748,6 +795,7 @@ impl Field {
 
     /// Intersection of two [`Field`]s.
     ///
+    /// Field ids come from `self`, unless `self` has no id assigned.
     pub fn intersection(&self, other: &Self) -> Result<Self> {
         self.do_intersection(other, false)
     }
@@ -4,18 +4,21 @@
 //! Schema
 
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{HashMap, HashSet},
     fmt::{self, Debug, Formatter},
     sync::Arc,
 };
 
 use arrow_array::RecordBatch;
-use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
+use arrow_schema::{DataType, Field as ArrowField, FieldRef, Schema as ArrowSchema};
 use deepsize::DeepSizeOf;
 use lance_arrow::*;
 use snafu::location;
 
-use super::field::{Field, OnTypeMismatch, SchemaCompareOptions, StorageClass};
+use super::field::{
+    is_reserved_metadata_key, DiffCategory, Field, NullabilityComparison, OnTypeMismatch,
+    SchemaCompareOptions, StorageClass,
+};
 use crate::{Error, Result, ROW_ADDR, ROW_ADDR_FIELD, ROW_ID, ROW_ID_FIELD};
 
 /// Lance Schema.
@@ -28,18 +31,29 @@ pub struct Schema {
 }
 
 /// State for a pre-order DFS iterator over the fields of a schema.
-struct SchemaFieldIterPreOrder<'a> {
-    field_stack: Vec<&'a Field>,
+enum SchemaFieldIterPreOrder<'a> {
+    /// No field has children, so the pre-order is just the top-level fields
+    Flat(std::slice::Iter<'a, Field>),
+    Nested {
+        field_stack: Vec<&'a Field>,
+    },
 }
 
 impl<'a> SchemaFieldIterPreOrder<'a> {
-    #[allow(dead_code)]
     fn new(schema: &'a Schema) -> Self {
+        if schema.fields.iter().all(|f| f.children.is_empty()) {
+            Self::Flat(schema.fields.iter())
+        } else {
+            Self::new_nested(schema)
+        }
+    }
+
+    fn new_nested(schema: &'a Schema) -> Self {
         let mut field_stack = Vec::with_capacity(schema.fields.len() * 2);
         for field in schema.fields.iter().rev() {
             field_stack.push(field);
         }
-        Self { field_stack }
+        Self::Nested { field_stack }
     }
 }
 
@@ -48,13 +62,15 @@ impl<'a> Iterator for SchemaFieldIterPreOrder<'a> {
     type Item = &'a Field;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(next_field) = self.field_stack.pop() {
-            for child in next_field.children.iter().rev() {
-                self.field_stack.push(child);
+        match self {
+            Self::Flat(fields) => fields.next(),
+            Self::Nested { field_stack } => {
+                let next_field = field_stack.pop()?;
+                for child in next_field.children.iter().rev() {
+                    field_stack.push(child);
+                }
+                Some(next_field)
             }
-            Some(next_field)
-        } else {
-            None
         }
     }
 }
@@ -67,39 +83,149 @@ impl Schema {
             .collect::<Vec<_>>()
     }
 
+    /// Replace the schema metadata
+    pub fn with_metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// The schema metadata entries in the namespace reserved by Lance
+    pub fn reserved_metadata(&self) -> HashMap<String, String> {
+        self.metadata
+            .iter()
+            .filter(|(k, _)| is_reserved_metadata_key(k))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    /// The schema metadata entries outside of the namespace reserved by Lance
+    pub fn user_metadata(&self) -> HashMap<String, String> {
+        self.metadata
+            .iter()
+            .filter(|(k, _)| !is_reserved_metadata_key(k))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    /// Set a single schema metadata entry, replacing any existing value
+    pub fn with_metadata_entry(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Remove a single schema metadata entry, if present
+    pub fn without_metadata_key(mut self, key: &str) -> Self {
+        self.metadata.remove(key);
+        self
+    }
+
+    /// Convert to an Arrow schema with the [`ROW_ID`] column appended, followed
+    /// by the [`ROW_ADDR`] column if `with_addr` is true.
+    pub fn to_arrow_with_row_id(&self, with_addr: bool) -> Result<ArrowSchema> {
+        let mut arrow_schema = ArrowSchema::from(self).try_with_column(ROW_ID_FIELD.clone())?;
+        if with_addr {
+            arrow_schema = arrow_schema.try_with_column(ROW_ADDR_FIELD.clone())?;
+        }
+        Ok(arrow_schema)
+    }
+
     pub fn compare_with_options(&self, expected: &Self, options: &SchemaCompareOptions) -> bool {
         compare_fields(&self.fields, &expected.fields, options)
             && (!options.compare_metadata || self.metadata == expected.metadata)
     }
 
+    /// Returns true if the two schemas are exactly the same, including the
+    /// schema metadata.
+    ///
+    /// Every field is compared in order, including its id, name, type,
+    /// nullability and metadata.  `==` compares the fields in the same way but
+    /// ignores the schema metadata.  [`Self::compare_with_options`] is looser
+    /// still: by default it ignores field ids and all metadata.
+    pub fn strict_eq(&self, other: &Self) -> bool {
+        self == other && self.metadata == other.metadata
+    }
+
+    /// Returns true if every field in this schema (recursively) exists in `other`
+    /// and is compatible according to `options`.
+    ///
+    /// Fields in `other` that are not present in this schema are ignored.
+    pub fn is_subset_of(&self, other: &Self, options: &SchemaCompareOptions) -> bool {
+        prune_fields_to(&other.fields, &self.fields)
+            .map(|pruned| compare_fields(&self.fields, &pruned, options))
+            .unwrap_or(false)
+    }
+
     pub fn explain_difference(
         &self,
         expected: &Self,
         options: &SchemaCompareOptions,
     ) -> Option<String> {
+        let differences = self.explain_differences(expected, options, &mut HashSet::new());
+        if differences.is_empty() {
+            None
+        } else {
+            Some(differences.join(", "))
+        }
+    }
+
+    /// Returns the categories of differences between this schema and `expected`.
+    ///
+    /// This finds the same differences as [`Self::explain_difference`], and is
+    /// empty if and only if that returns `None`.
+    pub fn difference_categories(
+        &self,
+        expected: &Self,
+        options: &SchemaCompareOptions,
+    ) -> HashSet<DiffCategory> {
+        let mut categories = HashSet::new();
+        self.explain_differences(expected, options, &mut categories);
+        categories
+    }
+
+    fn explain_differences(
+        &self,
+        expected: &Self,
+        options: &SchemaCompareOptions,
+        categories: &mut HashSet<DiffCategory>,
+    ) -> Vec<String> {
         let mut differences =
-            explain_fields_difference(&self.fields, &expected.fields, options, None);
+            explain_fields_difference(&self.fields, &expected.fields, options, None, categories);
 
         if options.compare_metadata {
             if let Some(difference) =
                 explain_metadata_difference(&self.metadata, &expected.metadata)
             {
                 differences.push(difference);
+                categories.insert(DiffCategory::Metadata);
             }
         }
+        differences
+    }
 
-        if differences.is_empty() {
-            None
-        } else {
-            Some(differences.join(", "))
-        }
+    /// Find the leaf fields whose type differs between this schema and `other`.
+    ///
+    /// Fields are matched by name (recursively through nested fields), so field
+    /// order and nullability are ignored.  Fields that only exist in one of the
+    /// schemas are not reported.
+    ///
+    /// Returns the dotted path of each changed field along with its type in
+    /// this schema and its type in `other`.
+    pub fn changed_types(&self, other: &Self) -> Vec<(String, DataType, DataType)> {
+        let mut changes = vec![];
+        collect_changed_types(&self.fields, &other.fields, None, &mut changes);
+        changes
     }
 
-    pub fn retain_storage_class(&self, storage_class: StorageClass) -> Self {
+    /// Keep only the top-level fields that match the predicate.
+    ///
+    /// Retained fields keep their ids, metadata and all of their children,
+    /// even the ones that don't match the predicate. Fields that don't match
+    /// are dropped along with their children. The schema metadata is kept.
+    pub fn retain(&self, predicate: impl Fn(&Field) -> bool) -> Self {
         let fields = self
             .fields
             .iter()
-            .filter(|f| f.storage_class() == storage_class)
+            .filter(|f| predicate(f))
             .cloned()
             .collect();
         Self {
@@ -108,6 +234,10 @@ impl Schema {
         }
     }
 
+    pub fn retain_storage_class(&self, storage_class: StorageClass) -> Self {
+        self.retain(|f| f.storage_class() == storage_class)
+    }
+
     /// Splits the schema into two schemas, one with default storage class fields and the other with blob storage class fields.
     /// If there are no blob storage class fields, the second schema will be `None`.
     /// The order of fields is preserved.
@@ -136,10 +266,55 @@ impl Schema {
         )
     }
 
+    /// Count the leaf fields, at any level, of each storage class.
+    ///
+    /// Storage classes without any leaf fields are not included.
+    pub fn storage_class_leaf_counts(&self) -> HashMap<StorageClass, usize> {
+        let mut counts = HashMap::new();
+        for field in self.fields_pre_order().filter(|f| f.is_leaf()) {
+            *counts.entry(field.storage_class()).or_insert(0) += 1;
+        }
+        counts
+    }
+
     pub fn has_dictionary_types(&self) -> bool {
         self.fields.iter().any(|f| f.has_dictionary_types())
     }
 
+    /// Check that every dictionary field, at any level, has its dictionary
+    /// values set, e.g. by [`Self::set_dictionary`], so that it can be written.
+    ///
+    /// The error names all the dictionary fields without values.
+    pub fn validate_dictionaries_set(&self) -> Result<()> {
+        if !self.has_dictionary_types() {
+            return Ok(());
+        }
+        let missing = self
+            .fields_pre_order()
+            .filter(|f| {
+                f.data_type().is_dictionary()
+                    && f.dictionary
+                        .as_ref()
+                        .and_then(|d| d.values.as_ref())
+                        .is_none()
+            })
+            .map(|f| {
+                self.field_path_by_id(f.id)
+                    .unwrap_or_else(|| f.name.clone())
+            })
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(Error::Schema {
+                message: format!(
+                    "Dictionary fields have no dictionary values set: {}",
+                    missing.join(", ")
+                ),
+                location: location!(),
+            });
+        }
+        Ok(())
+    }
+
     pub fn check_compatible(&self, expected: &Self, options: &SchemaCompareOptions) -> Result<()> {
         if !self.compare_with_options(expected, options) {
             let difference = self.explain_difference(expected, options);
@@ -153,6 +328,43 @@ impl Schema {
         }
     }
 
+    /// Check that data with the `incoming` schema can be appended to a dataset
+    /// with this schema, returning [`Error::SchemaMismatch`] if it cannot.
+    ///
+    /// This is [`Self::check_compatible`] with the options used for appends:
+    /// * `compare_nullability` is [`NullabilityComparison::Ignore`], since the
+    ///   nullability of the actual data is checked while writing
+    /// * `allow_missing_if_nullable` is true, missing nullable fields are
+    ///   written as nulls
+    /// * `ignore_field_order` is true
+    ///
+    /// All other options keep their defaults, so metadata, dictionaries and
+    /// field ids are not compared. Datasets using the legacy file format also
+    /// need identical dictionaries, which an Arrow schema does not carry, see
+    /// [`Self::check_append_compatible_schema`].
+    pub fn check_append_compatible(&self, incoming: &ArrowSchema) -> Result<()> {
+        self.check_append_compatible_schema(&Self::try_from(incoming)?, false)
+    }
+
+    /// Like [`Self::check_append_compatible`], for an `incoming` Lance schema.
+    ///
+    /// If `compare_dictionary` is true, dictionaries must also be identical,
+    /// as required by the legacy file format.
+    pub fn check_append_compatible_schema(
+        &self,
+        incoming: &Self,
+        compare_dictionary: bool,
+    ) -> Result<()> {
+        let options = SchemaCompareOptions {
+            compare_nullability: NullabilityComparison::Ignore,
+            allow_missing_if_nullable: true,
+            ignore_field_order: true,
+            compare_dictionary,
+            ..Default::default()
+        };
+        incoming.check_compatible(self, &options)
+    }
+
     /// Convert to a compact string representation.
     ///
     /// This is intended for display purposes and not for serialization.
@@ -160,43 +372,134 @@ impl Schema {
         ArrowSchema::from(self).to_compact_string(indent)
     }
 
+    /// Convert to a multi-line string showing the field tree with the Lance
+    /// field ids, one field per line:
+    ///
+    /// ```text
+    /// a: int32 (id=0, nullable=false, storage=default)
+    /// b: struct (id=1, nullable=true, storage=default)
+    ///   f1: string (id=2, nullable=true, storage=default)
+    /// ```
+    ///
+    /// This is intended for diagnostics and not for serialization.
+    pub fn to_debug_string(&self) -> String {
+        let mut out = String::new();
+        let mut to_visit = self.fields.iter().rev().map(|f| (f, 0)).collect::<Vec<_>>();
+        while let Some((field, depth)) = to_visit.pop() {
+            out.push_str(&format!(
+                "{}{}: {} (id={}, nullable={}, storage={})\n",
+                "  ".repeat(depth),
+                field.name,
+                field.logical_type,
+                field.id,
+                field.nullable,
+                field.storage_class
+            ));
+            to_visit.extend(field.children.iter().rev().map(|c| (c, depth + 1)));
+        }
+        out
+    }
+
     /// Given a string column reference, resolve the path of fields
     ///
     /// For example, given a.b.c we will return the fields [a, b, c]
     ///
-    /// Returns None if we can't find a segment at any point
+    /// List (and large list) fields are descended into by using the name of
+    /// the element field as a path segment.  For example, given a column
+    /// `tags: list<item: struct<key, value>>` the path `tags.item.key` resolves
+    /// to the fields [tags, item, key]. Map columns can't be part of a schema
+    /// (converting one from Arrow fails), so there are no map paths.
+    ///
+    /// Returns None if we can't find a segment at any point, see
+    /// [`Self::try_resolve`] for the reason.
     pub fn resolve(&self, column: impl AsRef<str>) -> Option<Vec<&Field>> {
-        let mut split = column.as_ref().split('.').collect::<VecDeque<_>>();
-        let mut fields = Vec::with_capacity(split.len());
-        let first = split.pop_front().unwrap();
-        if let Some(field) = self.field(first) {
-            if field.resolve(&mut split, &mut fields) {
-                Some(fields)
-            } else {
-                None
+        self.try_resolve(column).ok()
+    }
+
+    /// Like [`Self::resolve`], but reports why a column reference could not
+    /// be resolved.
+    pub fn try_resolve(
+        &self,
+        column: impl AsRef<str>,
+    ) -> std::result::Result<Vec<&Field>, ResolveError> {
+        let mut split = column.as_ref().split('.');
+        let first = split.next().unwrap();
+        let mut field = self
+            .field(first)
+            .ok_or_else(|| ResolveError::FieldNotFound {
+                name: first.to_string(),
+            })?;
+        let mut path = first.to_string();
+        let mut fields = vec![field];
+        for name in split {
+            if field.children.is_empty() {
+                return Err(ResolveError::NotNested {
+                    parent: path,
+                    child: name.to_string(),
+                });
             }
-        } else {
-            None
+            field = field
+                .children
+                .iter()
+                .find(|c| c.name == name)
+                .ok_or_else(|| ResolveError::ChildNotFound {
+                    parent: path.clone(),
+                    child: name.to_string(),
+                })?;
+            path.push('.');
+            path.push_str(name);
+            fields.push(field);
         }
+        Ok(fields)
     }
 
-    fn do_project<T: AsRef<str>>(&self, columns: &[T], err_on_missing: bool) -> Result<Self> {
+    /// Columns that are not in the schema are added to `dropped`, unless
+    /// `err_on_missing` is set.
+    fn do_project<T: AsRef<str>>(
+        &self,
+        columns: &[T],
+        err_on_missing: bool,
+        dropped: &mut Vec<String>,
+    ) -> Result<Self> {
         let mut candidates: Vec<Field> = vec![];
+        // Repeated paths are dropped up front so that they never reach
+        // `Field::merge`, which is only meant to combine distinct paths.
+        let mut seen = HashSet::new();
         for col in columns {
+            if !seen.insert(col.as_ref()) {
+                continue;
+            }
             let split = col.as_ref().split('.').collect::<Vec<_>>();
             let first = split[0];
             if let Some(field) = self.field(first) {
-                let projected_field = field.project(&split[1..])?;
+                let mut projected_field = field.project(&split[1..])?;
+                if split.len() > 1 {
+                    // A nested path that doesn't exist leaves empty structs behind
+                    prune_empty_struct_fields(&mut projected_field.children);
+                    if projected_field.children.is_empty() {
+                        if err_on_missing {
+                            return Err(Error::Schema {
+                                message: format!("Column {} does not exist", col.as_ref()),
+                                location: location!(),
+                            });
+                        }
+                        dropped.push(col.as_ref().to_string());
+                        continue;
+                    }
+                }
                 if let Some(candidate_field) = candidates.iter_mut().find(|f| f.name == first) {
                     candidate_field.merge(&projected_field)?;
                 } else {
                     candidates.push(projected_field)
                 }
-            } else if err_on_missing && first != ROW_ID && first != ROW_ADDR {
-                return Err(Error::Schema {
-                    message: format!("Column {} does not exist", col.as_ref()),
-                    location: location!(),
-                });
+            } else if first != ROW_ID && first != ROW_ADDR {
+                if err_on_missing {
+                    return Err(Error::Schema {
+                        message: format!("Column {} does not exist", col.as_ref()),
+                        location: location!(),
+                    });
+                }
+                dropped.push(col.as_ref().to_string());
             }
         }
 
@@ -213,12 +516,59 @@ impl Schema {
     /// let projected = schema.project(&["col1", "col2.sub_col3.field4"])?;
     /// ```
     pub fn project<T: AsRef<str>>(&self, columns: &[T]) -> Result<Self> {
-        self.do_project(columns, true)
+        self.do_project(columns, true, &mut Vec::new())
+    }
+
+    /// Project the columns over the schema, also returning the ids of the
+    /// projected fields in pre-order.
+    ///
+    /// Behaves like [`Self::project`].
+    pub fn project_with_ids<T: AsRef<str>>(&self, columns: &[T]) -> Result<(Self, Vec<i32>)> {
+        let projected = self.do_project(columns, true, &mut Vec::new())?;
+        let ids = projected.fields_pre_order().map(|f| f.id).collect();
+        Ok((projected, ids))
     }
 
     /// Project the columns over the schema, dropping unrecognized columns
     pub fn project_or_drop<T: AsRef<str>>(&self, columns: &[T]) -> Result<Self> {
-        self.do_project(columns, false)
+        self.do_project(columns, false, &mut Vec::new())
+    }
+
+    /// Project the columns over the schema, dropping unrecognized columns.
+    ///
+    /// Also returns the requested columns that were dropped, in the order
+    /// they were requested, so callers can warn about them. The row id and
+    /// row address columns are not part of the schema but are not reported.
+    pub fn project_or_drop_reporting<T: AsRef<str>>(
+        &self,
+        columns: &[T],
+    ) -> Result<(Self, Vec<String>)> {
+        let mut dropped = Vec::new();
+        let projected = self.do_project(columns, false, &mut dropped)?;
+        Ok((projected, dropped))
+    }
+
+    /// Recursively remove struct fields that have no children.
+    ///
+    /// Projections and intersections can leave a struct with all of its
+    /// children removed, which is not a valid column.  If removing an empty
+    /// struct leaves its parent struct empty then the parent is removed too.
+    pub fn prune_empty_structs(&mut self) {
+        prune_empty_struct_fields(&mut self.fields);
+    }
+
+    /// Returns a copy of the schema with fields, at every level, sorted by
+    /// field id.
+    ///
+    /// Ids and metadata are unchanged, so two schemas that only differ in field
+    /// order canonicalize to the same schema.  This is meant for producing a
+    /// deterministic serialization (e.g. for hashing).  The field order of the
+    /// result generally does not match the Arrow column order of the data, so
+    /// it must not be used to read or write data.
+    pub fn canonicalize(&self) -> Self {
+        let mut schema = self.clone();
+        sort_fields_by_id(&mut schema.fields);
+        schema
     }
 
     /// Check that the top level fields don't contain `.` in their names
@@ -235,13 +585,7 @@ impl Schema {
                 ), location: location!(),});
             }
 
-            let column_path = self
-                .field_ancestry_by_id(field.id)
-                .unwrap()
-                .iter()
-                .map(|f| f.name.as_str())
-                .collect::<Vec<_>>()
-                .join(".");
+            let column_path = self.field_path_by_id(field.id).unwrap();
             if !seen_names.insert(column_path.clone()) {
                 return Err(Error::Schema {
                     message: format!(
@@ -273,16 +617,143 @@ impl Schema {
         Ok(())
     }
 
+    /// Check that every leaf field has a distinct dotted path.
+    ///
+    /// Leaves with the same name under different parents, such as `a.f1` and
+    /// `b.f1`, are allowed.  Two leaves with the same full path, for example
+    /// two children named `f1` in the same struct, would make name-based
+    /// projection ambiguous and are rejected.
+    pub fn validate_unique_paths(&self) -> Result<()> {
+        let mut seen_paths = HashSet::new();
+        let mut stack = self
+            .fields
+            .iter()
+            .map(|f| (f.name.clone(), f))
+            .collect::<Vec<_>>();
+        while let Some((path, field)) = stack.pop() {
+            if field.is_leaf() {
+                if !seen_paths.insert(path.clone()) {
+                    return Err(Error::Schema {
+                        message: format!("Leaf field path \"{}\" is not unique", path),
+                        location: location!(),
+                    });
+                }
+            } else {
+                stack.extend(
+                    field
+                        .children
+                        .iter()
+                        .map(|c| (format!("{}.{}", path, c.name), c)),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that no field, at any level, is named [`ROW_ID`] or [`ROW_ADDR`].
+    ///
+    /// These names are reserved for the row id / row address meta columns, so
+    /// user data written to a dataset can't use them. Internal files, such as
+    /// those written by indices, may store a row id column, which is why this
+    /// isn't part of [`Self::validate`].
+    pub fn assert_no_row_id_fields(&self) -> Result<()> {
+        if let Some(field) = self
+            .fields_pre_order()
+            .find(|f| f.name == ROW_ID || f.name == ROW_ADDR)
+        {
+            return Err(Error::Schema {
+                message: format!(
+                    "Field name \"{}\" is reserved and cannot be used in a schema",
+                    field.name
+                ),
+                location: location!(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that every struct field, at any level, has at least one child.
+    ///
+    /// A dataset needs at least one child of a struct to be stored or read
+    /// back. The file format itself supports empty structs, which is why this
+    /// isn't part of [`Self::validate`].
+    pub fn assert_no_empty_structs(&self) -> Result<()> {
+        if let Some(field) = self
+            .fields_pre_order()
+            .find(|f| f.logical_type.is_struct() && f.children.is_empty())
+        {
+            return Err(Error::Schema {
+                message: format!(
+                    "Struct field \"{}\" has no children",
+                    self.field_path_by_id(field.id).unwrap()
+                ),
+                location: location!(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that every field, at any level, has been assigned an id.
+    ///
+    /// Fields added by [`Self::merge`] have an id of -1 until
+    /// [`Self::set_field_id`] is called.
+    pub fn ensure_ids_assigned(&self) -> Result<()> {
+        let mut unassigned = vec![];
+        collect_unassigned_ids(&self.fields, None, &mut unassigned);
+        if !unassigned.is_empty() {
+            return Err(Error::Schema {
+                message: format!(
+                    "Fields have not been assigned an id: [{}]",
+                    unassigned.join(", ")
+                ),
+                location: location!(),
+            });
+        }
+        Ok(())
+    }
+
     /// Intersection between two [`Schema`].
+    ///
+    /// The result has the fields of `self` that are also in `other`, matched
+    /// by name and in the order of `other`. Field ids, like types and metadata,
+    /// always come from `self`; the id in `other` is only used for fields that
+    /// don't have one assigned in `self`. Use
+    /// [`Self::intersection_keep_other_ids`] to take the ids from `other`.
     pub fn intersection(&self, other: &Self) -> Result<Self> {
         self.do_intersection(other, false)
     }
 
     /// Intersection between two [`Schema`], ignoring data types.
+    ///
+    /// Field ids come from `self`, as in [`Self::intersection`].
     pub fn intersection_ignore_types(&self, other: &Self) -> Result<Self> {
         self.do_intersection(other, true)
     }
 
+    /// Intersection between two [`Schema`], with field ids from `other`.
+    ///
+    /// Everything else about the fields still comes from `self`, as in
+    /// [`Self::intersection`].
+    pub fn intersection_keep_other_ids(&self, other: &Self) -> Result<Self> {
+        fn copy_ids(field: &mut Field, other: &Field) {
+            field.id = other.id;
+            field.parent_id = other.parent_id;
+            for child in field.children.iter_mut() {
+                if let Some(other_child) = other.child(&child.name) {
+                    copy_ids(child, other_child);
+                }
+            }
+        }
+
+        let mut intersection = self.intersection(other)?;
+        for field in intersection.fields.iter_mut() {
+            if let Some(other_field) = other.fields.iter().find(|f| f.name == field.name) {
+                copy_ids(field, other_field);
+            }
+        }
+        Ok(intersection)
+    }
+
     fn do_intersection(&self, other: &Self, ignore_types: bool) -> Result<Self> {
         let mut candidates: Vec<Field> = vec![];
         for field in other.fields.iter() {
@@ -305,6 +776,40 @@ impl Schema {
         SchemaFieldIterPreOrder::new(self)
     }
 
+    /// The number of levels of nesting in the schema.
+    ///
+    /// A schema with only top-level primitive fields has depth 1, and each
+    /// level of struct or list children adds one.  An empty schema has depth 0.
+    /// This does not recurse, so it is safe to call on deeply nested schemas.
+    pub fn max_depth(&self) -> usize {
+        let mut max_depth = 0;
+        let mut to_visit = self.fields.iter().map(|f| (f, 1)).collect::<Vec<_>>();
+        while let Some((field, depth)) = to_visit.pop() {
+            max_depth = max_depth.max(depth);
+            to_visit.extend(field.children.iter().map(|c| (c, depth + 1)));
+        }
+        max_depth
+    }
+
+    /// The total number of fields in the schema, including nested fields.
+    pub fn field_count(&self) -> usize {
+        self.fields_pre_order().count()
+    }
+
+    /// The leaf fields of the schema, paired with their dotted paths.
+    ///
+    /// Struct and list parents are skipped. Fields are returned in pre-order.
+    pub fn leaf_fields(&self) -> Vec<(String, &Field)> {
+        self.fields_pre_order()
+            .filter(|field| field.is_leaf())
+            .map(|field| {
+                // The field was just found in the schema, so it has an ancestry.
+                let path = self.field_path_by_id(field.id).unwrap();
+                (path, field)
+            })
+            .collect()
+    }
+
     /// Returns a new schema that only contains the fields in `column_ids`.
     ///
     /// This projection can filter out both top-level and nested fields
@@ -325,6 +830,35 @@ impl Schema {
         }
     }
 
+    /// Project the schema by field ids, ordering top-level fields by the order
+    /// in which their ids first appear in `column_ids`.
+    ///
+    /// The id of a nested field places its top-level ancestor at that position.
+    /// Nested children are kept grouped under their parent, in their original
+    /// order. Duplicate ids are deduped, and unknown ids are ignored.  If a
+    /// parent field id is passed, all of its children are included, as with
+    /// `project_by_ids(column_ids, true)`.
+    pub fn project_by_ids_ordered(&self, column_ids: &[i32]) -> Self {
+        let mut top_level_ids = Vec::new();
+        for id in column_ids {
+            if let Some(ancestry) = self.field_ancestry_by_id(*id) {
+                let top_level_id = ancestry[0].id;
+                if !top_level_ids.contains(&top_level_id) {
+                    top_level_ids.push(top_level_id);
+                }
+            }
+        }
+        let fields = top_level_ids
+            .iter()
+            .filter_map(|id| self.fields.iter().find(|f| f.id == *id))
+            .filter_map(|f| f.project_by_ids(column_ids, true))
+            .collect();
+        Self {
+            fields,
+            metadata: self.metadata.clone(),
+        }
+    }
+
     /// Project the schema by another schema, and preserves field metadata, i.e., Field IDs.
     ///
     /// Parameters
@@ -353,6 +887,20 @@ impl Schema {
         })
     }
 
+    /// Project the schema by a list of Arrow fields, like [`Self::project_by_schema`].
+    ///
+    /// This is useful when the fields come from a plan rather than a full
+    /// [`arrow_schema::Schema`].
+    pub fn project_by_arrow_fields(
+        &self,
+        fields: &[FieldRef],
+        on_missing: OnMissing,
+        on_type_mismatch: OnTypeMismatch,
+    ) -> Result<Self> {
+        let projection = ArrowSchema::new(fields.to_vec());
+        self.project_by_schema(&projection, on_missing, on_type_mismatch)
+    }
+
     /// Exclude the fields from `other` Schema, and returns a new Schema.
     pub fn exclude<T: TryInto<Self> + Debug>(&self, schema: T) -> Result<Self> {
         let other = schema.try_into().map_err(|_| Error::Schema {
@@ -386,6 +934,16 @@ impl Schema {
             .and_then(|c| c.sub_field(&split[1..]))
     }
 
+    /// Get a mutable reference to a field by its dotted path, like [`Self::field`].
+    /// Return `None` if the field does not exist.
+    pub fn field_by_path_mut(&mut self, path: &str) -> Option<&mut Field> {
+        let split = path.split('.').collect::<Vec<_>>();
+        self.fields
+            .iter_mut()
+            .find(|f| f.name == split[0])
+            .and_then(|c| c.sub_field_mut(&split[1..]))
+    }
+
     // TODO: This is not a public API, change to pub(crate) after refactor is done.
     pub fn field_id(&self, column: &str) -> Result<i32> {
         self.field(column)
@@ -450,6 +1008,17 @@ impl Schema {
         None
     }
 
+    /// Get the dotted path (e.g. `a.b.c`) of the field with the given id.
+    pub fn field_path_by_id(&self, id: i32) -> Option<String> {
+        self.field_ancestry_by_id(id).map(|ancestry| {
+            ancestry
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<_>>()
+                .join(".")
+        })
+    }
+
     pub fn mut_field_by_id(&mut self, id: impl Into<i32>) -> Option<&mut Field> {
         let id = id.into();
         for field in self.fields.as_mut_slice() {
@@ -463,35 +1032,158 @@ impl Schema {
         None
     }
 
-    // TODO: pub(crate)
-    /// Get the maximum field id in the schema.
+    /// Remove the field with the given id, and all of its children, in place.
     ///
-    /// Note: When working with Datasets, you should prefer [Manifest::max_field_id()]
-    /// over this method. This method does not take into account the field IDs
-    /// of dropped fields.
-    pub fn max_field_id(&self) -> Option<i32> {
-        self.fields.iter().map(|f| f.max_id()).max()
-    }
+    /// This is a pure schema edit and does not touch any data files.  Returns
+    /// an error if no field has the given id, or if the field is the only child
+    /// of its parent, since that would leave the parent without children.
+    pub fn remove_field_by_id(&mut self, id: i32) -> Result<()> {
+        let ancestry = self.field_ancestry_by_id(id).ok_or_else(|| Error::Schema {
+            message: format!("Field with id {} does not exist", id),
+            location: location!(),
+        })?;
+        let parent_id = match ancestry.as_slice() {
+            [.., parent, _] => {
+                if parent.children.len() == 1 {
+                    return Err(Error::Schema {
+                        message: format!(
+                            "Cannot remove field {} because it is the only child of its parent",
+                            self.field_path_by_id(id).unwrap_or_default()
+                        ),
+                        location: location!(),
+                    });
+                }
+                Some(parent.id)
+            }
+            _ => None,
+        };
 
-    /// Recursively attach set up dictionary values to the dictionary fields.
-    // TODO: pub(crate)
-    pub fn set_dictionary(&mut self, batch: &RecordBatch) -> Result<()> {
-        for field in self.fields.as_mut_slice() {
-            let column = batch
-                .column_by_name(&field.name)
-                .ok_or_else(|| Error::Schema {
-                    message: format!("column '{}' does not exist in the record batch", field.name),
-                    location: location!(),
-                })?;
-            field.set_dictionary(column);
+        match parent_id {
+            Some(parent_id) => {
+                // Ancestry was just found, so the parent must exist.
+                let parent = self.mut_field_by_id(parent_id).unwrap();
+                parent.children.retain(|f| f.id != id);
+            }
+            None => self.fields.retain(|f| f.id != id),
         }
         Ok(())
     }
 
-    /// Walk through the fields and assign a new field id to each field that does
-    /// not have one (e.g. is set to -1)
+    /// Change the data type of the field with the given id, in place.
     ///
-    /// If this schema is on an existing dataset, pass the result of
+    /// The field keeps its id, name, nullability and metadata.  Only leaf
+    /// fields can change type: converting a scalar to a nested type (or back)
+    /// would leave children without a parent, or a parent without children.
+    pub fn replace_field_type(&mut self, id: i32, new_type: &DataType) -> Result<()> {
+        let field = self.field_by_id_mut(id).ok_or_else(|| Error::Schema {
+            message: format!("Field with id {} does not exist", id),
+            location: location!(),
+        })?;
+        if field.logical_type.is_struct() || !field.children.is_empty() || new_type.is_nested() {
+            return Err(Error::Schema {
+                message: format!(
+                    "Cannot change type of field {} from {} to {}, only leaf fields can change type",
+                    field.name,
+                    field.data_type(),
+                    new_type
+                ),
+                location: location!(),
+            });
+        }
+
+        let replacement = Field::try_from(ArrowField::new(
+            field.name.clone(),
+            new_type.clone(),
+            field.nullable,
+        ))?;
+        field.logical_type = replacement.logical_type;
+        field.encoding = replacement.encoding;
+        // Any loaded dictionary belonged to the old type
+        field.dictionary = None;
+        Ok(())
+    }
+
+    /// Replace all children of the struct field with the given id.
+    ///
+    /// The struct keeps its id, name and metadata.  The new children, and
+    /// their own children, are assigned fresh ids with [`Self::set_field_id`],
+    /// starting after the largest id in the schema before the replacement, so
+    /// that the ids of the old children are not reused.
+    pub fn replace_struct_children(
+        &mut self,
+        parent_id: i32,
+        new_children: Vec<Field>,
+    ) -> Result<()> {
+        let max_existing_id = self.max_field_id();
+        let parent = self
+            .field_by_id_mut(parent_id)
+            .ok_or_else(|| Error::Schema {
+                message: format!("Field with id {} does not exist", parent_id),
+                location: location!(),
+            })?;
+        if !parent.logical_type.is_struct() {
+            return Err(Error::Schema {
+                message: format!(
+                    "Cannot replace the children of field {} with type {}, it is not a struct",
+                    parent.name,
+                    parent.data_type()
+                ),
+                location: location!(),
+            });
+        }
+        if new_children.is_empty() {
+            return Err(Error::Schema {
+                message: format!("Struct field {} must have at least one child", parent.name),
+                location: location!(),
+            });
+        }
+
+        parent.children = new_children;
+        parent.children.iter_mut().for_each(|f| f.reset_id());
+        self.set_field_id(max_existing_id);
+        Ok(())
+    }
+
+    /// Rename every leaf field, i.e. every field without children, with `f`.
+    ///
+    /// Fields keep their ids, and parents keep their names. Returns an error,
+    /// leaving the schema unchanged, if the new names collide with a sibling.
+    pub fn map_leaf_names(&mut self, f: impl Fn(&str) -> String) -> Result<()> {
+        let mut fields = self.fields.clone();
+        map_leaf_names_in(&mut fields, None, &f)?;
+        self.fields = fields;
+        Ok(())
+    }
+
+    // TODO: pub(crate)
+    /// Get the maximum field id in the schema.
+    ///
+    /// Note: When working with Datasets, you should prefer [Manifest::max_field_id()]
+    /// over this method. This method does not take into account the field IDs
+    /// of dropped fields.
+    pub fn max_field_id(&self) -> Option<i32> {
+        self.fields.iter().map(|f| f.max_id()).max()
+    }
+
+    /// Recursively attach set up dictionary values to the dictionary fields.
+    // TODO: pub(crate)
+    pub fn set_dictionary(&mut self, batch: &RecordBatch) -> Result<()> {
+        for field in self.fields.as_mut_slice() {
+            let column = batch
+                .column_by_name(&field.name)
+                .ok_or_else(|| Error::Schema {
+                    message: format!("column '{}' does not exist in the record batch", field.name),
+                    location: location!(),
+                })?;
+            field.set_dictionary(column);
+        }
+        Ok(())
+    }
+
+    /// Walk through the fields and assign a new field id to each field that does
+    /// not have one (e.g. is set to -1)
+    ///
+    /// If this schema is on an existing dataset, pass the result of
     /// `Manifest::max_field_id` to `max_existing_id`. If for some reason that
     /// id is lower than the maximum field id in this schema, the field IDs will
     /// be reassigned starting from the maximum field id in this schema.
@@ -511,6 +1203,88 @@ impl Schema {
         self.fields.iter_mut().for_each(|f| f.reset_id());
     }
 
+    /// Replace every field id, at any level, with the id it maps to in
+    /// `mapping`, updating the parent ids to match.
+    ///
+    /// Ids that are not in `mapping` are kept, unless `strict` is set, in
+    /// which case they are an error. It is also an error if the new ids are
+    /// not unique. On error the schema is left unchanged.
+    pub fn remap_field_ids(&mut self, mapping: &HashMap<i32, i32>, strict: bool) -> Result<()> {
+        if strict {
+            if let Some(field) = self
+                .fields_pre_order()
+                .find(|f| !mapping.contains_key(&f.id))
+            {
+                return Err(Error::Schema {
+                    message: format!(
+                        "Field {} (id={}) has no id in the mapping",
+                        self.field_path_by_id(field.id).unwrap(),
+                        field.id
+                    ),
+                    location: location!(),
+                });
+            }
+        }
+
+        let mut fields = self.fields.clone();
+        remap_ids(&mut fields, -1, mapping);
+        let remapped = Self {
+            fields,
+            metadata: self.metadata.clone(),
+        };
+        let mut seen_ids = HashSet::new();
+        if let Some(field) = remapped.fields_pre_order().find(|f| !seen_ids.insert(f.id)) {
+            return Err(Error::Schema {
+                message: format!("Remapping field ids results in duplicate id {}", field.id),
+                location: location!(),
+            });
+        }
+        *self = remapped;
+        Ok(())
+    }
+
+    /// Create a schema from an Arrow schema, taking the field ids from `ids`
+    /// instead of assigning fresh ones.
+    ///
+    /// `ids` maps the dotted path of every field, at any level, to its id
+    /// (e.g. `"b.f1"` for the child `f1` of struct `b`).  Returns an error if
+    /// any field has no entry in `ids` or if the resulting ids are not unique.
+    pub fn from_arrow_with_ids(schema: &ArrowSchema, ids: &HashMap<String, i32>) -> Result<Self> {
+        let mut schema = Self::try_from(schema)?;
+        schema.reset_id();
+        assign_ids_by_path(&mut schema.fields, -1, None, ids);
+        schema.ensure_ids_assigned()?;
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    /// Insert a new top-level field at `index`, shifting the fields after it.
+    ///
+    /// The new field has no id, call [`Self::set_field_id`] to assign one.
+    /// Returns an error, without changing the schema, if `index` is past the
+    /// end of the fields or if a top-level field with the same name exists.
+    pub fn insert_field(&mut self, index: usize, field: ArrowField) -> Result<()> {
+        if index > self.fields.len() {
+            return Err(Error::Schema {
+                message: format!(
+                    "Cannot insert field {} at position {}, the schema only has {} fields",
+                    field.name(),
+                    index,
+                    self.fields.len()
+                ),
+                location: location!(),
+            });
+        }
+        if self.fields.iter().any(|f| &f.name == field.name()) {
+            return Err(Error::Schema {
+                message: format!("Field {} already exists in the schema", field.name()),
+                location: location!(),
+            });
+        }
+        self.fields.insert(index, Field::try_from(&field)?);
+        Ok(())
+    }
+
     /// Create a new schema by adding fields to the end of this schema
     pub fn extend(&mut self, fields: &[ArrowField]) -> Result<()> {
         let new_fields = fields
@@ -533,10 +1307,20 @@ impl Schema {
         }
     }
 
+    /// The union of the metadata of this schema and `other`.
+    ///
+    /// The value from this schema wins when both have the same key.
+    pub fn union_metadata(&self, other: &Self) -> HashMap<String, String> {
+        let mut metadata = other.metadata.clone();
+        metadata.extend(self.metadata.iter().map(|(k, v)| (k.clone(), v.clone())));
+        metadata
+    }
+
     /// Merge this schema from the other schema.
     ///
     /// After merging, the field IDs from `other` schema will be reassigned,
-    /// following the fields in `self`.
+    /// following the fields in `self`. Fields and schema metadata that are in
+    /// both schemas are taken from `self` (see [`Self::union_metadata`]).
     pub fn merge<S: TryInto<Self, Error = Error>>(&self, other: S) -> Result<Self> {
         let mut other: Self = other.try_into()?;
         other.reset_id();
@@ -557,19 +1341,48 @@ impl Schema {
                 merged_fields.push(field.clone());
             }
         }
-        let metadata = self
-            .metadata
-            .iter()
-            .chain(other.metadata.iter())
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect();
         let schema = Self {
             fields: merged_fields,
-            metadata,
+            metadata: self.union_metadata(&other),
         };
         Ok(schema)
     }
 
+    /// Append the top-level fields of `other` after the fields of this schema.
+    ///
+    /// Unlike [`Self::merge`], structs are not merged: a top-level name that
+    /// is in both schemas is an error. The fields of this schema keep their
+    /// ids, while the fields of `other` get new ids after
+    /// [`Self::max_field_id`]. Schema metadata that is in both schemas is
+    /// taken from `self`, as in [`Self::union_metadata`].
+    pub fn concat(&self, other: &Self) -> Result<Self> {
+        if let Some(duplicate) = other
+            .fields
+            .iter()
+            .find(|f| self.fields.iter().any(|sf| sf.name == f.name))
+        {
+            return Err(Error::Schema {
+                message: format!(
+                    "Cannot concatenate schemas, field '{}' is in both",
+                    duplicate.name
+                ),
+                location: location!(),
+            });
+        }
+
+        let mut other = other.clone();
+        other.reset_id();
+        let mut next_id = self.max_field_id().unwrap_or(-1) + 1;
+        for field in other.fields.iter_mut() {
+            field.set_id(-1, &mut next_id);
+        }
+
+        let metadata = self.union_metadata(&other);
+        let mut fields = self.fields.clone();
+        fields.extend(other.fields);
+        Ok(Self { fields, metadata })
+    }
+
     pub fn all_fields_nullable(&self) -> bool {
         SchemaFieldIterPreOrder::new(self).all(|f| f.nullable)
     }
@@ -581,6 +1394,36 @@ impl PartialEq for Schema {
     }
 }
 
+/// Why a column reference could not be resolved, see [`Schema::try_resolve`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    /// There is no top-level field with this name
+    FieldNotFound { name: String },
+    /// The field at path `parent` has no children (it is not a struct or
+    /// list), so `child` can't be nested in it
+    NotNested { parent: String, child: String },
+    /// The field at path `parent` has children, but none named `child`
+    ChildNotFound { parent: String, child: String },
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FieldNotFound { name } => write!(f, "top-level field {} not found", name),
+            Self::NotNested { parent, child } => write!(
+                f,
+                "{} is not a struct so {} can't be nested in it",
+                parent, child
+            ),
+            Self::ChildNotFound { parent, child } => {
+                write!(f, "{} does not have a child named {}", parent, child)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
 impl fmt::Display for Schema {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         for field in self.fields.iter() {
@@ -667,15 +1510,18 @@ pub fn compare_fields(
     expected: &[Field],
     options: &SchemaCompareOptions,
 ) -> bool {
-    if options.allow_missing_if_nullable || options.ignore_field_order {
-        let expected_names = expected
-            .iter()
-            .map(|f| f.name.as_str())
-            .collect::<HashSet<_>>();
-        for field in fields {
-            if !expected_names.contains(field.name.as_str()) {
-                // Extra field
-                return false;
+    if options.allow_missing_if_nullable || options.ignore_field_order || options.allow_extra_fields
+    {
+        if !options.allow_extra_fields {
+            let expected_names = expected
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<HashSet<_>>();
+            for field in fields {
+                if !expected_names.contains(field.name.as_str()) {
+                    // Extra field
+                    return false;
+                }
             }
         }
 
@@ -711,11 +1557,142 @@ pub fn compare_fields(
     }
 }
 
+/// Collect the dotted paths of all fields (recursively) that have an id of -1.
+fn collect_unassigned_ids(fields: &[Field], prefix: Option<&str>, unassigned: &mut Vec<String>) {
+    for field in fields {
+        let path = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, field.name),
+            None => field.name.clone(),
+        };
+        if field.id == -1 {
+            unassigned.push(path.clone());
+        }
+        collect_unassigned_ids(&field.children, Some(&path), unassigned);
+    }
+}
+
+/// Replace the id of every field (recursively) with the one it maps to in
+/// `mapping`, leaving unmapped ids unchanged, and point each field's parent
+/// id at the new id of its parent.
+fn remap_ids(fields: &mut [Field], parent_id: i32, mapping: &HashMap<i32, i32>) {
+    for field in fields {
+        if let Some(id) = mapping.get(&field.id) {
+            field.id = *id;
+        }
+        field.parent_id = parent_id;
+        remap_ids(&mut field.children, field.id, mapping);
+    }
+}
+
+/// Set the id of every field (recursively) to the one of its dotted path in
+/// `ids`, leaving fields without an entry unchanged.
+fn assign_ids_by_path(
+    fields: &mut [Field],
+    parent_id: i32,
+    prefix: Option<&str>,
+    ids: &HashMap<String, i32>,
+) {
+    for field in fields {
+        let path = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, field.name),
+            None => field.name.clone(),
+        };
+        if let Some(id) = ids.get(&path) {
+            field.id = *id;
+        }
+        field.parent_id = parent_id;
+        assign_ids_by_path(&mut field.children, field.id, Some(&path), ids);
+    }
+}
+
+fn map_leaf_names_in(
+    fields: &mut [Field],
+    parent: Option<&str>,
+    f: &impl Fn(&str) -> String,
+) -> Result<()> {
+    for field in fields.iter_mut() {
+        if field.is_leaf() {
+            field.name = f(&field.name);
+        } else {
+            let path = match parent {
+                Some(parent) => format!("{}.{}", parent, field.name),
+                None => field.name.clone(),
+            };
+            map_leaf_names_in(&mut field.children, Some(&path), f)?;
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for field in fields.iter() {
+        if !seen.insert(field.name.as_str()) {
+            let location = match parent {
+                Some(parent) => format!("struct {}", parent),
+                None => "the schema".to_string(),
+            };
+            return Err(Error::Schema {
+                message: format!(
+                    "Renaming leaf fields results in duplicate field {} in {}",
+                    field.name, location
+                ),
+                location: location!(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn sort_fields_by_id(fields: &mut [Field]) {
+    fields.sort_by_key(|f| f.id);
+    for field in fields.iter_mut() {
+        sort_fields_by_id(&mut field.children);
+    }
+}
+
+fn prune_empty_struct_fields(fields: &mut Vec<Field>) {
+    fields.retain_mut(|f| {
+        if !f.logical_type.is_struct() {
+            return true;
+        }
+        prune_empty_struct_fields(&mut f.children);
+        !f.children.is_empty()
+    });
+}
+
+/// Returns a copy of `fields` containing only the fields (recursively) that are
+/// named in `reference`, preserving the order of `fields`.
+///
+/// Returns None if a field in `reference` is missing from `fields`.
+fn prune_fields_to(fields: &[Field], reference: &[Field]) -> Option<Vec<Field>> {
+    if reference
+        .iter()
+        .any(|r| !fields.iter().any(|f| f.name == r.name))
+    {
+        return None;
+    }
+    fields
+        .iter()
+        .filter_map(|f| {
+            let r = reference.iter().find(|r| r.name == f.name)?;
+            if r.children.is_empty() {
+                Some(Some(f.clone()))
+            } else {
+                Some(
+                    prune_fields_to(&f.children, &r.children).map(|children| Field {
+                        children,
+                        ..f.clone()
+                    }),
+                )
+            }
+        })
+        .collect()
+}
+
 pub fn explain_fields_difference(
     fields: &[Field],
     expected: &[Field],
     options: &SchemaCompareOptions,
     path: Option<&str>,
+    categories: &mut HashSet<DiffCategory>,
 ) -> Vec<String> {
     let field_names = fields
         .iter()
@@ -735,11 +1712,15 @@ pub fn explain_fields_difference(
     };
 
     // Check there are no extra fields or missing fields
-    let unexpected_fields = field_names
-        .difference(&expected_names)
-        .cloned()
-        .map(prepend_path)
-        .collect::<Vec<_>>();
+    let unexpected_fields = if options.allow_extra_fields {
+        vec![]
+    } else {
+        field_names
+            .difference(&expected_names)
+            .cloned()
+            .map(prepend_path)
+            .collect::<Vec<_>>()
+    };
     let missing_fields = expected_names.difference(&field_names);
     let missing_fields = if options.allow_missing_if_nullable {
         missing_fields
@@ -764,6 +1745,7 @@ pub fn explain_fields_difference(
             missing_fields.join(", "),
             unexpected_fields.join(", ")
         ));
+        categories.insert(DiffCategory::Names);
     }
 
     // Map the expected fields to position of field
@@ -788,6 +1770,7 @@ pub fn explain_fields_difference(
                 expected_order.join(", "),
                 actual_order.join(", ")
             ));
+            categories.insert(DiffCategory::Order);
         }
     }
 
@@ -795,7 +1778,7 @@ pub fn explain_fields_difference(
     for (expected_field, field_pos) in field_mapping.iter() {
         let field = &fields[*field_pos];
         debug_assert_eq!(field.name, expected_field.name);
-        let field_diffs = field.explain_differences(expected_field, options, path);
+        let field_diffs = field.explain_differences(expected_field, options, path, categories);
         if !field_diffs.is_empty() {
             differences.push(field_diffs.join(", "))
         }
@@ -804,6 +1787,36 @@ pub fn explain_fields_difference(
     differences
 }
 
+fn collect_changed_types(
+    fields: &[Field],
+    other: &[Field],
+    path: Option<&str>,
+    changes: &mut Vec<(String, DataType, DataType)>,
+) {
+    for field in fields {
+        let Some(other_field) = other.iter().find(|f| f.name == field.name) else {
+            continue;
+        };
+        let field_path = if let Some(path) = path {
+            format!("{}.{}", path, field.name)
+        } else {
+            field.name.clone()
+        };
+        if field.is_leaf() || other_field.is_leaf() {
+            if field.logical_type != other_field.logical_type {
+                changes.push((field_path, field.data_type(), other_field.data_type()));
+            }
+        } else {
+            collect_changed_types(
+                &field.children,
+                &other_field.children,
+                Some(&field_path),
+                changes,
+            );
+        }
+    }
+}
+
 fn explain_metadata_difference(
     metadata: &HashMap<String, String>,
     expected: &HashMap<String, String>,
@@ -906,6 +1919,54 @@ impl Projection {
         Ok(self)
     }
 
+    /// Returns an error if the projection does not select any fields (or the
+    /// row id / addr)
+    pub fn require_non_empty(self) -> Result<Self> {
+        if self.is_empty() {
+            return Err(Error::InvalidInput {
+                source: "Projection does not select any columns".into(),
+                location: location!(),
+            });
+        }
+        Ok(self)
+    }
+
+    /// Returns an error if any of the given columns is not selected by the projection
+    ///
+    /// Columns are string references, as in [`Self::union_column`].  A nested column
+    /// is only considered selected if it, and all of its parents, are selected.
+    pub fn require_columns(self, columns: &[&str]) -> Result<Self> {
+        let missing = columns
+            .iter()
+            .filter(|column| {
+                let column = **column;
+                if column == ROW_ID {
+                    !self.with_row_id
+                } else if column == ROW_ADDR {
+                    !self.with_row_addr
+                } else {
+                    self.base
+                        .schema()
+                        .resolve(column)
+                        .map(|fields| !fields.iter().all(|f| self.contains_field_id(f.id)))
+                        .unwrap_or(true)
+                }
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(Error::InvalidInput {
+                source: format!(
+                    "Projection is missing required columns: [{}]",
+                    missing.join(", ")
+                )
+                .into(),
+                location: location!(),
+            });
+        }
+        Ok(self)
+    }
+
     /// True if the projection selects the given field id
     pub fn contains_field_id(&self, id: i32) -> bool {
         self.field_ids.contains(&id)
@@ -916,6 +1977,23 @@ impl Projection {
         !self.field_ids.is_empty()
     }
 
+    /// The storage classes of all fields selected by the projection
+    ///
+    /// Field ids that are not in the base schema are ignored.
+    pub fn storage_classes(&self) -> HashSet<StorageClass> {
+        let schema = self.base.schema();
+        self.field_ids
+            .iter()
+            .filter_map(|id| schema.field_by_id(*id))
+            .map(|field| field.storage_class())
+            .collect()
+    }
+
+    /// True if the projection selects any field with the blob storage class
+    pub fn touches_blob_fields(&self) -> bool {
+        self.storage_classes().contains(&StorageClass::Blob)
+    }
+
     /// Add multiple columns (and their parents) to the projection
     pub fn union_columns(
         mut self,
@@ -928,6 +2006,38 @@ impl Projection {
         Ok(self)
     }
 
+    /// Add multiple columns (and their parents) to the projection, failing if
+    /// any are missing
+    ///
+    /// Unlike [`Self::union_columns`] with [`OnMissing::Error`], which stops at
+    /// the first missing column, this reports every missing column in a single
+    /// error.  Nothing is added to the projection unless all columns exist.
+    pub fn union_columns_checked(
+        self,
+        columns: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Self> {
+        let columns = columns
+            .into_iter()
+            .map(|c| c.as_ref().to_string())
+            .collect::<Vec<_>>();
+        let missing = columns
+            .iter()
+            .filter(|column| {
+                column.as_str() != ROW_ID
+                    && column.as_str() != ROW_ADDR
+                    && self.base.schema().resolve(column.as_str()).is_none()
+            })
+            .map(|column| column.as_str())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(Error::InvalidInput {
+                source: format!("Columns do not exist: [{}]", missing.join(", ")).into(),
+                location: location!(),
+            });
+        }
+        self.union_columns(columns, OnMissing::Error)
+    }
+
     /// Adds all fields from the base schema satisfying a predicate
     pub fn union_predicate(mut self, predicate: impl Fn(&Field) -> bool) -> Self {
         for field in self.base.schema().fields_pre_order() {
@@ -1037,6 +2147,42 @@ impl Projection {
         self
     }
 
+    /// Returns an error if `other` was not created from the same base schema
+    ///
+    /// Field ids are only meaningful relative to their base schema, so
+    /// combining projections over different schemas gives nonsense results.
+    fn check_same_base(&self, other: &Self) -> Result<()> {
+        if Arc::ptr_eq(&self.base, &other.base) || self.base.schema() == other.base.schema() {
+            Ok(())
+        } else {
+            Err(Error::invalid_input(
+                "Cannot combine projections over different base schemas",
+                location!(),
+            ))
+        }
+    }
+
+    /// Like [`Self::intersect`], but fails if the projections have different
+    /// base schemas
+    pub fn try_intersect(self, other: &Self) -> Result<Self> {
+        self.check_same_base(other)?;
+        Ok(self.intersect(other))
+    }
+
+    /// Like [`Self::union_projection`], but fails if the projections have
+    /// different base schemas
+    pub fn try_union_projection(self, other: &Self) -> Result<Self> {
+        self.check_same_base(other)?;
+        Ok(self.union_projection(other))
+    }
+
+    /// Like [`Self::subtract_projection`], but fails if the projections have
+    /// different base schemas
+    pub fn try_subtract_projection(self, other: &Self) -> Result<Self> {
+        self.check_same_base(other)?;
+        Ok(self.subtract_projection(other))
+    }
+
     /// Removes all fields from the projection that are in the given schema
     ///
     /// Fields are only removed if they exist in the base schema, otherwise they
@@ -1091,13 +2237,90 @@ impl Projection {
         }
         Ok(arrow_schema)
     }
+
+    /// Convert the projection into a list of Arrow fields, e.g. for projection
+    /// pushdown in DataFusion.
+    ///
+    /// The fields are the same as those of [`Self::to_arrow_schema`], in the
+    /// same order: the selected top-level fields in schema order, where a
+    /// struct only has its selected children, followed by the row id and the
+    /// row address if they are selected.
+    pub fn to_field_refs(&self) -> Result<Vec<FieldRef>> {
+        let mut fields = self
+            .to_schema()
+            .fields
+            .iter()
+            .map(|f| Arc::new(ArrowField::from(f)))
+            .collect::<Vec<_>>();
+        for (selected, meta_field) in [
+            (self.with_row_id, &*ROW_ID_FIELD),
+            (self.with_row_addr, &*ROW_ADDR_FIELD),
+        ] {
+            if selected {
+                if fields.iter().any(|f| f.name() == meta_field.name()) {
+                    return Err(Error::Schema {
+                        message: format!("Field {} is already in the schema", meta_field.name()),
+                        location: location!(),
+                    });
+                }
+                fields.push(Arc::new(meta_field.clone()));
+            }
+        }
+        Ok(fields)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::sync::Arc;
+/// A selection of columns by name that has not been resolved against a schema.
+///
+/// This records what to select independently of the schema it will be selected
+/// from, e.g. for pass-through reads where the schema is not known yet.  Use
+/// [`ColumnSelection::resolve`] to turn it into a [`Projection`] once the base
+/// schema is known.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnSelection {
+    pub columns: Vec<String>,
+    pub with_row_id: bool,
+    pub with_row_addr: bool,
+}
+
+impl ColumnSelection {
+    pub fn new(columns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            columns: columns.into_iter().map(Into::into).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_row_id(mut self) -> Self {
+        self.with_row_id = true;
+        self
+    }
+
+    pub fn with_row_addr(mut self) -> Self {
+        self.with_row_addr = true;
+        self
+    }
+
+    /// Resolve the selection against `base`, failing if any column does not
+    /// exist in it.
+    pub fn resolve(&self, base: Arc<dyn Projectable>) -> Result<Projection> {
+        let mut projection = Projection::empty(base).union_columns_checked(&self.columns)?;
+        if self.with_row_id {
+            projection = projection.with_row_id();
+        }
+        if self.with_row_addr {
+            projection = projection.with_row_addr();
+        }
+        Ok(projection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
 
     use super::*;
+    use crate::datatypes::{LANCE_STORAGE_CLASS_SCHEMA_META_KEY, LANCE_UNENFORCED_PRIMARY_KEY};
 
     use arrow_schema::{
         DataType, Field as ArrowField, Fields as ArrowFields, Schema as ArrowSchema,
@@ -1135,6 +2358,204 @@ mod tests {
         assert_eq!(ArrowSchema::from(&projected), expected_arrow_schema);
     }
 
+    #[test]
+    fn test_schema_projection_duplicate_paths() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+            ArrowField::new(
+                "l",
+                DataType::List(Arc::new(ArrowField::new(
+                    "item",
+                    DataType::Struct(ArrowFields::from(vec![
+                        ArrowField::new("x", DataType::Int64, true),
+                        ArrowField::new("y", DataType::Int64, true),
+                    ])),
+                    true,
+                ))),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let projected = schema.project(&["b.f1", "b.f1"]).unwrap();
+        assert_eq!(projected, schema.project(&["b.f1"]).unwrap());
+        assert_eq!(projected.fields.len(), 1);
+        assert_eq!(projected.fields[0].children.len(), 1);
+
+        let projected = schema.project(&["l.item.x", "a", "l.item.x"]).unwrap();
+        assert_eq!(projected, schema.project(&["l.item.x", "a"]).unwrap());
+        let expected_arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new(
+                "l",
+                DataType::List(Arc::new(ArrowField::new(
+                    "item",
+                    DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                        "x",
+                        DataType::Int64,
+                        true,
+                    )])),
+                    true,
+                ))),
+                true,
+            ),
+            ArrowField::new("a", DataType::Int32, false),
+        ]);
+        assert_eq!(ArrowSchema::from(&projected), expected_arrow_schema);
+
+        // Distinct paths into the same list element are still merged
+        let projected = schema.project(&["l.item.x", "l.item.y"]).unwrap();
+        assert_eq!(projected.fields.len(), 1);
+        assert_eq!(projected.fields[0].children[0].children.len(), 2);
+    }
+
+    #[test]
+    fn test_from_arrow_with_ids() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+            ArrowField::new(
+                "l",
+                DataType::List(Arc::new(ArrowField::new("item", DataType::Int64, true))),
+                true,
+            ),
+        ]);
+        let mut ids = HashMap::from([
+            ("a".to_string(), 10),
+            ("b".to_string(), 3),
+            ("b.f1".to_string(), 7),
+            ("b.f2".to_string(), 4),
+            ("l".to_string(), 0),
+            ("l.item".to_string(), 12),
+        ]);
+
+        let schema = Schema::from_arrow_with_ids(&arrow_schema, &ids).unwrap();
+        for field in schema.fields_pre_order() {
+            let path = schema.field_path_by_id(field.id).unwrap();
+            assert_eq!(ids[&path], field.id, "{}", path);
+        }
+        assert_eq!(schema.field("b.f1").unwrap().parent_id, 3);
+        assert_eq!(schema.field("l.item").unwrap().parent_id, 0);
+        assert_eq!(schema.field("a").unwrap().parent_id, -1);
+        assert_eq!(ArrowSchema::from(&schema), arrow_schema);
+
+        // Every field needs an id
+        ids.remove("b.f2");
+        let err = Schema::from_arrow_with_ids(&arrow_schema, &ids).unwrap_err();
+        assert!(err.to_string().contains("b.f2"), "{}", err);
+
+        // Ids must be unique
+        ids.insert("b.f2".to_string(), 7);
+        let err = Schema::from_arrow_with_ids(&arrow_schema, &ids).unwrap_err();
+        assert!(err.to_string().contains("Duplicate field id 7"), "{}", err);
+    }
+
+    #[test]
+    fn test_field_by_path_mut() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+        ]);
+        let mut schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let field = schema.field_by_path_mut("b.f1").unwrap();
+        assert_eq!(field.name, "f1");
+        field
+            .metadata
+            .insert("key".to_string(), "value".to_string());
+
+        assert_eq!(
+            schema.field("b.f1").unwrap().metadata,
+            HashMap::from([("key".to_string(), "value".to_string())])
+        );
+        assert!(schema.field("b.f2").unwrap().metadata.is_empty());
+        assert!(schema.field("b").unwrap().metadata.is_empty());
+
+        assert!(schema.field_by_path_mut("a").is_some());
+        assert!(schema.field_by_path_mut("b.f3").is_none());
+        assert!(schema.field_by_path_mut("c").is_none());
+    }
+
+    #[test]
+    fn test_check_append_compatible() {
+        let schema = Schema::try_from(&ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("b", DataType::Utf8, true),
+            ArrowField::new(
+                "c",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Int64, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                false,
+            ),
+        ]))
+        .unwrap();
+        let c = |children: Vec<ArrowField>| {
+            ArrowField::new("c", DataType::Struct(ArrowFields::from(children)), false)
+        };
+        let f1 = ArrowField::new("f1", DataType::Int64, true);
+        let f2 = ArrowField::new("f2", DataType::Boolean, false);
+
+        // Matching
+        let incoming = ArrowSchema::from(&schema);
+        schema.check_append_compatible(&incoming).unwrap();
+
+        // Reordered, with different nullability and metadata
+        let incoming = ArrowSchema::new(vec![
+            c(vec![f2.clone(), f1.clone()]),
+            ArrowField::new("b", DataType::Utf8, false),
+            ArrowField::new("a", DataType::Int32, true),
+        ])
+        .with_metadata(HashMap::from([("k".to_string(), "v".to_string())]));
+        schema.check_append_compatible(&incoming).unwrap();
+
+        // Missing nullable fields are fine
+        let incoming = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            c(vec![f2.clone()]),
+        ]);
+        schema.check_append_compatible(&incoming).unwrap();
+
+        // Missing non-nullable fields are not
+        let incoming = ArrowSchema::new(vec![
+            ArrowField::new("b", DataType::Utf8, true),
+            c(vec![f1.clone(), f2.clone()]),
+        ]);
+        let err = schema.check_append_compatible(&incoming).unwrap_err();
+        assert!(matches!(err, Error::SchemaMismatch { .. }), "{}", err);
+        assert!(err.to_string().contains("missing=[a]"), "{}", err);
+
+        let incoming = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            c(vec![f1]),
+        ]);
+        let err = schema.check_append_compatible(&incoming).unwrap_err();
+        assert!(matches!(err, Error::SchemaMismatch { .. }), "{}", err);
+        assert!(err.to_string().contains("missing=[c.f2]"), "{}", err);
+    }
+
     #[test]
     fn test_schema_project_by_ids() {
         let arrow_schema = ArrowSchema::new(vec![
@@ -1271,6 +2692,52 @@ mod tests {
         assert_eq!(ArrowSchema::from(&projected), projection);
     }
 
+    #[test]
+    fn test_schema_project_by_arrow_fields() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let fields = vec![
+            Arc::new(ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "f2",
+                    DataType::Boolean,
+                    false,
+                )])),
+                true,
+            )),
+            Arc::new(ArrowField::new("a", DataType::Int32, false)),
+        ];
+        let projected = schema
+            .project_by_arrow_fields(&fields, OnMissing::Error, OnTypeMismatch::TakeSelf)
+            .unwrap();
+        assert_eq!(ArrowSchema::from(&projected), ArrowSchema::new(fields));
+        // Field ids come from this schema
+        assert_eq!(projected.field("b.f2").unwrap().id, 3);
+        assert_eq!(projected.field("a").unwrap().id, 0);
+
+        let missing = vec![Arc::new(ArrowField::new("x", DataType::Int32, false))];
+        assert!(schema
+            .project_by_arrow_fields(&missing, OnMissing::Error, OnTypeMismatch::TakeSelf)
+            .is_err());
+        let projected = schema
+            .project_by_arrow_fields(&missing, OnMissing::Ignore, OnTypeMismatch::TakeSelf)
+            .unwrap();
+        assert!(projected.fields.is_empty());
+    }
+
     #[test]
     fn test_get_nested_field() {
         let arrow_schema = ArrowSchema::new(vec![ArrowField::new(
@@ -1289,7 +2756,33 @@ mod tests {
     }
 
     #[test]
-    fn test_exclude_fields() {
+    fn test_field_path_by_id() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "c",
+                    DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                        "d",
+                        DataType::Utf8,
+                        true,
+                    )])),
+                    true,
+                )])),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let id = schema.field("b.c.d").unwrap().id;
+        assert_eq!(schema.field_path_by_id(id), Some("b.c.d".to_string()));
+        assert_eq!(schema.field_path_by_id(0), Some("a".to_string()));
+        assert_eq!(schema.field_path_by_id(100), None);
+    }
+
+    #[test]
+    fn test_project_with_ids() {
         let arrow_schema = ArrowSchema::new(vec![
             ArrowField::new("a", DataType::Int32, false),
             ArrowField::new(
@@ -1305,26 +2798,1341 @@ mod tests {
         ]);
         let schema = Schema::try_from(&arrow_schema).unwrap();
 
-        let projection = schema.project(&["a", "b.f2", "b.f3"]).unwrap();
-        let excluded = schema.exclude(&projection).unwrap();
+        let (projected, ids) = schema.project_with_ids(&["b.f1", "b.f3", "c"]).unwrap();
+        assert_eq!(projected, schema.project(&["b.f1", "b.f3", "c"]).unwrap());
+        assert_eq!(
+            ids,
+            projected
+                .fields_pre_order()
+                .map(|f| f.id)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(ids, vec![1, 2, 4, 5]);
 
-        let expected_arrow_schema = ArrowSchema::new(vec![
+        assert!(schema.project_with_ids(&["d"]).is_err());
+    }
+
+    #[test]
+    fn test_try_resolve() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
             ArrowField::new(
                 "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let fields = schema.try_resolve("b.f2").unwrap();
+        assert_eq!(fields.iter().map(|f| f.id).collect::<Vec<_>>(), vec![1, 3]);
+
+        let err = schema.try_resolve("c.f1").unwrap_err();
+        assert_eq!(
+            err,
+            ResolveError::FieldNotFound {
+                name: "c".to_string()
+            }
+        );
+        assert_eq!(err.to_string(), "top-level field c not found");
+
+        let err = schema.try_resolve("a.x").unwrap_err();
+        assert_eq!(
+            err,
+            ResolveError::NotNested {
+                parent: "a".to_string(),
+                child: "x".to_string()
+            }
+        );
+        assert!(err.to_string().contains("a is not a struct"), "{}", err);
+
+        let err = schema.try_resolve("b.f1.x").unwrap_err();
+        assert_eq!(
+            err,
+            ResolveError::NotNested {
+                parent: "b.f1".to_string(),
+                child: "x".to_string()
+            }
+        );
+
+        let err = schema.try_resolve("b.f3").unwrap_err();
+        assert_eq!(
+            err,
+            ResolveError::ChildNotFound {
+                parent: "b".to_string(),
+                child: "f3".to_string()
+            }
+        );
+
+        // resolve is a wrapper that drops the reason
+        assert_eq!(schema.resolve("b.f2").unwrap().len(), 2);
+        assert!(schema.resolve("b.f3").is_none());
+    }
+
+    #[test]
+    fn test_resolve_list_element() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "tags",
+                DataType::List(Arc::new(ArrowField::new(
+                    "item",
+                    DataType::Struct(ArrowFields::from(vec![
+                        ArrowField::new("key", DataType::Utf8, true),
+                        ArrowField::new("value", DataType::Utf8, true),
+                    ])),
+                    true,
+                ))),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let fields = schema.resolve("tags.item.key").unwrap();
+        assert_eq!(
+            fields.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(),
+            vec!["tags", "item", "key"]
+        );
+        assert_eq!(fields[2].data_type(), DataType::Utf8);
+
+        let fields = schema.resolve("tags.item").unwrap();
+        assert_eq!(fields.len(), 2);
+
+        // Invalid paths resolve to None
+        assert!(schema.resolve("tags.key").is_none());
+        assert!(schema.resolve("tags.item.missing").is_none());
+        assert!(schema.resolve("a.item").is_none());
+
+        // The element subfield can be projected
+        let projection = Projection::empty(Arc::new(schema.clone()))
+            .union_column("tags.item.value", OnMissing::Error)
+            .unwrap();
+        let expected_arrow_schema = ArrowSchema::new(vec![ArrowField::new(
+            "tags",
+            DataType::List(Arc::new(ArrowField::new(
+                "item",
                 DataType::Struct(ArrowFields::from(vec![ArrowField::new(
-                    "f1",
+                    "value",
                     DataType::Utf8,
                     true,
                 )])),
                 true,
-            ),
-            ArrowField::new("c", DataType::Float64, false),
-        ]);
-        assert_eq!(ArrowSchema::from(&excluded), expected_arrow_schema);
+            ))),
+            true,
+        )]);
+        assert_eq!(
+            ArrowSchema::from(&projection.to_schema()),
+            expected_arrow_schema
+        );
+
+        // Map columns are not supported, so there is nothing to resolve
+        let entries = ArrowField::new(
+            "entries",
+            DataType::Struct(ArrowFields::from(vec![
+                ArrowField::new("key", DataType::Utf8, false),
+                ArrowField::new("value", DataType::Utf8, true),
+            ])),
+            false,
+        );
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new(
+            "attrs",
+            DataType::Map(Arc::new(entries), false),
+            true,
+        )]);
+        let err = Schema::try_from(&arrow_schema).unwrap_err();
+        assert!(err.to_string().contains("Unsupported data type"), "{}", err);
+    }
+
+    #[test]
+    fn test_projection_to_field_refs() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+        ]);
+        let schema = Arc::new(Schema::try_from(&arrow_schema).unwrap());
+
+        let projection = Projection::empty(schema.clone())
+            .union_columns(["b.f2", "a"], OnMissing::Error)
+            .unwrap();
+        let fields = projection.to_field_refs().unwrap();
+        let names = fields.iter().map(|f| f.name().as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(
+            fields[1].data_type(),
+            &DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                "f2",
+                DataType::Boolean,
+                false,
+            )]))
+        );
+
+        let fields = projection.with_row_id().to_field_refs().unwrap();
+        let names = fields.iter().map(|f| f.name().as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "b", ROW_ID]);
+        assert_eq!(fields[2].as_ref(), &*ROW_ID_FIELD);
+
+        let projection = Projection::full(schema).with_row_id().with_row_addr();
+        assert_eq!(
+            ArrowSchema::new(projection.to_field_refs().unwrap()),
+            projection.to_arrow_schema().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_projection_require() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+        ]);
+        let schema = Arc::new(Schema::try_from(&arrow_schema).unwrap());
+
+        // Typo'd column with OnMissing::Ignore leads to an empty projection
+        let err = Projection::empty(schema.clone())
+            .union_column("aa", OnMissing::Ignore)
+            .unwrap()
+            .require_non_empty()
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+
+        Projection::empty(schema.clone())
+            .with_row_id()
+            .require_non_empty()
+            .unwrap();
+
+        let projection = Projection::empty(schema.clone())
+            .union_columns(["a", "b.f1"], OnMissing::Error)
+            .unwrap()
+            .with_row_id();
+        let projection = projection
+            .require_columns(&["a", "b", "b.f1", ROW_ID])
+            .unwrap();
+
+        let err = projection
+            .require_columns(&["a", "b.f2", "c", ROW_ADDR])
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        let message = err.to_string();
+        assert!(message.contains("b.f2, c, _rowaddr"), "{}", message);
+    }
+
+    #[test]
+    fn test_projection_storage_classes() {
+        let blob_metadata = HashMap::from([(
+            LANCE_STORAGE_CLASS_SCHEMA_META_KEY.to_string(),
+            "blob".to_string(),
+        )]);
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("b", DataType::LargeBinary, true).with_metadata(blob_metadata),
+        ]);
+        let schema = Arc::new(Schema::try_from(&arrow_schema).unwrap());
+
+        let empty = Projection::empty(schema.clone()).with_row_id();
+        assert!(empty.storage_classes().is_empty());
+        assert!(!empty.touches_blob_fields());
+
+        let default_only = Projection::empty(schema.clone())
+            .union_column("a", OnMissing::Error)
+            .unwrap();
+        assert_eq!(
+            default_only.storage_classes(),
+            HashSet::from([StorageClass::Default])
+        );
+        assert!(!default_only.touches_blob_fields());
+
+        let blob_only = Projection::empty(schema.clone())
+            .union_column("b", OnMissing::Error)
+            .unwrap();
+        assert_eq!(
+            blob_only.storage_classes(),
+            HashSet::from([StorageClass::Blob])
+        );
+        assert!(blob_only.touches_blob_fields());
+
+        let full = Projection::full(schema);
+        assert_eq!(
+            full.storage_classes(),
+            HashSet::from([StorageClass::Default, StorageClass::Blob])
+        );
+        assert!(full.touches_blob_fields());
+    }
+
+    #[test]
+    fn test_projection_checked_combinators() {
+        let schema_a = Arc::new(
+            Schema::try_from(&ArrowSchema::new(vec![
+                ArrowField::new("a", DataType::Int32, false),
+                ArrowField::new("b", DataType::Int32, false),
+            ]))
+            .unwrap(),
+        );
+        let schema_x = Arc::new(
+            Schema::try_from(&ArrowSchema::new(vec![
+                ArrowField::new("x", DataType::Utf8, false),
+                ArrowField::new("y", DataType::Utf8, false),
+            ]))
+            .unwrap(),
+        );
+
+        let a = Projection::empty(schema_a.clone())
+            .union_column("a", OnMissing::Error)
+            .unwrap();
+        let b = Projection::empty(schema_a.clone())
+            .union_column("b", OnMissing::Error)
+            .unwrap();
+        let x = Projection::empty(schema_x.clone())
+            .union_column("x", OnMissing::Error)
+            .unwrap();
+
+        // Same base schema
+        let ab = a.clone().try_union_projection(&b).unwrap();
+        assert!(ab.contains_field_id(0) && ab.contains_field_id(1));
+        let only_a = ab.clone().try_subtract_projection(&b).unwrap();
+        assert!(only_a.contains_field_id(0) && !only_a.contains_field_id(1));
+        assert!(ab.try_intersect(&a).unwrap().contains_field_id(0));
+
+        // Different base schemas, even though the field ids overlap
+        for result in [
+            a.clone().try_intersect(&x),
+            a.clone().try_union_projection(&x),
+            a.try_subtract_projection(&x),
+        ] {
+            let err = result.unwrap_err();
+            assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_union_columns_checked() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("b", DataType::Utf8, true),
+        ]);
+        let schema = Arc::new(Schema::try_from(&arrow_schema).unwrap());
+
+        let err = Projection::empty(schema.clone())
+            .union_columns_checked(["a", "aa", "bb"])
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        let message = err.to_string();
+        assert!(message.contains("aa"), "{}", message);
+        assert!(message.contains("bb"), "{}", message);
+
+        let projection = Projection::empty(schema.clone())
+            .union_columns_checked(["a", ROW_ID])
+            .unwrap();
+        assert!(projection.contains_field_id(0));
+        assert!(!projection.contains_field_id(1));
+        assert!(projection.with_row_id);
+        assert_eq!(schema.fields.len(), 2);
+    }
+
+    #[test]
+    fn test_column_selection_resolve() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+        ]);
+        let schema = Arc::new(Schema::try_from(&arrow_schema).unwrap());
+
+        let selection = ColumnSelection::new(["a", "b.f2"]).with_row_id();
+        let resolved = selection.resolve(schema.clone()).unwrap();
+        let expected = Projection::empty(schema.clone())
+            .union_columns(["a", "b.f2"], OnMissing::Error)
+            .unwrap()
+            .with_row_id();
+        assert_eq!(resolved.field_ids, expected.field_ids);
+        assert_eq!(resolved.with_row_id, expected.with_row_id);
+        assert_eq!(resolved.with_row_addr, expected.with_row_addr);
+        assert_eq!(resolved.to_schema(), expected.to_schema());
+
+        let err = ColumnSelection::new(["a", "c"])
+            .resolve(schema)
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_changed_types() {
+        let old = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Float32, false),
+                ])),
+                true,
+            ),
+        ]);
+        let old = Schema::try_from(&old).unwrap();
+
+        // Different order and nullability are ignored
+        let new = ArrowSchema::new(vec![
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f2", DataType::Float64, true),
+                    ArrowField::new("f1", DataType::Utf8, false),
+                ])),
+                true,
+            ),
+            ArrowField::new("a", DataType::Int32, true),
+        ]);
+        let new = Schema::try_from(&new).unwrap();
+
+        assert_eq!(
+            old.changed_types(&new),
+            vec![("b.f2".to_string(), DataType::Float32, DataType::Float64)]
+        );
+        assert!(old.changed_types(&old).is_empty());
+    }
+
+    #[test]
+    fn test_is_subset_of() {
+        let full = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        let full = Schema::try_from(&full).unwrap();
+        let options = SchemaCompareOptions::default();
+
+        let subset = ArrowSchema::new(vec![
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "f2",
+                    DataType::Boolean,
+                    false,
+                )])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        let subset = Schema::try_from(&subset).unwrap();
+        assert!(subset.is_subset_of(&full, &options));
+        assert!(full.is_subset_of(&full, &options));
+        assert!(!full.is_subset_of(&subset, &options));
+
+        let type_mismatch = ArrowSchema::new(vec![ArrowField::new("c", DataType::Float32, false)]);
+        let type_mismatch = Schema::try_from(&type_mismatch).unwrap();
+        assert!(!type_mismatch.is_subset_of(&full, &options));
+
+        let extra_field = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("d", DataType::Int32, false),
+        ]);
+        let extra_field = Schema::try_from(&extra_field).unwrap();
+        assert!(!extra_field.is_subset_of(&full, &options));
+    }
+
+    #[test]
+    fn test_prune_empty_structs() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        // Intersecting with a sibling that isn't one of b's children empties b
+        let other = ArrowSchema::new(vec![
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "f3",
+                    DataType::Utf8,
+                    true,
+                )])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        let other = Schema::try_from(&other).unwrap();
+        let mut intersection = schema.intersection(&other).unwrap();
+        assert_eq!(
+            intersection.field("b").unwrap().data_type(),
+            DataType::Struct(ArrowFields::empty())
+        );
+        intersection.prune_empty_structs();
+        let expected = ArrowSchema::new(vec![ArrowField::new("c", DataType::Float64, false)]);
+        assert_eq!(ArrowSchema::from(&intersection), expected);
+
+        // Projecting a non-existent child does not emit an empty struct
+        let projected = schema.project_or_drop(&["b.f3", "c"]).unwrap();
+        assert_eq!(ArrowSchema::from(&projected), expected);
+        assert!(schema.project(&["b.f3", "c"]).is_err());
+    }
+
+    #[test]
+    fn test_project_or_drop_reporting() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "f1",
+                    DataType::Utf8,
+                    true,
+                )])),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let (projected, dropped) = schema.project_or_drop_reporting(&["a", "typo"]).unwrap();
+        assert_eq!(projected, schema.project(&["a"]).unwrap());
+        assert_eq!(dropped, vec!["typo".to_string()]);
+
+        // Missing nested paths are reported, while row id columns are not
+        let (projected, dropped) = schema
+            .project_or_drop_reporting(&["b.f1", "b.f2", ROW_ID, "typo", "typo"])
+            .unwrap();
+        assert_eq!(projected, schema.project(&["b.f1"]).unwrap());
+        assert_eq!(dropped, vec!["b.f2".to_string(), "typo".to_string()]);
+    }
+
+    #[test]
+    fn test_map_leaf_names() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("Id", DataType::Int32, false),
+            ArrowField::new(
+                "Info",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("FirstName", DataType::Utf8, true),
+                    ArrowField::new("LastName", DataType::Utf8, true),
+                ])),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let mut renamed = schema.clone();
+        renamed.map_leaf_names(|name| name.to_lowercase()).unwrap();
+        let names = renamed
+            .fields_pre_order()
+            .map(|f| (f.name.as_str(), f.id))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![("id", 0), ("Info", 1), ("firstname", 2), ("lastname", 3)]
+        );
+    }
+
+    #[test]
+    fn test_map_leaf_names_collision() {
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new(
+            "info",
+            DataType::Struct(ArrowFields::from(vec![
+                ArrowField::new("name", DataType::Utf8, true),
+                ArrowField::new("Name", DataType::Utf8, true),
+            ])),
+            true,
+        )]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let mut renamed = schema.clone();
+        let err = renamed
+            .map_leaf_names(|name| name.to_lowercase())
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("duplicate field name in struct info"),
+            "{}",
+            err
+        );
+        assert_eq!(renamed, schema);
+    }
+
+    #[test]
+    fn test_project_through_scalar() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "f1",
+                    DataType::Utf8,
+                    true,
+                )])),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let err = schema.project(&["a.b"]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("cannot select nested field 'b' because 'a' is not a struct"),
+            "{}",
+            err
+        );
+        // Not a missing column, so it isn't dropped either
+        let err = schema.project_or_drop(&["b.f1.x"]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("cannot select nested field 'x' because 'f1' is not a struct"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_metadata_builders() {
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new("a", DataType::Int32, false)]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let options = SchemaCompareOptions {
+            compare_metadata: true,
+            ..Default::default()
+        };
+
+        let updated = schema
+            .clone()
+            .with_metadata(HashMap::from([("k1".to_string(), "v1".to_string())]))
+            .with_metadata_entry("k2", "v2");
+        assert_eq!(updated.fields, schema.fields);
+        assert_eq!(
+            updated.metadata,
+            HashMap::from([
+                ("k1".to_string(), "v1".to_string()),
+                ("k2".to_string(), "v2".to_string()),
+            ])
+        );
+        assert!(!updated.compare_with_options(&schema, &options));
+
+        let reverted = updated
+            .without_metadata_key("k1")
+            .without_metadata_key("k2");
+        assert!(reverted.compare_with_options(&schema, &options));
+    }
+
+    #[test]
+    fn test_to_arrow_with_row_id() {
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new("a", DataType::Int32, false)]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let with_row_id = schema.to_arrow_with_row_id(false).unwrap();
+        assert_eq!(
+            with_row_id,
+            ArrowSchema::new(vec![
+                ArrowField::new("a", DataType::Int32, false),
+                ROW_ID_FIELD.clone(),
+            ])
+        );
+        assert_eq!(with_row_id.field(1).data_type(), &DataType::UInt64);
+
+        let with_row_addr = schema.to_arrow_with_row_id(true).unwrap();
+        assert_eq!(
+            with_row_addr
+                .fields()
+                .iter()
+                .map(|f| f.name().as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", ROW_ID, ROW_ADDR]
+        );
+        assert_eq!(with_row_addr.field(2), &*ROW_ADDR_FIELD);
+
+        // Same as the projection equivalent
+        let projection = Projection::full(Arc::new(schema))
+            .with_row_id()
+            .with_row_addr();
+        assert_eq!(projection.to_arrow_schema().unwrap(), with_row_addr);
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        // Same fields and ids, in a different order at every level
+        let mut reordered = schema.clone();
+        reordered.fields.reverse();
+        for field in reordered.fields.iter_mut() {
+            field.children.reverse();
+        }
+        assert_ne!(schema, reordered);
+
+        let canonical = schema.canonicalize();
+        let reordered_canonical = reordered.canonicalize();
+        assert_eq!(canonical, reordered_canonical);
+        assert_eq!(canonical, schema);
+        assert_eq!(
+            reordered_canonical
+                .fields_pre_order()
+                .map(|f| (f.name.as_str(), f.id))
+                .collect::<Vec<_>>(),
+            vec![("a", 0), ("b", 1), ("f1", 2), ("f2", 3), ("c", 4)]
+        );
+    }
+
+    #[test]
+    fn test_validate_reserved_row_id_names() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(ROW_ID, DataType::UInt64, true),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let err = schema.assert_no_row_id_fields().unwrap_err();
+        assert!(err.to_string().contains(ROW_ID), "{}", err);
+        // Files written by indices store a row id column
+        schema.validate().unwrap();
+
+        // Nested fields are checked as well
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new(
+            "b",
+            DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                ROW_ADDR,
+                DataType::UInt64,
+                true,
+            )])),
+            true,
+        )]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let err = schema.assert_no_row_id_fields().unwrap_err();
+        assert!(err.to_string().contains(ROW_ADDR), "{}", err);
+    }
+
+    #[test]
+    fn test_assert_no_empty_structs() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Struct(ArrowFields::empty()), true),
+                ])),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let err = schema.assert_no_empty_structs().unwrap_err();
+        assert!(matches!(err, Error::Schema { .. }), "{}", err);
+        assert!(err.to_string().contains("\"b.f2\""), "{}", err);
+        // Files can store empty structs
+        schema.validate().unwrap();
+
+        let mut schema = schema;
+        schema.prune_empty_structs();
+        schema.assert_no_empty_structs().unwrap();
+
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new(
+            "b",
+            DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                "f1",
+                DataType::Utf8,
+                true,
+            )])),
+            true,
+        )]);
+        Schema::try_from(&arrow_schema)
+            .unwrap()
+            .assert_no_empty_structs()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_validate_unique_paths() {
+        let struct_field = |name: &str, children: Vec<&str>| {
+            ArrowField::new(
+                name,
+                DataType::Struct(
+                    children
+                        .into_iter()
+                        .map(|c| ArrowField::new(c, DataType::Int32, true))
+                        .collect(),
+                ),
+                true,
+            )
+        };
+
+        // The same leaf name under different parents is fine
+        let arrow_schema = ArrowSchema::new(vec![
+            struct_field("a", vec!["f1", "f2"]),
+            struct_field("b", vec!["f1"]),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        schema.validate_unique_paths().unwrap();
+
+        // Two children with the same name in one struct are ambiguous
+        let arrow_schema = ArrowSchema::new(vec![
+            struct_field("a", vec!["f1", "f2"]),
+            struct_field("b", vec!["f1", "f1"]),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let err = schema.validate_unique_paths().unwrap_err();
+        assert!(matches!(err, Error::Schema { .. }), "{}", err);
+        assert!(err.to_string().contains("\"b.f1\""), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_dictionaries_set() {
+        use arrow_array::{ArrayRef, DictionaryArray, Int32Array, StringArray, UInt32Array};
+
+        let dictionary_type =
+            DataType::Dictionary(Box::new(DataType::UInt32), Box::new(DataType::Utf8));
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("d", dictionary_type, true),
+        ]);
+        let mut schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let err = schema.validate_dictionaries_set().unwrap_err();
+        assert!(matches!(err, Error::Schema { .. }), "{}", err);
+        assert!(err.to_string().contains(": d"), "{}", err);
+
+        let keys = UInt32Array::from_iter_values(vec![0, 1]);
+        let values: ArrayRef = Arc::new(StringArray::from_iter_values(["x", "y"]));
+        let batch = RecordBatch::try_new(
+            Arc::new(arrow_schema),
+            vec![
+                Arc::new(Int32Array::from_iter_values(vec![1, 2])),
+                Arc::new(DictionaryArray::new(keys, values)),
+            ],
+        )
+        .unwrap();
+        schema.set_dictionary(&batch).unwrap();
+        schema.validate_dictionaries_set().unwrap();
+
+        // Schemas without dictionaries have nothing to check
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new("a", DataType::Int32, false)]);
+        Schema::try_from(&arrow_schema)
+            .unwrap()
+            .validate_dictionaries_set()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_exclude_fields() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                    ArrowField::new("f3", DataType::Float32, false),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let projection = schema.project(&["a", "b.f2", "b.f3"]).unwrap();
+        let excluded = schema.exclude(&projection).unwrap();
+
+        let expected_arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "f1",
+                    DataType::Utf8,
+                    true,
+                )])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        assert_eq!(ArrowSchema::from(&excluded), expected_arrow_schema);
+    }
+
+    #[test]
+    fn test_intersection() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                    ArrowField::new("f3", DataType::Float32, false),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+            ArrowField::new("d", DataType::Utf8, false),
+        ]);
+        let other = Schema::try_from(&arrow_schema).unwrap();
+
+        let intersection = schema.intersection(&other).unwrap();
+        let actual: ArrowSchema = (&intersection).into();
+
+        let expected = ArrowSchema::new(vec![
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        assert_eq!(actual, expected);
+
+        // Field ids come from the schema being intersected, not the other one
+        assert_eq!(schema.field_ids(), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(other.field_ids(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(intersection.field_ids(), vec![1, 2, 3, 5]);
+        assert_eq!(intersection.field("b.f1").unwrap().parent_id, 1);
+
+        let intersection = schema.intersection_keep_other_ids(&other).unwrap();
+        assert_eq!(ArrowSchema::from(&intersection), expected);
+        assert_eq!(intersection.field_ids(), vec![0, 1, 2, 3]);
+        assert_eq!(intersection.field("b.f1").unwrap().parent_id, 0);
+
+        let schema_with_list_struct = ArrowSchema::new(vec![ArrowField::new(
+            "struct_list",
+            DataType::List(Arc::new(ArrowField::new(
+                "item",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ))),
+            true,
+        )]);
+        let schema_with_list_struct = Schema::try_from(&schema_with_list_struct).unwrap();
+
+        let with_missing_field = schema_with_list_struct.project_by_ids(&[1, 3], false);
+        let intersection = schema_with_list_struct
+            .intersection_ignore_types(&with_missing_field)
+            .unwrap();
+        assert_eq!(intersection, with_missing_field);
+        let intersection = with_missing_field
+            .intersection_ignore_types(&schema_with_list_struct)
+            .unwrap();
+        assert_eq!(intersection, with_missing_field);
+    }
+
+    #[test]
+    fn test_merge_schemas_and_assign_field_ids() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                    ArrowField::new("f3", DataType::Float32, false),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        assert_eq!(schema.max_field_id(), Some(5));
+
+        let to_merged_arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("d", DataType::Int32, false),
+            ArrowField::new("e", DataType::Binary, false),
+        ]);
+        let to_merged = Schema::try_from(&to_merged_arrow_schema).unwrap();
+        // It is already assigned with field ids.
+        assert_eq!(to_merged.max_field_id(), Some(1));
+
+        let mut merged = schema.merge(&to_merged).unwrap();
+        assert_eq!(merged.max_field_id(), Some(5));
+
+        let field = merged.field("d").unwrap();
+        assert_eq!(field.id, -1);
+        let field = merged.field("e").unwrap();
+        assert_eq!(field.id, -1);
+
+        // Need to explicitly assign field ids. Testing we can pass a larger
+        // field id to set_field_id.
+        merged.set_field_id(Some(7));
+        let field = merged.field("d").unwrap();
+        assert_eq!(field.id, 8);
+        let field = merged.field("e").unwrap();
+        assert_eq!(field.id, 9);
+        assert_eq!(merged.max_field_id(), Some(9));
+    }
+
+    #[test]
+    fn test_merge_metadata_precedence() {
+        let schema = Schema::try_from(&ArrowSchema::new(vec![ArrowField::new(
+            "a",
+            DataType::Int32,
+            false,
+        )]))
+        .unwrap()
+        .with_metadata_entry("shared", "self")
+        .with_metadata_entry("self-only", "1");
+        let other = Schema::try_from(&ArrowSchema::new(vec![ArrowField::new(
+            "b",
+            DataType::Int32,
+            false,
+        )]))
+        .unwrap()
+        .with_metadata_entry("shared", "other")
+        .with_metadata_entry("other-only", "2");
+
+        let expected = HashMap::from([
+            ("shared".to_string(), "self".to_string()),
+            ("self-only".to_string(), "1".to_string()),
+            ("other-only".to_string(), "2".to_string()),
+        ]);
+        assert_eq!(schema.union_metadata(&other), expected);
+        assert_eq!(schema.merge(other).unwrap().metadata, expected);
+    }
+
+    #[test]
+    fn test_fields_pre_order_flat() {
+        let arrow_schema = ArrowSchema::new(
+            (0..1000)
+                .map(|i| ArrowField::new(format!("c{}", i), DataType::Int32, true))
+                .collect::<Vec<_>>(),
+        );
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        assert!(matches!(
+            SchemaFieldIterPreOrder::new(&schema),
+            SchemaFieldIterPreOrder::Flat(_)
+        ));
+
+        let fast = schema.fields_pre_order().collect::<Vec<_>>();
+        let general = SchemaFieldIterPreOrder::new_nested(&schema).collect::<Vec<_>>();
+        assert_eq!(fast.len(), 1000);
+        assert_eq!(fast, general);
+
+        // A single nested field disables the fast path
+        let mut nested = arrow_schema.fields().iter().cloned().collect::<Vec<_>>();
+        nested.push(Arc::new(ArrowField::new(
+            "s",
+            DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                "f",
+                DataType::Int32,
+                true,
+            )])),
+            true,
+        )));
+        let schema = Schema::try_from(&ArrowSchema::new(nested)).unwrap();
+        let ids = schema.fields_pre_order().map(|f| f.id).collect::<Vec<_>>();
+        assert_eq!(ids, (0..1002).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_retain() {
+        let tagged = HashMap::from([("tag".to_string(), "v".to_string())]);
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false).with_metadata(tagged.clone()),
+            ArrowField::new("b", DataType::Int32, false),
+            ArrowField::new(
+                "c",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("c1", DataType::Int32, true),
+                    ArrowField::new("c2", DataType::Int32, true).with_metadata(tagged.clone()),
+                ])),
+                true,
+            )
+            .with_metadata(tagged.clone()),
+            ArrowField::new(
+                "d",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("d1", DataType::Int32, true).with_metadata(tagged),
+                    ArrowField::new("d2", DataType::Int32, true),
+                ])),
+                true,
+            ),
+        ])
+        .with_metadata(HashMap::from([("k".to_string(), "v".to_string())]));
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let retained = schema.retain(|f| f.metadata.contains_key("tag"));
+        // A matching struct keeps all of its children, while a non-matching
+        // struct is dropped even if one of its children matches.
+        assert_eq!(
+            retained
+                .fields_pre_order()
+                .map(|f| (f.name.as_str(), f.id))
+                .collect::<Vec<_>>(),
+            vec![("a", 0), ("c", 2), ("c1", 3), ("c2", 4)]
+        );
+        assert_eq!(retained.field("c.c2").unwrap().metadata["tag"], "v");
+        assert_eq!(retained.metadata, schema.metadata);
+
+        assert!(schema.retain(|_| false).fields.is_empty());
+        assert_eq!(schema.retain(|_| true), schema);
+    }
+
+    #[test]
+    fn test_to_debug_string() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new(
+                        "f2",
+                        DataType::List(Arc::new(ArrowField::new("item", DataType::Int64, true))),
+                        false,
+                    ),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::LargeBinary, true).with_metadata(HashMap::from([(
+                LANCE_STORAGE_CLASS_SCHEMA_META_KEY.to_string(),
+                "blob".to_string(),
+            )])),
+        ]);
+        let mut schema = Schema::try_from(&arrow_schema).unwrap();
+        schema.mut_field_by_id(4).unwrap().id = 10;
+
+        assert_eq!(
+            schema.to_debug_string(),
+            "a: int32 (id=0, nullable=false, storage=default)\n\
+             b: struct (id=1, nullable=true, storage=default)\n\
+             \x20 f1: string (id=2, nullable=true, storage=default)\n\
+             \x20 f2: list (id=3, nullable=false, storage=default)\n\
+             \x20   item: int64 (id=10, nullable=true, storage=default)\n\
+             c: large_binary (id=5, nullable=true, storage=blob)\n"
+        );
+    }
+
+    #[test]
+    fn test_storage_class_leaf_counts() {
+        let blob_metadata = HashMap::from([(
+            LANCE_STORAGE_CLASS_SCHEMA_META_KEY.to_string(),
+            "blob".to_string(),
+        )]);
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new(
+                        "f2",
+                        DataType::List(Arc::new(ArrowField::new("item", DataType::Int64, true))),
+                        false,
+                    ),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::LargeBinary, true).with_metadata(blob_metadata.clone()),
+            ArrowField::new("d", DataType::LargeBinary, true).with_metadata(blob_metadata),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        // Structs and lists are not leaves, but the list items are
+        assert_eq!(
+            schema.storage_class_leaf_counts(),
+            HashMap::from([(StorageClass::Default, 3), (StorageClass::Blob, 2)])
+        );
+        assert_eq!(
+            schema
+                .retain_storage_class(StorageClass::Default)
+                .storage_class_leaf_counts(),
+            HashMap::from([(StorageClass::Default, 3)])
+        );
+        assert!(Schema::default().storage_class_leaf_counts().is_empty());
+    }
+
+    #[test]
+    fn test_remap_field_ids() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Int64, true),
+                ])),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let mapping = HashMap::from([(0, 10), (1, 11), (2, 12), (3, 13)]);
+
+        let mut remapped = schema.clone();
+        remapped.remap_field_ids(&mapping, true).unwrap();
+        assert_eq!(remapped.field_ids(), vec![10, 11, 12, 13]);
+        assert_eq!(remapped.field_by_id(12).unwrap().name, "f1");
+        assert_eq!(remapped.field_by_id(12).unwrap().parent_id, 11);
+        assert_eq!(remapped.field_path_by_id(13).unwrap(), "b.f2");
+        assert!(remapped.field_by_id(0).is_none());
+        remapped.validate().unwrap();
+
+        // Unmapped ids are kept, unless the remapping is strict
+        let partial = HashMap::from([(1, 11), (2, 12), (3, 13)]);
+        let err = schema.clone().remap_field_ids(&partial, true).unwrap_err();
+        assert!(err.to_string().contains("Field a (id=0)"), "{}", err);
+        let mut remapped = schema.clone();
+        remapped.remap_field_ids(&partial, false).unwrap();
+        assert_eq!(remapped.field_ids(), vec![0, 11, 12, 13]);
+
+        // The new ids must be unique
+        let mut remapped = schema.clone();
+        let err = remapped
+            .remap_field_ids(&HashMap::from([(0, 3)]), false)
+            .unwrap_err();
+        assert!(err.to_string().contains("duplicate id 3"), "{}", err);
+        assert_eq!(remapped.field_ids(), schema.field_ids());
+    }
+
+    #[test]
+    fn test_ensure_ids_assigned() {
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new("a", DataType::Int32, false)]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        schema.ensure_ids_assigned().unwrap();
+
+        let other = ArrowSchema::new(vec![ArrowField::new(
+            "b",
+            DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                "f1",
+                DataType::Utf8,
+                true,
+            )])),
+            true,
+        )]);
+        let mut merged = schema.merge(&other).unwrap();
+        let err = merged.ensure_ids_assigned().unwrap_err();
+        assert!(matches!(err, Error::Schema { .. }), "{}", err);
+        assert!(err.to_string().contains("[b, b.f1]"), "{}", err);
+
+        merged.set_field_id(None);
+        merged.ensure_ids_assigned().unwrap();
+    }
+
+    #[test]
+    fn test_max_depth_and_field_count() {
+        assert_eq!(Schema::default().max_depth(), 0);
+        assert_eq!(Schema::default().field_count(), 0);
+
+        let flat = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("b", DataType::Utf8, true),
+        ]);
+        let flat = Schema::try_from(&flat).unwrap();
+        assert_eq!(flat.max_depth(), 1);
+        assert_eq!(flat.field_count(), 2);
+
+        let nested = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+        ]);
+        let nested = Schema::try_from(&nested).unwrap();
+        assert_eq!(nested.max_depth(), 2);
+        assert_eq!(nested.field_count(), 4);
+    }
+
+    #[test]
+    fn test_leaf_fields() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new(
+                        "f2",
+                        DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                            "g",
+                            DataType::Boolean,
+                            false,
+                        )])),
+                        false,
+                    ),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let leaves = schema
+            .leaf_fields()
+            .into_iter()
+            .map(|(path, field)| (path, field.id))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            leaves,
+            vec![
+                ("a".to_string(), 0),
+                ("b.f1".to_string(), 2),
+                ("b.f2.g".to_string(), 4),
+                ("c".to_string(), 5),
+            ]
+        );
+        assert!(Schema::default().leaf_fields().is_empty());
     }
 
     #[test]
-    fn test_intersection() {
+    fn test_project_by_ids_ordered() {
         let arrow_schema = ArrowSchema::new(vec![
             ArrowField::new("a", DataType::Int32, false),
             ArrowField::new(
@@ -1332,15 +4140,43 @@ mod tests {
                 DataType::Struct(ArrowFields::from(vec![
                     ArrowField::new("f1", DataType::Utf8, true),
                     ArrowField::new("f2", DataType::Boolean, false),
-                    ArrowField::new("f3", DataType::Float32, false),
                 ])),
                 true,
             ),
             ArrowField::new("c", DataType::Float64, false),
         ]);
         let schema = Schema::try_from(&arrow_schema).unwrap();
+        let names = |schema: &Schema| {
+            schema
+                .fields_pre_order()
+                .map(|f| f.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // Reversed ids reverse the top-level fields, but children stay in order.
+        let projected = schema.project_by_ids_ordered(&[4, 3, 2, 1, 0]);
+        assert_eq!(names(&projected), vec!["c", "b", "f1", "f2", "a"]);
+        assert_eq!(projected.field_ids(), vec![4, 1, 2, 3, 0]);
+
+        // A nested id places its parent, and only that child is kept.
+        let projected = schema.project_by_ids_ordered(&[4, 3, 0]);
+        assert_eq!(names(&projected), vec!["c", "b", "f2", "a"]);
+
+        // Duplicate and unknown ids are ignored.
+        let projected = schema.project_by_ids_ordered(&[4, 42, 0, 4]);
+        assert_eq!(names(&projected), vec!["c", "a"]);
+
+        // Matches project_by_ids in the original order.
+        assert_eq!(
+            schema.project_by_ids_ordered(&[0, 1, 4]),
+            schema.project_by_ids(&[0, 1, 4], true)
+        );
+    }
 
+    #[test]
+    fn test_remove_field_by_id() {
         let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
             ArrowField::new(
                 "b",
                 DataType::Struct(ArrowFields::from(vec![
@@ -1350,13 +4186,79 @@ mod tests {
                 true,
             ),
             ArrowField::new("c", DataType::Float64, false),
-            ArrowField::new("d", DataType::Utf8, false),
         ]);
-        let other = Schema::try_from(&arrow_schema).unwrap();
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        assert_eq!(schema.field_ids(), vec![0, 1, 2, 3, 4]);
+
+        // Remove a leaf
+        let mut removed_leaf = schema.clone();
+        removed_leaf.remove_field_by_id(2).unwrap();
+        assert_eq!(removed_leaf.field_ids(), vec![0, 1, 3, 4]);
+        assert_eq!(removed_leaf.field_path_by_id(3).unwrap(), "b.f2");
+
+        // Removing the last child of a struct would leave it empty.
+        let err = removed_leaf.remove_field_by_id(3).unwrap_err();
+        assert!(matches!(err, Error::Schema { .. }), "{}", err);
+        assert!(err.to_string().contains("b.f2"), "{}", err);
+        assert_eq!(removed_leaf.field_ids(), vec![0, 1, 3, 4]);
+
+        // Remove a struct parent, which removes its children too.
+        let mut removed_parent = schema.clone();
+        removed_parent.remove_field_by_id(1).unwrap();
+        assert_eq!(removed_parent.field_ids(), vec![0, 4]);
+        assert!(removed_parent.field_by_id(2).is_none());
+        assert!(removed_parent.field_by_id(3).is_none());
+        assert_eq!(removed_parent.metadata, schema.metadata);
+
+        // Unknown id
+        let mut unknown = schema.clone();
+        let err = unknown.remove_field_by_id(42).unwrap_err();
+        assert!(matches!(err, Error::Schema { .. }), "{}", err);
+        assert_eq!(unknown, schema);
+    }
 
-        let actual: ArrowSchema = (&schema.intersection(&other).unwrap()).into();
+    #[test]
+    fn test_insert_field() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        let mut schema = Schema::try_from(&arrow_schema).unwrap();
 
-        let expected = ArrowSchema::new(vec![
+        schema
+            .insert_field(1, ArrowField::new("b", DataType::Utf8, true))
+            .unwrap();
+        assert_eq!(schema.field("b").unwrap().id, -1);
+        schema.set_field_id(None);
+        schema.validate().unwrap();
+        let fields = schema
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.id))
+            .collect::<Vec<_>>();
+        assert_eq!(fields, vec![("a", 0), ("b", 2), ("c", 1)]);
+
+        // Duplicate names and positions past the end are rejected
+        let err = schema
+            .insert_field(0, ArrowField::new("c", DataType::Utf8, true))
+            .unwrap_err();
+        assert!(matches!(err, Error::Schema { .. }), "{}", err);
+        assert!(schema
+            .insert_field(4, ArrowField::new("d", DataType::Utf8, true))
+            .is_err());
+        assert_eq!(schema.fields.len(), 3);
+
+        // Inserting at the end appends
+        schema
+            .insert_field(3, ArrowField::new("d", DataType::Utf8, true))
+            .unwrap();
+        assert_eq!(schema.fields[3].name, "d");
+    }
+
+    #[test]
+    fn test_replace_struct_children() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
             ArrowField::new(
                 "b",
                 DataType::Struct(ArrowFields::from(vec![
@@ -1364,79 +4266,172 @@ mod tests {
                     ArrowField::new("f2", DataType::Boolean, false),
                 ])),
                 true,
-            ),
+            )
+            .with_metadata(HashMap::from([("k".to_string(), "v".to_string())])),
             ArrowField::new("c", DataType::Float64, false),
         ]);
-        assert_eq!(actual, expected);
+        let mut schema = Schema::try_from(&arrow_schema).unwrap();
+        let b_id = schema.field("b").unwrap().id;
 
-        let schema_with_list_struct = ArrowSchema::new(vec![ArrowField::new(
-            "struct_list",
-            DataType::List(Arc::new(ArrowField::new(
-                "item",
-                DataType::Struct(ArrowFields::from(vec![
-                    ArrowField::new("f1", DataType::Utf8, true),
-                    ArrowField::new("f2", DataType::Boolean, false),
-                ])),
+        let new_children = vec![
+            Field::try_from(ArrowField::new("g1", DataType::Int64, true)).unwrap(),
+            Field::try_from(ArrowField::new(
+                "g2",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "h",
+                    DataType::Utf8,
+                    true,
+                )])),
                 true,
-            ))),
-            true,
-        )]);
-        let schema_with_list_struct = Schema::try_from(&schema_with_list_struct).unwrap();
-
-        let with_missing_field = schema_with_list_struct.project_by_ids(&[1, 3], false);
-        let intersection = schema_with_list_struct
-            .intersection_ignore_types(&with_missing_field)
-            .unwrap();
-        assert_eq!(intersection, with_missing_field);
-        let intersection = with_missing_field
-            .intersection_ignore_types(&schema_with_list_struct)
-            .unwrap();
-        assert_eq!(intersection, with_missing_field);
+            ))
+            .unwrap(),
+        ];
+        schema.replace_struct_children(b_id, new_children).unwrap();
+        schema.validate().unwrap();
+
+        let b = schema.field("b").unwrap();
+        assert_eq!(b.id, b_id);
+        assert_eq!(b.metadata["k"], "v");
+        let children = b
+            .children
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(children, vec!["g1", "g2"]);
+        // The old children had ids 2 and 3, and c has id 4
+        assert_eq!(schema.field("b.g1").unwrap().id, 5);
+        assert_eq!(schema.field("b.g2").unwrap().id, 6);
+        assert_eq!(schema.field("b.g2.h").unwrap().id, 7);
+        assert_eq!(schema.field("b.g2.h").unwrap().parent_id, 6);
+        assert_eq!(schema.field("b.g1").unwrap().parent_id, b_id);
+
+        // Only structs can have their children replaced
+        let new_child = Field::try_from(ArrowField::new("x", DataType::Int32, true)).unwrap();
+        let err = schema
+            .replace_struct_children(0, vec![new_child.clone()])
+            .unwrap_err();
+        assert!(matches!(err, Error::Schema { .. }), "{}", err);
+        assert!(schema.replace_struct_children(b_id, vec![]).is_err());
+        assert!(schema
+            .replace_struct_children(100, vec![new_child])
+            .is_err());
     }
 
     #[test]
-    fn test_merge_schemas_and_assign_field_ids() {
+    fn test_replace_field_type() {
         let arrow_schema = ArrowSchema::new(vec![
             ArrowField::new("a", DataType::Int32, false),
             ArrowField::new(
                 "b",
                 DataType::Struct(ArrowFields::from(vec![
-                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f1", DataType::Utf8, true)
+                        .with_metadata(HashMap::from([("k".to_string(), "v".to_string())])),
                     ArrowField::new("f2", DataType::Boolean, false),
-                    ArrowField::new("f3", DataType::Float32, false),
                 ])),
                 true,
             ),
-            ArrowField::new("c", DataType::Float64, false),
         ]);
         let schema = Schema::try_from(&arrow_schema).unwrap();
 
-        assert_eq!(schema.max_field_id(), Some(5));
+        // Dictionary encode a nested string column
+        let dict_type = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+        let mut dict_encoded = schema.clone();
+        dict_encoded.replace_field_type(2, &dict_type).unwrap();
+        let field = dict_encoded.field_by_id(2).unwrap();
+        assert_eq!(field.data_type(), dict_type);
+        assert_eq!(field.name, "f1");
+        assert_eq!(field.parent_id, 1);
+        assert!(field.nullable);
+        assert_eq!(field.metadata, schema.field_by_id(2).unwrap().metadata);
+        assert_eq!(dict_encoded.field_ids(), schema.field_ids());
+        assert_eq!(dict_encoded.field_path_by_id(2).unwrap(), "b.f1");
+
+        // Scalar to struct would need new children
+        let struct_type = DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+            "x",
+            DataType::Utf8,
+            true,
+        )]));
+        let mut unchanged = schema.clone();
+        let err = unchanged.replace_field_type(2, &struct_type).unwrap_err();
+        assert!(matches!(err, Error::Schema { .. }), "{}", err);
+        assert_eq!(unchanged, schema);
+
+        // Struct to scalar would orphan the children
+        let err = unchanged
+            .replace_field_type(1, &DataType::Utf8)
+            .unwrap_err();
+        assert!(matches!(err, Error::Schema { .. }), "{}", err);
+        assert_eq!(unchanged, schema);
+
+        // Unknown id
+        let err = unchanged
+            .replace_field_type(42, &DataType::Utf8)
+            .unwrap_err();
+        assert!(matches!(err, Error::Schema { .. }), "{}", err);
+    }
 
-        let to_merged_arrow_schema = ArrowSchema::new(vec![
-            ArrowField::new("d", DataType::Int32, false),
-            ArrowField::new("e", DataType::Binary, false),
+    #[test]
+    fn test_concat() {
+        let left = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "f1",
+                    DataType::Utf8,
+                    true,
+                )])),
+                true,
+            ),
         ]);
-        let to_merged = Schema::try_from(&to_merged_arrow_schema).unwrap();
-        // It is already assigned with field ids.
-        assert_eq!(to_merged.max_field_id(), Some(1));
-
-        let mut merged = schema.merge(&to_merged).unwrap();
-        assert_eq!(merged.max_field_id(), Some(5));
-
-        let field = merged.field("d").unwrap();
-        assert_eq!(field.id, -1);
-        let field = merged.field("e").unwrap();
-        assert_eq!(field.id, -1);
+        let left = Schema::try_from(&left)
+            .unwrap()
+            .with_metadata_entry("shared", "left")
+            .with_metadata_entry("left", "1");
+        let right = ArrowSchema::new(vec![
+            ArrowField::new(
+                "c",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "f1",
+                    DataType::Float32,
+                    true,
+                )])),
+                true,
+            ),
+            ArrowField::new("d", DataType::Boolean, false),
+        ]);
+        let right = Schema::try_from(&right)
+            .unwrap()
+            .with_metadata_entry("shared", "right");
+        assert_eq!(left.field_ids(), vec![0, 1, 2]);
+        assert_eq!(right.field_ids(), vec![0, 1, 2]);
+
+        let concatenated = left.concat(&right).unwrap();
+        let expected = ArrowSchema::new(
+            ArrowSchema::from(&left)
+                .fields()
+                .iter()
+                .chain(ArrowSchema::from(&right).fields().iter())
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(ArrowSchema::from(&concatenated).fields(), expected.fields());
+        assert_eq!(concatenated.field_ids(), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(concatenated.field("c.f1").unwrap().parent_id, 3);
+        assert_eq!(concatenated.field("d").unwrap().parent_id, -1);
+        assert_eq!(
+            concatenated.metadata,
+            HashMap::from([
+                ("shared".to_string(), "left".to_string()),
+                ("left".to_string(), "1".to_string()),
+            ])
+        );
 
-        // Need to explicitly assign field ids. Testing we can pass a larger
-        // field id to set_field_id.
-        merged.set_field_id(Some(7));
-        let field = merged.field("d").unwrap();
-        assert_eq!(field.id, 8);
-        let field = merged.field("e").unwrap();
-        assert_eq!(field.id, 9);
-        assert_eq!(merged.max_field_id(), Some(9));
+        // Structs with the same name are not merged
+        let err = left.concat(&left.project(&["b"]).unwrap()).unwrap_err();
+        assert!(matches!(err, Error::Schema { .. }), "{}", err);
+        assert!(err.to_string().contains("'b'"), "{}", err);
     }
 
     #[test]
@@ -1605,6 +4600,225 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_explain_difference_list_of_struct() {
+        let list_of_struct = |f2_type: DataType| {
+            let item = ArrowField::new(
+                "item",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", f2_type, true),
+                ])),
+                true,
+            );
+            let arrow_schema = ArrowSchema::new(vec![ArrowField::new(
+                "l",
+                DataType::List(Arc::new(item)),
+                true,
+            )]);
+            Schema::try_from(&arrow_schema).unwrap()
+        };
+        let expected = list_of_struct(DataType::Float32);
+        let mismatched = list_of_struct(DataType::Int32);
+
+        // The list element is part of the path of the mismatched subfield
+        let difference = mismatched
+            .explain_difference(&expected, &SchemaCompareOptions::default())
+            .unwrap();
+        assert!(
+            difference.contains("`l.item` had mismatched children"),
+            "{}",
+            difference
+        );
+        assert!(
+            difference.contains("`l.item.f2` should have type float but type was int32"),
+            "{}",
+            difference
+        );
+    }
+
+    #[test]
+    fn test_difference_categories() {
+        let expected = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("b", DataType::Utf8, false),
+        ]);
+        let expected = Schema::try_from(&expected).unwrap();
+
+        // Only nullability differs
+        let nullable = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, true),
+            ArrowField::new("b", DataType::Utf8, false),
+        ]);
+        let nullable = Schema::try_from(&nullable).unwrap();
+        assert_eq!(
+            nullable.difference_categories(&expected, &SchemaCompareOptions::default()),
+            HashSet::from([DiffCategory::Nullability])
+        );
+        let options = SchemaCompareOptions {
+            compare_nullability: crate::datatypes::NullabilityComparison::Ignore,
+            ..Default::default()
+        };
+        assert!(nullable
+            .difference_categories(&expected, &options)
+            .is_empty());
+
+        // Type and order differ
+        let mismatched = ArrowSchema::new(vec![
+            ArrowField::new("b", DataType::Utf8, false),
+            ArrowField::new("a", DataType::Int64, false),
+        ]);
+        let mismatched = Schema::try_from(&mismatched).unwrap();
+        assert_eq!(
+            mismatched.difference_categories(&expected, &SchemaCompareOptions::default()),
+            HashSet::from([DiffCategory::Order, DiffCategory::Types])
+        );
+
+        assert!(expected
+            .difference_categories(&expected, &SchemaCompareOptions::default())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_compare_ignore_field_metadata() {
+        let metadata = HashMap::from([("foo".to_string(), "bar".to_string())]);
+        let expected = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false).with_metadata(metadata),
+            ArrowField::new("b", DataType::Utf8, false),
+        ]);
+        let expected = Schema::try_from(&expected).unwrap();
+        let actual = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("b", DataType::Utf8, false),
+        ]);
+        let actual = Schema::try_from(&actual).unwrap();
+
+        let options = SchemaCompareOptions {
+            compare_metadata: true,
+            ..Default::default()
+        };
+        assert!(!actual.compare_with_options(&expected, &options));
+        assert_eq!(
+            actual.explain_difference(&expected, &options),
+            Some("metadata for `a` did not match expected metadata".to_string())
+        );
+
+        let options = SchemaCompareOptions {
+            compare_metadata: true,
+            ignore_field_metadata: true,
+            ..Default::default()
+        };
+        assert!(actual.compare_with_options(&expected, &options));
+        assert_eq!(actual.explain_difference(&expected, &options), None);
+
+        // Schema metadata is still compared
+        let with_schema_metadata = actual.clone().with_metadata_entry("key", "value");
+        assert!(!with_schema_metadata.compare_with_options(&actual, &options));
+    }
+
+    #[test]
+    fn test_strict_eq() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("b", DataType::Utf8, true),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        assert!(schema.strict_eq(&schema.clone()));
+
+        // Only the schema metadata differs
+        let mut other = schema.clone();
+        other
+            .metadata
+            .insert("key".to_string(), "value".to_string());
+        assert_eq!(schema, other);
+        assert!(!schema.strict_eq(&other));
+
+        // Only a field id differs
+        let mut other = schema.clone();
+        other.mut_field_by_id(1).unwrap().id = 5;
+        assert!(!schema.strict_eq(&other));
+        assert!(schema.compare_with_options(&other, &SchemaCompareOptions::default()));
+    }
+
+    #[test]
+    fn test_compare_allow_extra_fields() {
+        let expected = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "f1",
+                    DataType::Utf8,
+                    true,
+                )])),
+                true,
+            ),
+        ]);
+        let expected = Schema::try_from(&expected).unwrap();
+
+        let extra_column = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "f1",
+                    DataType::Utf8,
+                    true,
+                )])),
+                true,
+            ),
+            ArrowField::new("c", DataType::Float64, false),
+        ]);
+        let extra_column = Schema::try_from(&extra_column).unwrap();
+
+        let extra_child = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+        ]);
+        let extra_child = Schema::try_from(&extra_child).unwrap();
+
+        let strict = SchemaCompareOptions::default();
+        assert!(!extra_column.compare_with_options(&expected, &strict));
+        assert_eq!(
+            extra_column.explain_difference(&expected, &strict),
+            Some("fields did not match, missing=[], unexpected=[c]".to_string())
+        );
+        assert!(!extra_child.compare_with_options(&expected, &strict));
+        assert!(extra_child
+            .explain_difference(&expected, &strict)
+            .unwrap()
+            .contains("unexpected=[b.f2]"));
+
+        let allow_extra = SchemaCompareOptions {
+            allow_extra_fields: true,
+            ..Default::default()
+        };
+        assert!(extra_column.compare_with_options(&expected, &allow_extra));
+        assert_eq!(
+            extra_column.explain_difference(&expected, &allow_extra),
+            None
+        );
+        assert!(extra_child.compare_with_options(&expected, &allow_extra));
+        assert_eq!(
+            extra_child.explain_difference(&expected, &allow_extra),
+            None
+        );
+
+        // Missing fields are still reported
+        assert!(!expected.compare_with_options(&extra_column, &allow_extra));
+        assert!(expected
+            .explain_difference(&extra_column, &allow_extra)
+            .unwrap()
+            .contains("missing=[c]"));
+    }
+
     #[test]
     fn test_schema_difference_subschema() {
         let expected = ArrowSchema::new(vec![
@@ -1841,6 +5055,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_schema_reserved_and_user_metadata() {
+        let arrow_schema = ArrowSchema::new_with_metadata(
+            vec![
+                ArrowField::new("a", DataType::Int32, false).with_metadata(HashMap::from([(
+                    LANCE_UNENFORCED_PRIMARY_KEY.to_owned(),
+                    "true".to_owned(),
+                )])),
+                ArrowField::new("b", DataType::Utf8, true),
+            ],
+            HashMap::from([
+                ("lance-schema:future-setting".to_owned(), "x".to_owned()),
+                ("owner".to_owned(), "analytics".to_owned()),
+                ("lance-schema".to_owned(), "not reserved".to_owned()),
+            ]),
+        );
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        assert_eq!(
+            schema.reserved_metadata(),
+            HashMap::from([("lance-schema:future-setting".to_owned(), "x".to_owned())])
+        );
+        assert_eq!(
+            schema.user_metadata(),
+            HashMap::from([
+                ("owner".to_owned(), "analytics".to_owned()),
+                ("lance-schema".to_owned(), "not reserved".to_owned()),
+            ])
+        );
+
+        let pk = schema.unenforced_primary_key();
+        assert_eq!(pk.len(), 1);
+        assert_eq!(pk[0].name, "a");
+    }
+
     #[test]
     fn test_schema_unenforced_primary_key_failures() {
         let cases = vec![
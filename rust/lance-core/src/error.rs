@@ -12,6 +12,8 @@ pub fn box_error(e: impl std::error::Error + Send + Sync + 'static) -> BoxedErro
     Box::new(e)
 }
 
+const STABLE_ROW_ID_INCOMPATIBILITY: &str = "Cannot enable stable row ids on existing dataset";
+
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub))]
 pub enum Error {
@@ -143,6 +145,24 @@ impl Error {
         }
     }
 
+    /// Stable row ids were requested for a dataset that was created without them.
+    ///
+    /// Stable row ids can only be enabled when a dataset is created.
+    pub fn stable_row_id_incompatibility(location: Location) -> Self {
+        Self::NotSupported {
+            source: STABLE_ROW_ID_INCOMPATIBILITY.into(),
+            location,
+        }
+    }
+
+    /// Whether this error was created by [`Self::stable_row_id_incompatibility`].
+    pub fn is_stable_row_id_incompatibility(&self) -> bool {
+        matches!(
+            self,
+            Self::NotSupported { source, .. } if source.to_string() == STABLE_ROW_ID_INCOMPATIBILITY
+        )
+    }
+
     pub fn version_conflict(
         message: impl Into<String>,
         major_version: u16,
@@ -457,4 +477,20 @@ mod test {
             _ => panic!("expected ObjectStore error"),
         }
     }
+
+    #[test]
+    fn test_stable_row_id_incompatibility() {
+        let err = Error::stable_row_id_incompatibility(snafu::location!());
+        assert!(err.is_stable_row_id_incompatibility());
+        assert!(matches!(err, Error::NotSupported { .. }));
+
+        let err = Error::NotSupported {
+            source: "something else".into(),
+            location: snafu::location!(),
+        };
+        assert!(!err.is_stable_row_id_incompatibility());
+
+        let err = Error::invalid_input(STABLE_ROW_ID_INCOMPATIBILITY, snafu::location!());
+        assert!(!err.is_stable_row_id_incompatibility());
+    }
 }
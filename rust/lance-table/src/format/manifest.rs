@@ -206,6 +206,12 @@ impl Manifest {
             .retain(|key, _| !delete_keys.contains(&key.as_str()));
     }
 
+    /// Delete all `config` keys starting with any of the given prefixes
+    pub fn delete_config_keys_by_prefix(&mut self, prefixes: &[&str]) {
+        self.config
+            .retain(|key, _| !prefixes.iter().any(|prefix| key.starts_with(prefix)));
+    }
+
     /// Replaces the schema metadata with the given key-value pairs.
     pub fn replace_schema_metadata(&mut self, new_metadata: HashMap<String, String>) {
         self.schema.metadata = new_metadata;
@@ -822,5 +828,12 @@ mod tests {
         config.remove("other-key");
         manifest.delete_config_keys(&["other-key"]);
         assert_eq!(manifest.config, config);
+
+        manifest.update_config([
+            ("partition.1.a".to_string(), "1".to_string()),
+            ("partition.2.a".to_string(), "2".to_string()),
+        ]);
+        manifest.delete_config_keys_by_prefix(&["partition."]);
+        assert_eq!(manifest.config, config);
     }
 }
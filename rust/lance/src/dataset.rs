@@ -1766,7 +1766,9 @@ impl Dataset {
         self.update_op(Operation::UpdateConfig {
             upsert_values: Some(HashMap::from_iter(upsert_values)),
             delete_keys: None,
+            delete_key_prefixes: None,
             schema_metadata: None,
+            schema_metadata_delete_keys: None,
             field_metadata: None,
         })
         .await
@@ -1777,7 +1779,22 @@ impl Dataset {
         self.update_op(Operation::UpdateConfig {
             upsert_values: None,
             delete_keys: Some(Vec::from_iter(delete_keys.iter().map(ToString::to_string))),
+            delete_key_prefixes: None,
             schema_metadata: None,
+            schema_metadata_delete_keys: None,
+            field_metadata: None,
+        })
+        .await
+    }
+
+    /// Delete all keys starting with any of the given prefixes from the config.
+    pub async fn delete_config_keys_by_prefix(&mut self, prefixes: &[&str]) -> Result<()> {
+        self.update_op(Operation::UpdateConfig {
+            upsert_values: None,
+            delete_keys: None,
+            delete_key_prefixes: Some(Vec::from_iter(prefixes.iter().map(ToString::to_string))),
+            schema_metadata: None,
+            schema_metadata_delete_keys: None,
             field_metadata: None,
         })
         .await
@@ -1791,7 +1808,9 @@ impl Dataset {
         self.update_op(Operation::UpdateConfig {
             upsert_values: None,
             delete_keys: None,
+            delete_key_prefixes: None,
             schema_metadata: Some(HashMap::from_iter(new_values)),
+            schema_metadata_delete_keys: None,
             field_metadata: None,
         })
         .await
@@ -1806,7 +1825,9 @@ impl Dataset {
         self.update_op(Operation::UpdateConfig {
             upsert_values: None,
             delete_keys: None,
+            delete_key_prefixes: None,
             schema_metadata: None,
+            schema_metadata_delete_keys: None,
             field_metadata: Some(new_values),
         })
         .await
@@ -3801,6 +3822,19 @@ mod tests {
         assert_eq!(dataset.manifest.config, desired_config);
         assert_eq!(dataset.config().unwrap(), desired_config);
         assert_true!(!dataset.config().unwrap().contains_key("other-key"));
+
+        dataset
+            .update_config([
+                ("partition.1.a".to_string(), "1".to_string()),
+                ("partition.2.a".to_string(), "2".to_string()),
+            ])
+            .await
+            .unwrap();
+        dataset
+            .delete_config_keys_by_prefix(&["partition."])
+            .await
+            .unwrap();
+        assert_eq!(dataset.config().unwrap(), desired_config);
     }
 
     #[rstest]
@@ -6016,6 +6050,7 @@ mod tests {
             WriteDestination::Dataset(dataset.clone()),
             Operation::DataReplacement {
                 replacements: vec![],
+                new_index: None,
             },
             Some(1),
             None,
@@ -6045,6 +6080,7 @@ mod tests {
             WriteDestination::Dataset(Arc::new(dataset)),
             Operation::DataReplacement {
                 replacements: vec![],
+                new_index: None,
             },
             Some(3),
             None,
@@ -6098,7 +6134,8 @@ mod tests {
         let dataset = Dataset::commit(
             WriteDestination::Dataset(Arc::new(dataset)),
             Operation::DataReplacement {
-                replacements: vec![DataReplacementGroup(0, new_data_file)],
+                replacements: vec![DataReplacementGroup::new(0, new_data_file)],
+                new_index: None,
             },
             Some(4),
             None,
@@ -6211,7 +6248,8 @@ mod tests {
         let dataset = Dataset::commit(
             WriteDestination::Dataset(Arc::new(dataset)),
             Operation::DataReplacement {
-                replacements: vec![DataReplacementGroup(0, new_data_file)],
+                replacements: vec![DataReplacementGroup::new(0, new_data_file)],
+                new_index: None,
             },
             Some(3),
             None,
@@ -6265,7 +6303,8 @@ mod tests {
         let dataset = Dataset::commit(
             WriteDestination::Dataset(Arc::new(dataset)),
             Operation::DataReplacement {
-                replacements: vec![DataReplacementGroup(0, new_data_file)],
+                replacements: vec![DataReplacementGroup::new(0, new_data_file)],
+                new_index: None,
             },
             Some(4),
             None,
@@ -6368,7 +6407,8 @@ mod tests {
         let err = Dataset::commit(
             WriteDestination::Dataset(Arc::new(dataset.clone())),
             Operation::DataReplacement {
-                replacements: vec![DataReplacementGroup(0, new_data_file)],
+                replacements: vec![DataReplacementGroup::new(0, new_data_file)],
+                new_index: None,
             },
             Some(2),
             None,
@@ -16,6 +16,8 @@
 //!   any fragment in a valid manifest file then it will be deleted.
 //! * Unreferenced index files - If an index file is not referenced by
 //!   any valid manifest file then it will be deleted.
+//! * Unreferenced row id files - If a shared row id file is not referenced
+//!   by any fragment in a valid manifest file then it will be deleted.
 //!
 //! It is also difficult to distinguish between a data/tx/idx file which was
 //! leftover from an abandoned transaction and a data file which is part
@@ -44,7 +46,7 @@ use lance_core::{
     Error, Result,
 };
 use lance_table::{
-    format::{Index, Manifest},
+    format::{Index, Manifest, RowIdMeta},
     io::{
         commit::ManifestLocation,
         deletion::deletion_file_path,
@@ -62,12 +64,14 @@ use tracing::{info, instrument, Span};
 use crate::{utils::temporal::utc_now, Dataset};
 
 use super::refs::TagContents;
+use super::rowids::ROW_ID_FILE_PREFIX;
 
 #[derive(Clone, Debug, Default)]
 struct ReferencedFiles {
     data_paths: HashSet<Path>,
     delete_paths: HashSet<Path>,
     tx_paths: HashSet<Path>,
+    row_id_paths: HashSet<Path>,
     index_uuids: HashSet<String>,
 }
 
@@ -239,6 +243,11 @@ impl<'a> CleanupTask<'a> {
                 let relative_path = remove_prefix(&delpath, &self.dataset.base);
                 referenced_files.delete_paths.insert(relative_path);
             }
+            if let Some(RowIdMeta::External(file)) = &fragment.row_id_meta {
+                referenced_files
+                    .row_id_paths
+                    .insert(Path::parse(file.path.as_str())?);
+            }
         }
         if let Some(relative_tx_path) = &manifest.transaction_file {
             referenced_files
@@ -415,6 +424,23 @@ impl<'a> CleanupTask<'a> {
                     } else {
                         Ok(None)
                     }
+                } else if relative_path.as_ref().starts_with(ROW_ID_FILE_PREFIX) {
+                    if inspection
+                        .referenced_files
+                        .row_id_paths
+                        .contains(&relative_path)
+                    {
+                        Ok(None)
+                    } else if !maybe_in_progress
+                        || inspection
+                            .verified_files
+                            .row_id_paths
+                            .contains(&relative_path)
+                    {
+                        Ok(Some(path))
+                    } else {
+                        Ok(None)
+                    }
                 } else {
                     Ok(None)
                 }
@@ -447,7 +473,7 @@ impl<'a> CleanupTask<'a> {
 /// needed.
 ///
 /// This function will remove old manifest files, data files, indexes,
-/// delete files, and transaction files.
+/// delete files, transaction files, and row id files.
 ///
 /// It will only remove files that are not referenced by any valid manifest.
 ///
@@ -623,6 +649,7 @@ mod tests {
         num_index_files: usize,
         num_delete_files: usize,
         num_tx_files: usize,
+        num_row_id_files: usize,
         num_bytes: u64,
     }
 
@@ -805,13 +832,19 @@ mod tests {
                 num_index_files: 0,
                 num_manifest_files: 0,
                 num_tx_files: 0,
+                num_row_id_files: 0,
                 num_bytes: 0,
             };
             while let Some(path) = file_stream.try_next().await? {
                 file_count.num_bytes += path.size;
+                let is_row_id_file = path
+                    .location
+                    .filename()
+                    .is_some_and(|name| name.starts_with(ROW_ID_FILE_PREFIX));
                 match path.location.extension() {
                     Some("lance") => file_count.num_data_files += 1,
                     Some("manifest") => file_count.num_manifest_files += 1,
+                    Some("bin") if is_row_id_file => file_count.num_row_id_files += 1,
                     Some("arrow") | Some("bin") => file_count.num_delete_files += 1,
                     Some("idx") => file_count.num_index_files += 1,
                     Some("txn") => file_count.num_tx_files += 1,
@@ -1258,6 +1291,72 @@ mod tests {
         assert_eq!(row_count_after, 8);
     }
 
+    #[tokio::test]
+    async fn clean_old_row_id_files() {
+        let fixture = MockDatasetFixture::try_new().unwrap();
+        let mut data_gen = BatchGenerator::new().col(Box::new(
+            IncrementingInt32::new().named("filter_me".to_owned()),
+        ));
+        let write_params = |mode| WriteParams {
+            store_params: Some(fixture.os_params()),
+            commit_handler: Some(Arc::new(RenameCommitHandler)),
+            mode,
+            enable_move_stable_row_ids: true,
+            max_rows_per_file: 4,
+            ..Default::default()
+        };
+
+        // Writing several fragments at once shares one row id file
+        Dataset::write(
+            data_gen.batch(16),
+            &fixture.dataset_path,
+            Some(write_params(WriteMode::Create)),
+        )
+        .await
+        .unwrap();
+        // A row id file left behind by a failed commit attempt
+        let registry = Arc::new(ObjectStoreRegistry::default());
+        let (os, base) =
+            ObjectStore::from_uri_and_params(registry, &fixture.dataset_path, &fixture.os_params())
+                .await
+                .unwrap();
+        os.put(
+            &base.child(format!("{}orphan.bin", ROW_ID_FILE_PREFIX)),
+            b"orphan",
+        )
+        .await
+        .unwrap();
+        fixture
+            .clock
+            .set_system_time(TimeDelta::try_days(10).unwrap());
+        Dataset::write(
+            data_gen.batch(16),
+            &fixture.dataset_path,
+            Some(write_params(WriteMode::Overwrite)),
+        )
+        .await
+        .unwrap();
+
+        let before_count = fixture.count_files().await.unwrap();
+        assert_eq!(before_count.num_row_id_files, 3);
+
+        let before = utc_now() - TimeDelta::try_days(8).unwrap();
+        let removed = fixture.run_cleanup(before).await.unwrap();
+
+        let after_count = fixture.count_files().await.unwrap();
+        assert_eq!(removed.old_versions, 1);
+        assert_eq!(
+            removed.bytes_removed,
+            before_count.num_bytes - after_count.num_bytes
+        );
+        // Only the row id file of the latest version is left
+        assert_eq!(after_count.num_row_id_files, 1);
+
+        // Ensure we can still read the dataset
+        let row_count_after = fixture.count_rows().await.unwrap();
+        assert_eq!(row_count_after, 16);
+    }
+
     #[tokio::test]
     async fn dont_clean_index_data_files() {
         // Indexes have .lance files in them that are not referenced
@@ -5,14 +5,105 @@ use super::Dataset;
 use crate::session::caches::{RowIdIndexKey, RowIdSequenceKey};
 use crate::{Error, Result};
 use futures::{Stream, StreamExt, TryFutureExt, TryStreamExt};
+use lance_io::object_store::ObjectStore;
+use object_store::path::Path;
 use snafu::location;
 use std::sync::Arc;
 
 use lance_table::{
-    format::{Fragment, RowIdMeta},
+    format::{ExternalFile, Fragment, RowIdMeta},
     rowids::{read_row_ids, RowIdIndex, RowIdSequence},
 };
 
+/// File name prefix of the shared row id files, stored at the dataset root.
+pub(crate) const ROW_ID_FILE_PREFIX: &str = "_rowids-";
+
+/// Collects the row id sequences of several fragments into a single file.
+///
+/// Each fragment gets a [`RowIdMeta::External`] referencing its own range of
+/// the shared file, so a commit that creates many small fragments writes one
+/// object rather than inlining a sequence per fragment in the manifest.
+pub(crate) struct SharedRowIdFileWriter {
+    path: String,
+    buffer: Vec<u8>,
+}
+
+impl SharedRowIdFileWriter {
+    pub fn new() -> Self {
+        Self {
+            path: format!("{}{}.bin", ROW_ID_FILE_PREFIX, uuid::Uuid::new_v4()),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Add a serialized row id sequence, returning the metadata referencing it
+    pub fn push(&mut self, serialized: &[u8]) -> RowIdMeta {
+        let offset = self.buffer.len() as u64;
+        self.buffer.extend_from_slice(serialized);
+        RowIdMeta::External(ExternalFile {
+            path: self.path.clone(),
+            offset,
+            size: serialized.len() as u64,
+        })
+    }
+
+    /// Write the shared file under the dataset root
+    pub async fn finish(self, object_store: &ObjectStore, base: &Path) -> Result<()> {
+        let path = base.child(self.path.as_str());
+        object_store.put(&path, &self.buffer).await?;
+        Ok(())
+    }
+}
+
+/// The shared row id file written by an earlier attempt of a commit.
+///
+/// Each attempt rebuilds the manifest, and with it the row id sequences of the
+/// new fragments. While these stay the same, the file written by the first
+/// attempt is reused rather than uploading one per attempt.
+#[derive(Debug, Default)]
+pub(crate) struct SharedRowIds {
+    sequences: Vec<Vec<u8>>,
+    metas: Vec<RowIdMeta>,
+}
+
+/// Move the inline row id sequences of the given fragments into one shared file.
+///
+/// This is done once per commit, for the fragments that commit created. It is
+/// a no-op unless at least two fragments have inline sequences, since a single
+/// fragment would not save any objects. If `shared` already holds the same
+/// sequences, its file is referenced instead of writing a new one.
+pub(crate) async fn share_row_id_files(
+    object_store: &ObjectStore,
+    base: &Path,
+    fragments: &mut [Fragment],
+    shared: &mut SharedRowIds,
+) -> Result<()> {
+    let sequences = fragments
+        .iter()
+        .filter_map(|f| match &f.row_id_meta {
+            Some(RowIdMeta::Inline(data)) => Some(data.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if sequences.len() < 2 {
+        return Ok(());
+    }
+
+    if shared.sequences != sequences {
+        let mut writer = SharedRowIdFileWriter::new();
+        let metas = sequences.iter().map(|data| writer.push(data)).collect();
+        writer.finish(object_store, base).await?;
+        *shared = SharedRowIds { sequences, metas };
+    }
+    let inline = fragments
+        .iter_mut()
+        .filter(|f| matches!(f.row_id_meta, Some(RowIdMeta::Inline(_))));
+    for (fragment, meta) in inline.zip(&shared.metas) {
+        fragment.row_id_meta = Some(meta.clone());
+    }
+    Ok(())
+}
+
 /// Load a row id sequence from the given dataset and fragment.
 pub async fn load_row_id_sequence(
     dataset: &Dataset,
@@ -206,6 +297,65 @@ mod test {
         assert_eq!(dataset.manifest().next_row_id, num_rows);
     }
 
+    #[tokio::test]
+    async fn test_row_ids_shared_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let tmp_path = temp_dir.path().to_str().unwrap();
+
+        // A single fragment keeps its sequence inline.
+        let batch = sequence_batch(0..10);
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], batch.schema());
+        let write_params = WriteParams {
+            enable_move_stable_row_ids: true,
+            ..Default::default()
+        };
+        Dataset::write(reader, tmp_path, Some(write_params))
+            .await
+            .unwrap();
+
+        // Appending several fragments at once shares a single file.
+        let batch = sequence_batch(10..40);
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], batch.schema());
+        let write_params = WriteParams {
+            mode: WriteMode::Append,
+            max_rows_per_file: 10,
+            ..Default::default()
+        };
+        let dataset = Dataset::write(reader, tmp_path, Some(write_params))
+            .await
+            .unwrap();
+
+        let fragments = dataset.get_fragments();
+        assert_eq!(fragments.len(), 4);
+        assert!(matches!(
+            fragments[0].metadata().row_id_meta,
+            Some(RowIdMeta::Inline(_))
+        ));
+        let files = fragments[1..]
+            .iter()
+            .map(|frag| match &frag.metadata().row_id_meta {
+                Some(RowIdMeta::External(file)) => file.clone(),
+                other => panic!("Expected external row ids, got {:?}", other),
+            })
+            .collect::<Vec<_>>();
+        assert!(files.iter().all(|file| file.path == files[0].path));
+        assert!(files[0].path.starts_with(ROW_ID_FILE_PREFIX));
+        for pair in files.windows(2) {
+            assert_eq!(pair[0].offset + pair[0].size, pair[1].offset);
+        }
+
+        let dataset = DatasetBuilder::from_uri(tmp_path).load().await.unwrap();
+        let index = get_row_id_index(&dataset).await.unwrap().unwrap();
+        for i in 0..40 {
+            let address = index.get(i).unwrap();
+            assert_eq!(
+                address,
+                RowAddress::new_from_parts(i as u32 / 10, i as u32 % 10)
+            );
+        }
+        assert!(index.get(40).is_none());
+    }
+
     #[tokio::test]
     async fn test_row_ids_overwrite() {
         // Validate we don't re-use after overwriting
@@ -141,6 +141,9 @@ pub(super) async fn add_columns_to_fragments(
     // Check names early (before calling add_columns_impl) to avoid extra work if
     // the names are wrong.
     let check_names = |output_schema: &ArrowSchema| {
+        let schema = Schema::try_from(output_schema)?;
+        schema.assert_no_row_id_fields()?;
+        schema.assert_no_empty_structs()?;
         let new_names = output_schema.field_names();
         for field in &dataset.schema().fields {
             if new_names.contains(&&field.name) {
@@ -552,11 +555,7 @@ pub(super) async fn alter_columns(
         // Otherwise, we need to re-write the relevant fields.
         let read_columns = cast_fields
             .iter()
-            .map(|(old, _new)| {
-                let parts = dataset.schema().field_ancestry_by_id(old.id).unwrap();
-                let part_names = parts.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
-                part_names.join(".")
-            })
+            .map(|(old, _new)| dataset.schema().field_path_by_id(old.id).unwrap())
             .collect::<Vec<_>>();
 
         let new_ids = cast_fields
@@ -1509,6 +1508,55 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_rename_and_cast_column() -> Result<()> {
+        use arrow_array::Int64Array;
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("b", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(Int32Array::from(vec![3, 4])),
+            ],
+        )?;
+        let test_dir = tempfile::tempdir()?;
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            test_uri,
+            None,
+        )
+        .await?;
+
+        // The column is rewritten under a new id and a new name at once
+        dataset
+            .alter_columns(&[ColumnAlteration::new("a".into())
+                .rename("x".into())
+                .cast_to(DataType::Int64)])
+            .await?;
+        dataset.validate().await?;
+
+        let expected_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("x", DataType::Int64, false),
+            ArrowField::new("b", DataType::Int32, false),
+        ]));
+        let expected_data = RecordBatch::try_new(
+            expected_schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(Int32Array::from(vec![3, 4])),
+            ],
+        )?;
+        let actual_data = dataset.scan().try_into_batch().await?;
+        assert_eq!(actual_data, expected_data);
+
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_drop_columns(
@@ -22,42 +22,69 @@
 //! a conflict. Some operations have additional conditions that must be met for
 //! them to be compatible.
 //!
-//! NOTE/TODO(rmeng): DataReplacement conflict resolution is not fully implemented
+//! This table is checked by `test_conflict_matrix` in the conflict resolver.
+//! [`Transaction::classify_conflict`] gives the reason a pair of transactions
+//! conflicts according to the conflict resolver.
 //!
 //! |                  | Append | Delete / Update | Overwrite/Create | Create Index | Rewrite | Merge | Project | UpdateConfig | DataReplacement |
 //! |------------------|--------|-----------------|------------------|--------------|---------|-------|---------|--------------|-----------------|
-//! | Append           | ✅     | ✅              | ❌                | ✅           | ✅      | ❌     | ❌      | ✅           | ✅
-//! | Delete / Update  | ✅     | 1️⃣              | ❌                | ✅           | 1️⃣      | ❌     | ❌      | ✅           | ✅
-//! | Overwrite/Create | ✅     | ✅              | ✅                | ✅           | ✅      | ✅     | ✅      | 2️⃣           | ✅
-//! | Create index     | ✅     | ✅              | ❌                | ✅           | ✅      | ✅     | ✅      | ✅           | 3️⃣
-//! | Rewrite          | ✅     | 1️⃣              | ❌                | ❌           | 1️⃣      | ❌     | ❌      | ✅           | 3️⃣
-//! | Merge            | ❌     | ❌              | ❌                | ❌           | ✅      | ❌     | ❌      | ✅           | ✅
-//! | Project          | ✅     | ✅              | ❌                | ❌           | ✅      | ❌     | ✅      | ✅           | ✅
+//! | Append           | ✅     | ✅              | ❌                | ✅           | ✅      | ✅     | ✅      | ✅           | ✅
+//! | Delete / Update  | ✅     | 1️⃣              | ❌                | ✅           | 1️⃣      | ❌     | ✅      | ✅           | 1️⃣
+//! | Overwrite/Create | ✅     | ✅              | 2️⃣                | ✅           | ✅      | ✅     | ✅      | 2️⃣           | ✅
+//! | Create index     | ✅     | ✅              | ❌                | ✅           | 4️⃣      | ✅     | ✅      | ✅           | 3️⃣
+//! | Rewrite          | ✅     | 1️⃣              | ❌                | 4️⃣           | 1️⃣      | ❌     | ✅      | ✅           | 3️⃣
+//! | Merge            | ❌     | ❌              | ❌                | ✅           | ❌      | ❌     | ❌      | ✅           | ❌
+//! | Project          | ✅     | ✅              | ❌                | ✅           | ✅      | ❌     | ❌      | ✅           | ✅
 //! | UpdateConfig     | ✅     | ✅              | 2️⃣                | ✅           | ✅      | ✅     | ✅      | 2️⃣           | ✅
 //! | DataReplacement  | ✅     | ✅              | ❌                | 3️⃣           | 1️⃣      | ✅     | 3️⃣      | ✅           | 3️⃣
 //!
 //! 1️⃣ Delete, update, and rewrite are compatible with each other and themselves only if
-//! they affect distinct fragments. Otherwise, they conflict.
+//! they affect distinct fragments. Otherwise, they conflict. The same goes for a data
+//! replacement committed before a delete or update, or after a rewrite.
 //! 2️⃣ Operations that mutate the config conflict if one of the operations upserts a key
 //! that if referenced by another concurrent operation or if both operations modify the schema
 //! metadata or the same field metadata.
 //! 3️⃣ DataReplacement on a column without index is compatible with any operation AS LONG AS
 //! the operation does not modify the region of the column being replaced.
+//! 4️⃣ Index creation and rewrite are compatible only if the index doesn't cover any of the
+//! rewritten fragments. An index without a fragment bitmap covers every fragment. A rewrite
+//! that defers index remapping to a fragment reuse index is compatible with index creation.
+//!
+//! Restore is not shown above: it rewrites history, so it conflicts with every
+//! other operation in either direction. Two restores to the same version are
+//! compatible. See [`Operation::restore_conflicts_with`].
+//!
+//! UpdateMemWalState is also not shown: two operations that reference the same
+//! MemWAL (by region and generation) conflict, whether they add, update, remove,
+//! or flush it (an Update with `mem_wal_to_flush`).
+//!
+//! RelaxNullability is also not shown: it is compatible with everything except
+//! Overwrite, Restore, and a Merge or Project whose schema drops one of the
+//! relaxed fields or still has it as non-nullable.
 //!
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    ops::Range,
     sync::Arc,
 };
 
 use super::ManifestWriteConfig;
 use crate::index::mem_wal::update_mem_wal_index_in_indices_list;
+use crate::io::commit::TransactionRebase;
 use crate::utils::temporal::timestamp_to_nanos;
 use deepsize::DeepSizeOf;
-use lance_core::{datatypes::Schema, Error, Result};
-use lance_file::{datatypes::Fields, version::LanceFileVersion};
+use lance_core::{
+    datatypes::{Field, Schema},
+    Error, Result,
+};
+use lance_file::{
+    datatypes::{Fields, FieldsWithMeta},
+    version::LanceFileVersion,
+};
 use lance_index::is_system_index;
-use lance_index::mem_wal::MemWal;
+use lance_index::mem_wal::{MemWal, MemWalId};
 use lance_io::object_store::ObjectStore;
 use lance_table::feature_flags::{apply_feature_flags, FLAG_MOVE_STABLE_ROW_IDS};
 use lance_table::{
@@ -76,6 +103,9 @@ use roaring::RoaringBitmap;
 use snafu::location;
 use uuid::Uuid;
 
+/// The maximum length of a transaction tag, in bytes.
+pub const MAX_TAG_LENGTH: usize = 256;
+
 /// A change to a dataset that can be retried
 ///
 /// This contains enough information to be able to build the next manifest,
@@ -92,6 +122,9 @@ pub struct Transaction {
     ///
     /// If this is `None`, then the blobs dataset was not modified
     pub blobs_op: Option<Operation>,
+    /// A user-provided tag, stored in the manifest. When present it must be
+    /// non-empty, free of control characters, and at most [`MAX_TAG_LENGTH`]
+    /// bytes, see [`Transaction::validate`].
     pub tag: Option<String>,
 }
 
@@ -103,8 +136,43 @@ pub enum BlobsOperation {
     Updated(u64),
 }
 
+impl BlobsOperation {
+    /// The new version of the blobs dataset, if it was modified.
+    ///
+    /// This is the blob version that `build_manifest` expects.
+    pub fn new_version(&self) -> Option<u64> {
+        match self {
+            Self::Unchanged => None,
+            Self::Updated(version) => Some(*version),
+        }
+    }
+}
+
+/// Replace a data file in a fragment: the id of the fragment and the new data
+/// file.
 #[derive(Debug, Clone, DeepSizeOf, PartialEq)]
-pub struct DataReplacementGroup(pub u64, pub DataFile);
+pub struct DataReplacementGroup(pub u64, pub DataFile, Option<u64>);
+
+impl DataReplacementGroup {
+    pub fn new(fragment_id: u64, new_file: DataFile) -> Self {
+        Self(fragment_id, new_file, None)
+    }
+
+    /// Set the number of rows in the new data file.
+    ///
+    /// The data file does not record its own length, so the row count is only
+    /// checked against the physical rows of the fragment being replaced when it
+    /// is set here.
+    pub fn with_num_rows(mut self, num_rows: u64) -> Self {
+        self.2 = Some(num_rows);
+        self
+    }
+
+    /// The number of rows in the new data file, if known.
+    pub fn num_rows(&self) -> Option<u64> {
+        self.2
+    }
+}
 
 /// An operation on a dataset.
 #[derive(Debug, Clone, DeepSizeOf)]
@@ -167,6 +235,9 @@ pub enum Operation {
     /// with a new column A, the operation is not allowed.
     DataReplacement {
         replacements: Vec<DataReplacementGroup>,
+        /// An index to create along with the replacement, e.g. on the column
+        /// being populated. It replaces any existing index with the same name.
+        new_index: Option<Index>,
     },
     /// Merge a new column in
     Merge {
@@ -213,11 +284,18 @@ pub enum Operation {
     /// Project to a new schema. This only changes the schema, not the data.
     Project { schema: Schema },
 
+    /// Make the given fields nullable. This only changes the schema, not the data.
+    RelaxNullability { field_ids: Vec<u32> },
+
     /// Update the dataset configuration.
     UpdateConfig {
         upsert_values: Option<HashMap<String, String>>,
         delete_keys: Option<Vec<String>>,
+        /// Any config key starting with one of these prefixes is deleted
+        delete_key_prefixes: Option<Vec<String>>,
         schema_metadata: Option<HashMap<String, String>>,
+        /// Schema metadata keys to delete, applied before `schema_metadata`
+        schema_metadata_delete_keys: Option<Vec<String>>,
         field_metadata: Option<HashMap<u32, HashMap<String, String>>>,
     },
     /// Update the state of MemWALs.
@@ -241,6 +319,7 @@ impl std::fmt::Display for Operation {
             Self::ReserveFragments { .. } => write!(f, "ReserveFragments"),
             Self::Update { .. } => write!(f, "Update"),
             Self::Project { .. } => write!(f, "Project"),
+            Self::RelaxNullability { .. } => write!(f, "RelaxNullability"),
             Self::UpdateConfig { .. } => write!(f, "UpdateConfig"),
             Self::DataReplacement { .. } => write!(f, "DataReplacement"),
             Self::UpdateMemWalState { .. } => write!(f, "UpdateMemWalState"),
@@ -256,7 +335,7 @@ impl PartialEq for Operation {
         // TODO: we can make it so the vecs are always constructed in order.
         // Then we can use `==` instead of `compare_vec`.
         fn compare_vec<T: PartialEq>(a: &[T], b: &[T]) -> bool {
-            a.len() == b.len() && a.iter().all(|f| b.contains(f))
+            a.len() == b.len() && a.iter().all(|f| b.contains(f)) && b.iter().all(|f| a.contains(f))
         }
         match (self, other) {
             (Self::Append { fragments: a }, Self::Append { fragments: b }) => compare_vec(a, b),
@@ -356,17 +435,24 @@ impl PartialEq for Operation {
                     && a_mem_wal_to_flush == b_mem_wal_to_flush
             }
             (Self::Project { schema: a }, Self::Project { schema: b }) => a == b,
+            (Self::RelaxNullability { field_ids: a }, Self::RelaxNullability { field_ids: b }) => {
+                compare_vec(a, b)
+            }
             (
                 Self::UpdateConfig {
                     upsert_values: a_upsert,
                     delete_keys: a_delete,
+                    delete_key_prefixes: a_delete_prefixes,
                     schema_metadata: a_schema,
+                    schema_metadata_delete_keys: a_schema_delete,
                     field_metadata: a_field,
                 },
                 Self::UpdateConfig {
                     upsert_values: b_upsert,
                     delete_keys: b_delete,
+                    delete_key_prefixes: b_delete_prefixes,
                     schema_metadata: b_schema,
+                    schema_metadata_delete_keys: b_schema_delete,
                     field_metadata: b_field,
                 },
             ) => {
@@ -380,13 +466,37 @@ impl PartialEq for Operation {
                         v.sort();
                         v
                     })
+                    && a_delete_prefixes.as_ref().map(|v| {
+                        let mut v = v.clone();
+                        v.sort();
+                        v
+                    }) == b_delete_prefixes.as_ref().map(|v| {
+                        let mut v = v.clone();
+                        v.sort();
+                        v
+                    })
                     && a_schema == b_schema
+                    && a_schema_delete.as_ref().map(|v| {
+                        let mut v = v.clone();
+                        v.sort();
+                        v
+                    }) == b_schema_delete.as_ref().map(|v| {
+                        let mut v = v.clone();
+                        v.sort();
+                        v
+                    })
                     && a_field == b_field
             }
             (
-                Self::DataReplacement { replacements: a },
-                Self::DataReplacement { replacements: b },
-            ) => a.len() == b.len() && a.iter().all(|r| b.contains(r)),
+                Self::DataReplacement {
+                    replacements: a,
+                    new_index: a_index,
+                },
+                Self::DataReplacement {
+                    replacements: b,
+                    new_index: b_index,
+                },
+            ) => a.len() == b.len() && a.iter().all(|r| b.contains(r)) && a_index == b_index,
             // Handle all remaining combinations.
             // We spell out all combinations explicitly to prevent
             // us accidentally handling a new case in the wrong way.
@@ -426,6 +536,9 @@ impl PartialEq for Operation {
             (Self::Append { .. }, Self::UpdateMemWalState { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::Append { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
 
             (Self::Delete { .. }, Self::Append { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
@@ -463,6 +576,9 @@ impl PartialEq for Operation {
             (Self::Delete { .. }, Self::UpdateMemWalState { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::Delete { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
 
             (Self::Overwrite { .. }, Self::Append { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
@@ -500,6 +616,9 @@ impl PartialEq for Operation {
             (Self::Overwrite { .. }, Self::UpdateMemWalState { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::Overwrite { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
 
             (Self::CreateIndex { .. }, Self::Append { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
@@ -537,6 +656,9 @@ impl PartialEq for Operation {
             (Self::CreateIndex { .. }, Self::UpdateMemWalState { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::CreateIndex { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
 
             (Self::Rewrite { .. }, Self::Append { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
@@ -574,6 +696,9 @@ impl PartialEq for Operation {
             (Self::Rewrite { .. }, Self::UpdateMemWalState { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::Rewrite { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
 
             (Self::Merge { .. }, Self::Append { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
@@ -611,6 +736,9 @@ impl PartialEq for Operation {
             (Self::Merge { .. }, Self::UpdateMemWalState { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::Merge { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
 
             (Self::Restore { .. }, Self::Append { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
@@ -648,6 +776,9 @@ impl PartialEq for Operation {
             (Self::Restore { .. }, Self::UpdateMemWalState { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::Restore { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
 
             (Self::ReserveFragments { .. }, Self::Append { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
@@ -685,6 +816,9 @@ impl PartialEq for Operation {
             (Self::ReserveFragments { .. }, Self::UpdateMemWalState { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::ReserveFragments { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
 
             (Self::Update { .. }, Self::Append { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
@@ -722,6 +856,9 @@ impl PartialEq for Operation {
             (Self::Update { .. }, Self::UpdateMemWalState { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::Update { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
 
             (Self::Project { .. }, Self::Append { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
@@ -759,6 +896,9 @@ impl PartialEq for Operation {
             (Self::Project { .. }, Self::UpdateMemWalState { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::Project { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
 
             (Self::UpdateConfig { .. }, Self::Append { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
@@ -796,6 +936,9 @@ impl PartialEq for Operation {
             (Self::UpdateConfig { .. }, Self::UpdateMemWalState { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::UpdateConfig { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
 
             (Self::DataReplacement { .. }, Self::Append { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
@@ -833,6 +976,49 @@ impl PartialEq for Operation {
             (Self::DataReplacement { .. }, Self::UpdateMemWalState { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::DataReplacement { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+
+            (Self::RelaxNullability { .. }, Self::Append { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+            (Self::RelaxNullability { .. }, Self::Delete { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+            (Self::RelaxNullability { .. }, Self::Overwrite { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+            (Self::RelaxNullability { .. }, Self::CreateIndex { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+            (Self::RelaxNullability { .. }, Self::Rewrite { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+            (Self::RelaxNullability { .. }, Self::Merge { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+            (Self::RelaxNullability { .. }, Self::Restore { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+            (Self::RelaxNullability { .. }, Self::ReserveFragments { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+            (Self::RelaxNullability { .. }, Self::Update { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+            (Self::RelaxNullability { .. }, Self::Project { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+            (Self::RelaxNullability { .. }, Self::UpdateConfig { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+            (Self::RelaxNullability { .. }, Self::DataReplacement { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
+            (Self::RelaxNullability { .. }, Self::UpdateMemWalState { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
 
             (Self::UpdateMemWalState { .. }, Self::Append { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
@@ -870,6 +1056,9 @@ impl PartialEq for Operation {
             (Self::UpdateMemWalState { .. }, Self::DataReplacement { .. }) => {
                 std::mem::discriminant(self) == std::mem::discriminant(other)
             }
+            (Self::UpdateMemWalState { .. }, Self::RelaxNullability { .. }) => {
+                std::mem::discriminant(self) == std::mem::discriminant(other)
+            }
             (
                 Self::UpdateMemWalState {
                     added: a_added,
@@ -890,6 +1079,163 @@ impl PartialEq for Operation {
     }
 }
 
+/// Hashes an [`Operation`] consistently with its [`PartialEq`] implementation,
+/// so that `a == b` implies `hash(a) == hash(b)`.
+///
+/// Since equality ignores the order of the `Vec`s in an operation, those are
+/// hashed by their length and the set of distinct element hashes. Only a key
+/// of each element (such as a fragment id) is hashed, which is enough to keep
+/// the invariant while avoiding hashing entire fragments.
+impl Hash for Operation {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        fn hash_unordered<T, K: Hash, H: Hasher>(
+            items: &[T],
+            key: impl Fn(&T) -> K,
+            state: &mut H,
+        ) {
+            items.len().hash(state);
+            items
+                .iter()
+                .map(|item| {
+                    let mut hasher = DefaultHasher::new();
+                    key(item).hash(&mut hasher);
+                    hasher.finish()
+                })
+                .collect::<BTreeSet<_>>()
+                .hash(state);
+        }
+        fn hash_schema<H: Hasher>(schema: &Schema, state: &mut H) {
+            for field in schema.fields_pre_order() {
+                (field.id, &field.name).hash(state);
+            }
+        }
+        fn hash_map<H: Hasher>(map: &Option<HashMap<String, String>>, state: &mut H) {
+            map.as_ref()
+                .map(|m| m.iter().collect::<BTreeMap<_, _>>())
+                .hash(state);
+        }
+        fn hash_sorted<H: Hasher>(values: &Option<Vec<String>>, state: &mut H) {
+            values
+                .as_ref()
+                .map(|v| v.iter().collect::<BTreeSet<_>>())
+                .hash(state);
+        }
+
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::Append { fragments } => hash_unordered(fragments, |f| f.id, state),
+            Self::Delete {
+                updated_fragments,
+                deleted_fragment_ids,
+                predicate,
+            } => {
+                hash_unordered(updated_fragments, |f| f.id, state);
+                hash_unordered(deleted_fragment_ids, |id| *id, state);
+                predicate.hash(state);
+            }
+            Self::Overwrite {
+                fragments,
+                schema,
+                config_upsert_values,
+            } => {
+                hash_unordered(fragments, |f| f.id, state);
+                hash_schema(schema, state);
+                hash_map(config_upsert_values, state);
+            }
+            Self::CreateIndex {
+                new_indices,
+                removed_indices,
+            } => {
+                hash_unordered(new_indices, |i| i.uuid, state);
+                hash_unordered(removed_indices, |i| i.uuid, state);
+            }
+            Self::Rewrite {
+                groups,
+                rewritten_indices,
+                frag_reuse_index,
+            } => {
+                hash_unordered(
+                    groups,
+                    |g| {
+                        (
+                            g.old_fragments
+                                .iter()
+                                .map(|f| f.id)
+                                .collect::<BTreeSet<_>>(),
+                            g.new_fragments
+                                .iter()
+                                .map(|f| f.id)
+                                .collect::<BTreeSet<_>>(),
+                        )
+                    },
+                    state,
+                );
+                hash_unordered(rewritten_indices, |i| (i.old_id, i.new_id), state);
+                frag_reuse_index.as_ref().map(|i| i.uuid).hash(state);
+            }
+            Self::DataReplacement {
+                replacements,
+                new_index,
+            } => {
+                hash_unordered(replacements, |r| r.0, state);
+                new_index.as_ref().map(|i| i.uuid).hash(state);
+            }
+            Self::Merge { fragments, schema } => {
+                hash_unordered(fragments, |f| f.id, state);
+                hash_schema(schema, state);
+            }
+            Self::Restore { version } => version.hash(state),
+            Self::ReserveFragments { num_fragments } => num_fragments.hash(state),
+            Self::Update {
+                removed_fragment_ids,
+                updated_fragments,
+                new_fragments,
+                fields_modified,
+                mem_wal_to_flush,
+            } => {
+                hash_unordered(removed_fragment_ids, |id| *id, state);
+                hash_unordered(updated_fragments, |f| f.id, state);
+                hash_unordered(new_fragments, |f| f.id, state);
+                hash_unordered(fields_modified, |id| *id, state);
+                mem_wal_to_flush.is_some().hash(state);
+            }
+            Self::Project { schema } => hash_schema(schema, state),
+            Self::RelaxNullability { field_ids } => hash_unordered(field_ids, |id| *id, state),
+            Self::UpdateConfig {
+                upsert_values,
+                delete_keys,
+                delete_key_prefixes,
+                schema_metadata,
+                schema_metadata_delete_keys,
+                field_metadata,
+            } => {
+                hash_map(upsert_values, state);
+                hash_sorted(delete_keys, state);
+                hash_sorted(delete_key_prefixes, state);
+                hash_map(schema_metadata, state);
+                hash_sorted(schema_metadata_delete_keys, state);
+                field_metadata
+                    .as_ref()
+                    .map(|m| {
+                        m.iter()
+                            .map(|(id, metadata)| (id, metadata.iter().collect::<BTreeMap<_, _>>()))
+                            .collect::<BTreeMap<_, _>>()
+                    })
+                    .hash(state);
+            }
+            Self::UpdateMemWalState {
+                added,
+                updated,
+                removed,
+            } => {
+                added.len().hash(state);
+                updated.len().hash(state);
+                removed.len().hash(state);
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct RewrittenIndex {
     pub old_id: Uuid,
@@ -911,14 +1257,237 @@ pub struct RewriteGroup {
 impl PartialEq for RewriteGroup {
     fn eq(&self, other: &Self) -> bool {
         fn compare_vec<T: PartialEq>(a: &[T], b: &[T]) -> bool {
-            a.len() == b.len() && a.iter().all(|f| b.contains(f))
+            a.len() == b.len() && a.iter().all(|f| b.contains(f)) && b.iter().all(|f| a.contains(f))
         }
         compare_vec(&self.old_fragments, &other.old_fragments)
             && compare_vec(&self.new_fragments, &other.new_fragments)
     }
 }
 
+impl RewriteGroup {
+    /// Returns the `(old, new)` fragment id pairs of this group.
+    ///
+    /// Rows from any of the old fragments may end up in any of the new
+    /// fragments, so every old fragment is paired with every new fragment.
+    /// New fragments that have not been assigned an id yet (id 0) are
+    /// reported with id 0; reserve ids up front with
+    /// [`Operation::ReserveFragments`] to know them before committing.
+    pub fn id_mapping(&self) -> Vec<(u64, u64)> {
+        self.old_fragments
+            .iter()
+            .flat_map(|old| self.new_fragments.iter().map(move |new| (old.id, new.id)))
+            .collect()
+    }
+}
+
 impl Operation {
+    /// Create a [`Operation::Rewrite`] for a compaction that does not remap
+    /// any indices.
+    pub fn compact(groups: Vec<RewriteGroup>) -> Self {
+        Self::Rewrite {
+            groups,
+            rewritten_indices: vec![],
+            frag_reuse_index: None,
+        }
+    }
+
+    /// Create a [`Operation::Update`], deriving `removed_fragment_ids` from the
+    /// fragments the update read.
+    ///
+    /// `existing` should be the existing fragments the update touched, not the
+    /// whole dataset.  Any of them that is not in `updated` (matched by id) is
+    /// assumed to be fully replaced and is removed.
+    ///
+    /// In a vertical update, rows are moved: the touched fragments come back in
+    /// `updated` with rows deleted (or not at all, if every row moved) and the
+    /// rewritten rows are in `new`.  In a horizontal update, columns are added:
+    /// every touched fragment comes back in `updated` with new data files, so
+    /// nothing is removed and `new` is usually empty.
+    pub fn update(
+        existing: &[Fragment],
+        updated: Vec<Fragment>,
+        new: Vec<Fragment>,
+        fields_modified: Vec<u32>,
+    ) -> Self {
+        let updated_ids = updated.iter().map(|f| f.id).collect::<HashSet<_>>();
+        let removed_fragment_ids = existing
+            .iter()
+            .map(|f| f.id)
+            .filter(|id| !updated_ids.contains(id))
+            .collect();
+        Self::Update {
+            removed_fragment_ids,
+            updated_fragments: updated,
+            new_fragments: new,
+            fields_modified,
+            mem_wal_to_flush: None,
+        }
+    }
+
+    /// Compare two operations like `==`, but ignoring the ids of the new
+    /// fragments they create.
+    ///
+    /// New fragments are compared by their content (files, deletions, row
+    /// counts) instead, which is useful to compare operations built by code
+    /// paths that assign different fragment ids. Other ids, such as those of
+    /// updated, rewritten, or deleted fragments, are still compared, since they
+    /// say which existing fragments are changed.
+    pub fn eq_ignoring_ids(&self, other: &Self) -> bool {
+        fn clear_ids(fragments: &mut [Fragment]) {
+            fragments.iter_mut().for_each(|f| f.id = 0);
+        }
+        fn without_fragment_ids(operation: &Operation) -> Operation {
+            let mut operation = operation.clone();
+            match &mut operation {
+                Operation::Append { fragments }
+                | Operation::Overwrite { fragments, .. }
+                | Operation::Update {
+                    new_fragments: fragments,
+                    ..
+                } => clear_ids(fragments),
+                Operation::Rewrite { groups, .. } => {
+                    for group in groups {
+                        clear_ids(&mut group.new_fragments);
+                    }
+                }
+                Operation::Delete { .. }
+                | Operation::Merge { .. }
+                | Operation::CreateIndex { .. }
+                | Operation::DataReplacement { .. }
+                | Operation::Restore { .. }
+                | Operation::ReserveFragments { .. }
+                | Operation::Project { .. }
+                | Operation::RelaxNullability { .. }
+                | Operation::UpdateConfig { .. }
+                | Operation::UpdateMemWalState { .. } => {}
+            }
+            operation
+        }
+        without_fragment_ids(self) == without_fragment_ids(other)
+    }
+
+    /// Returns the `(old, new)` fragment id pairs of a [`Operation::Rewrite`],
+    /// or `None` for any other operation.
+    ///
+    /// See [`RewriteGroup::id_mapping`].
+    pub fn rewrite_fragment_remap(&self) -> Option<Vec<(u64, u64)>> {
+        match self {
+            Self::Rewrite { groups, .. } => {
+                Some(groups.iter().flat_map(|g| g.id_mapping()).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Combine two [`Operation::UpdateConfig`] operations into one that has the
+    /// same effect as committing `self` followed by `other`.
+    ///
+    /// Upserted values are unioned, with `other` winning on duplicate keys.
+    /// Deleted keys and prefixes are concatenated. Schema metadata and the
+    /// metadata of each field are replaced wholesale when committed, so the
+    /// value from `other` wins wherever both set one. Schema metadata keys
+    /// deleted by `other` are removed from the schema metadata set by `self`.
+    ///
+    /// Returns an error if either operation is not an `UpdateConfig`, or if one
+    /// operation upserts a key that the other deletes.
+    pub fn try_merge_config(self, other: Self) -> Result<Self> {
+        match (self, other) {
+            (
+                Self::UpdateConfig {
+                    upsert_values: a_upsert,
+                    delete_keys: a_delete,
+                    delete_key_prefixes: a_prefixes,
+                    schema_metadata: a_schema,
+                    schema_metadata_delete_keys: a_schema_delete,
+                    field_metadata: a_field,
+                },
+                Self::UpdateConfig {
+                    upsert_values: b_upsert,
+                    delete_keys: b_delete,
+                    delete_key_prefixes: b_prefixes,
+                    schema_metadata: b_schema,
+                    schema_metadata_delete_keys: b_schema_delete,
+                    field_metadata: b_field,
+                },
+            ) => {
+                let is_deleted =
+                    |key: &str, delete: &Option<Vec<String>>, prefixes: &Option<Vec<String>>| {
+                        delete.iter().flatten().any(|k| k == key)
+                            || prefixes
+                                .iter()
+                                .flatten()
+                                .any(|p| key.starts_with(p.as_str()))
+                    };
+                let contradiction = a_upsert
+                    .iter()
+                    .flat_map(|m| m.keys())
+                    .find(|k| is_deleted(k.as_str(), &b_delete, &b_prefixes))
+                    .or_else(|| {
+                        b_upsert
+                            .iter()
+                            .flat_map(|m| m.keys())
+                            .find(|k| is_deleted(k.as_str(), &a_delete, &a_prefixes))
+                    });
+                if let Some(key) = contradiction {
+                    return Err(Error::invalid_input(
+                        format!(
+                            "Cannot merge config updates: key {} is both upserted and deleted",
+                            key
+                        ),
+                        location!(),
+                    ));
+                }
+
+                fn merge_maps<K: Eq + Hash, V>(
+                    a: Option<HashMap<K, V>>,
+                    b: Option<HashMap<K, V>>,
+                ) -> Option<HashMap<K, V>> {
+                    match (a, b) {
+                        (Some(mut a), Some(b)) => {
+                            a.extend(b);
+                            Some(a)
+                        }
+                        (a, b) => a.or(b),
+                    }
+                }
+                fn concat(a: Option<Vec<String>>, b: Option<Vec<String>>) -> Option<Vec<String>> {
+                    match (a, b) {
+                        (Some(mut a), Some(b)) => {
+                            a.extend(b);
+                            Some(a)
+                        }
+                        (a, b) => a.or(b),
+                    }
+                }
+
+                // The deletes of `other` also apply to the schema metadata set by `self`
+                let a_schema = a_schema.map(|mut metadata| {
+                    for key in b_schema_delete.iter().flatten() {
+                        metadata.remove(key);
+                    }
+                    metadata
+                });
+
+                Ok(Self::UpdateConfig {
+                    upsert_values: merge_maps(a_upsert, b_upsert),
+                    delete_keys: concat(a_delete, b_delete),
+                    delete_key_prefixes: concat(a_prefixes, b_prefixes),
+                    schema_metadata: b_schema.or(a_schema),
+                    schema_metadata_delete_keys: concat(a_schema_delete, b_schema_delete),
+                    field_metadata: merge_maps(a_field, b_field),
+                })
+            }
+            (a, b) => Err(Error::invalid_input(
+                format!(
+                    "Can only merge UpdateConfig operations, got {} and {}",
+                    a.name(),
+                    b.name()
+                ),
+                location!(),
+            )),
+        }
+    }
+
     /// Returns the config keys that have been upserted by this operation.
     fn get_upsert_config_keys(&self) -> Vec<String> {
         match self {
@@ -951,16 +1520,29 @@ impl Operation {
         }
     }
 
+    /// Returns the config key prefixes that have been deleted by this operation.
+    fn get_delete_config_key_prefixes(&self) -> Vec<String> {
+        match self {
+            Self::UpdateConfig {
+                delete_key_prefixes: Some(prefixes),
+                ..
+            } => prefixes.clone(),
+            _ => Vec::<String>::new(),
+        }
+    }
+
     pub(crate) fn modifies_same_metadata(&self, other: &Self) -> bool {
         match (self, other) {
             (
                 Self::UpdateConfig {
                     schema_metadata,
+                    schema_metadata_delete_keys,
                     field_metadata,
                     ..
                 },
                 Self::UpdateConfig {
                     schema_metadata: other_schema_metadata,
+                    schema_metadata_delete_keys: other_schema_metadata_delete_keys,
                     field_metadata: other_field_metadata,
                     ..
                 },
@@ -968,6 +1550,22 @@ impl Operation {
                 if schema_metadata.is_some() && other_schema_metadata.is_some() {
                     return true;
                 }
+                // Deleting a schema metadata key that the other sets depends on the order
+                let deletes_set_key =
+                    |delete_keys: &Option<Vec<String>>,
+                     metadata: &Option<HashMap<String, String>>| {
+                        metadata.as_ref().is_some_and(|metadata| {
+                            delete_keys
+                                .iter()
+                                .flatten()
+                                .any(|key| metadata.contains_key(key))
+                        })
+                    };
+                if deletes_set_key(schema_metadata_delete_keys, other_schema_metadata)
+                    || deletes_set_key(other_schema_metadata_delete_keys, schema_metadata)
+                {
+                    return true;
+                }
                 if let Some(field_metadata) = field_metadata {
                     if let Some(other_field_metadata) = other_field_metadata {
                         for field in field_metadata.keys() {
@@ -991,31 +1589,617 @@ impl Operation {
         let self_delete_keys = self.get_delete_config_keys();
         let other_delete_keys = other.get_delete_config_keys();
 
-        self_upsert_keys
-            .iter()
-            .any(|x| other_upsert_keys.contains(x) || other_delete_keys.contains(x))
-            || other_upsert_keys
-                .iter()
-                .any(|x| self_upsert_keys.contains(x) || self_delete_keys.contains(x))
+        let self_delete_prefixes = self.get_delete_config_key_prefixes();
+        let other_delete_prefixes = other.get_delete_config_key_prefixes();
+
+        self_upsert_keys.iter().any(|x| {
+            other_upsert_keys.contains(x)
+                || other_delete_keys.contains(x)
+                || other_delete_prefixes.iter().any(|p| x.starts_with(p))
+        }) || other_upsert_keys.iter().any(|x| {
+            self_upsert_keys.contains(x)
+                || self_delete_keys.contains(x)
+                || self_delete_prefixes.iter().any(|p| x.starts_with(p))
+        })
     }
 
-    pub fn name(&self) -> &str {
+    /// Returns whether this operation and `other` conflict because of their
+    /// config or metadata changes, footnote 2️⃣ of the compatibility table.
+    ///
+    /// They conflict if one of them upserts a config key that the other
+    /// upserts or deletes, or if both modify the schema metadata or the
+    /// metadata of the same field. This only looks at the two operations, so
+    /// it can be used to screen concurrent changes before committing.
+    pub fn config_conflicts_with(&self, other: &Self) -> bool {
+        self.upsert_key_conflict(other) || self.modifies_same_metadata(other)
+    }
+
+    /// Returns whether this operation conflicts with `other` because at least
+    /// one of them is a [`Operation::Restore`].
+    ///
+    /// A restore rewrites history, so it conflicts with every concurrent
+    /// operation except another restore to the same version. Returns `None`
+    /// if neither operation is a restore.
+    pub fn restore_conflicts_with(&self, other: &Self) -> Option<bool> {
+        match (self, other) {
+            (Self::Restore { version: a }, Self::Restore { version: b }) => Some(a != b),
+            (Self::Restore { .. }, _) | (_, Self::Restore { .. }) => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Returns the ids of the existing fragments that this operation updates or
+    /// removes.
+    ///
+    /// Only [`Operation::Delete`], [`Operation::Update`] and
+    /// [`Operation::Rewrite`] are covered, since these are compatible with
+    /// each other only if they touch distinct fragments. Other operations
+    /// return an empty set.
+    pub fn touched_fragment_ids(&self) -> HashSet<u64> {
         match self {
-            Self::Append { .. } => "Append",
-            Self::Delete { .. } => "Delete",
-            Self::Overwrite { .. } => "Overwrite",
-            Self::CreateIndex { .. } => "CreateIndex",
+            Self::Delete {
+                updated_fragments,
+                deleted_fragment_ids,
+                ..
+            }
+            | Self::Update {
+                updated_fragments,
+                removed_fragment_ids: deleted_fragment_ids,
+                ..
+            } => updated_fragments
+                .iter()
+                .map(|f| f.id)
+                .chain(deleted_fragment_ids.iter().copied())
+                .collect(),
+            Self::Rewrite { groups, .. } => groups
+                .iter()
+                .flat_map(|g| g.old_fragments.iter().map(|f| f.id))
+                .collect(),
+            _ => HashSet::new(),
+        }
+    }
+
+    /// The MemWALs that this operation adds, updates, removes, or flushes.
+    ///
+    /// MemWALs are identified by their [`MemWalId`] (region and generation), the
+    /// same identity used by
+    /// [`update_mem_wal_index_in_indices_list`](crate::index::mem_wal::update_mem_wal_index_in_indices_list).
+    pub(crate) fn referenced_mem_wal_ids(&self) -> HashSet<&MemWalId> {
+        match self {
+            Self::UpdateMemWalState {
+                added,
+                updated,
+                removed,
+            } => added
+                .iter()
+                .chain(updated.iter())
+                .chain(removed.iter())
+                .map(|mem_wal| &mem_wal.id)
+                .collect(),
+            Self::Update {
+                mem_wal_to_flush, ..
+            } => mem_wal_to_flush.iter().map(|mem_wal| &mem_wal.id).collect(),
+            _ => HashSet::new(),
+        }
+    }
+
+    /// Apply the schema and field metadata changes of this operation to
+    /// `schema`, the same way building a manifest does.
+    ///
+    /// For an [`Operation::UpdateConfig`], the deleted schema metadata keys are
+    /// removed first, then the schema metadata and the metadata of each listed
+    /// field are replaced, if given. Fields that don't
+    /// exist are skipped. For an [`Operation::Overwrite`], the schema metadata
+    /// and the metadata of fields with matching ids are replaced by those of
+    /// the new schema. Other operations don't change any metadata.
+    pub fn apply_metadata_to_schema(&self, schema: &mut Schema) {
+        match self {
+            Self::UpdateConfig {
+                schema_metadata,
+                schema_metadata_delete_keys,
+                field_metadata,
+                ..
+            } => {
+                if let Some(delete_keys) = schema_metadata_delete_keys {
+                    for key in delete_keys {
+                        schema.metadata.remove(key);
+                    }
+                }
+                if let Some(schema_metadata) = schema_metadata {
+                    schema.metadata = schema_metadata.clone();
+                }
+                if let Some(field_metadata) = field_metadata {
+                    for (field_id, metadata) in field_metadata {
+                        if let Some(field) = schema.field_by_id_mut(*field_id as i32) {
+                            field.metadata = metadata.clone();
+                        }
+                    }
+                }
+            }
+            Self::Overwrite {
+                schema: new_schema, ..
+            } => {
+                schema.metadata = new_schema.metadata.clone();
+                for new_field in new_schema.fields_pre_order() {
+                    if let Some(field) = schema.field_by_id_mut(new_field.id) {
+                        field.metadata = new_field.metadata.clone();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply this operation to a list of fragments, returning the resulting
+    /// fragments.
+    ///
+    /// This runs the same fragment logic as building a manifest, but without a
+    /// manifest, schema, or indices, which makes it useful for testing commit
+    /// logic. New fragments with id 0 are assigned ids starting at
+    /// `starting_id`, except for an [`Operation::Overwrite`], which starts
+    /// from 0 like a new dataset. Row ids are not assigned.
+    ///
+    /// Operations that don't change fragments return `existing` unchanged.
+    /// [`Operation::Project`], [`Operation::Restore`], and
+    /// [`Operation::DataReplacement`] depend on the schema or dataset history
+    /// and are not supported.
+    pub fn apply_to_fragments(
+        &self,
+        existing: &[Fragment],
+        starting_id: u64,
+    ) -> Result<Vec<Fragment>> {
+        let mut fragment_id = starting_id;
+        match self {
+            Self::Append { fragments } => {
+                Transaction::apply_append_fragments(existing, fragments, &mut fragment_id, None)
+            }
+            Self::Delete {
+                updated_fragments,
+                deleted_fragment_ids,
+                ..
+            } => Ok(Transaction::apply_delete_fragments(
+                existing,
+                updated_fragments,
+                deleted_fragment_ids,
+            )),
+            Self::Update {
+                removed_fragment_ids,
+                updated_fragments,
+                new_fragments,
+                ..
+            } => {
+                let mut final_fragments = Transaction::apply_update_fragments(
+                    existing,
+                    removed_fragment_ids,
+                    updated_fragments,
+                );
+                final_fragments.extend(Transaction::new_fragments_with_ids(
+                    new_fragments,
+                    &mut fragment_id,
+                    None,
+                )?);
+                Ok(final_fragments)
+            }
+            Self::Overwrite { fragments, .. } => {
+                Transaction::new_fragments_with_ids(fragments, &mut 0, None)
+            }
+            Self::Rewrite { groups, .. } => {
+                Transaction::apply_rewrite_fragments(existing, groups, &mut fragment_id, 0)
+                    .map(|(fragments, _)| fragments)
+            }
+            Self::Merge { fragments, .. } => Ok(fragments.clone()),
+            Self::CreateIndex { .. }
+            | Self::ReserveFragments { .. }
+            | Self::UpdateConfig { .. }
+            | Self::RelaxNullability { .. }
+            | Self::UpdateMemWalState { .. } => Ok(existing.to_vec()),
+            Self::Project { .. } | Self::Restore { .. } | Self::DataReplacement { .. } => {
+                Err(Error::NotSupported {
+                    source: format!(
+                        "Cannot apply a {} operation to fragments alone",
+                        self.name()
+                    )
+                    .into(),
+                    location: location!(),
+                })
+            }
+        }
+    }
+
+    /// The schema carried by the operation, if it replaces the dataset schema.
+    ///
+    /// This is the case for [`Operation::Overwrite`], [`Operation::Merge`] and
+    /// [`Operation::Project`].  Other operations keep the current schema.
+    pub fn schema(&self) -> Option<&Schema> {
+        match self {
+            Self::Overwrite { schema, .. }
+            | Self::Merge { schema, .. }
+            | Self::Project { schema } => Some(schema),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the operation only changes metadata and does not add or
+    /// remove any data files.
+    pub fn is_metadata_only(&self) -> bool {
+        // Every variant is spelled out so that new operations must be classified.
+        match self {
+            Self::UpdateConfig { .. }
+            | Self::Project { .. }
+            | Self::RelaxNullability { .. }
+            | Self::Restore { .. }
+            | Self::ReserveFragments { .. }
+            | Self::UpdateMemWalState { .. } => true,
+            Self::Append { .. }
+            | Self::Delete { .. }
+            | Self::Overwrite { .. }
+            | Self::CreateIndex { .. }
+            | Self::Rewrite { .. }
+            | Self::Merge { .. }
+            | Self::Update { .. }
+            | Self::DataReplacement { .. } => false,
+        }
+    }
+
+    /// Returns true if the operation can lose existing data.
+    ///
+    /// This covers operations that remove rows or columns
+    /// ([`Operation::Overwrite`], [`Operation::Delete`], [`Operation::Update`]
+    /// and [`Operation::Project`]), that replace existing data files
+    /// ([`Operation::Rewrite`] and [`Operation::DataReplacement`]), since a
+    /// faulty replacement loses the original data, and [`Operation::Restore`],
+    /// which discards the versions after the restored one from the latest view.
+    pub fn is_destructive(&self) -> bool {
+        // Every variant is spelled out so that new operations must be classified.
+        match self {
+            Self::Overwrite { .. }
+            | Self::Delete { .. }
+            | Self::Update { .. }
+            | Self::Project { .. }
+            | Self::Rewrite { .. }
+            | Self::DataReplacement { .. }
+            | Self::Restore { .. } => true,
+            Self::Append { .. }
+            | Self::CreateIndex { .. }
+            | Self::Merge { .. }
+            | Self::ReserveFragments { .. }
+            | Self::RelaxNullability { .. }
+            | Self::UpdateConfig { .. }
+            | Self::UpdateMemWalState { .. } => false,
+        }
+    }
+
+    /// Returns true if the operation only adds rows, columns or indices.
+    ///
+    /// These are [`Operation::Append`], [`Operation::CreateIndex`] and
+    /// [`Operation::Merge`]. An additive operation is never destructive, but
+    /// operations such as [`Operation::UpdateConfig`] are neither.
+    pub fn is_additive(&self) -> bool {
+        // Every variant is spelled out so that new operations must be classified.
+        match self {
+            Self::Append { .. } | Self::CreateIndex { .. } | Self::Merge { .. } => true,
+            Self::Overwrite { .. }
+            | Self::Delete { .. }
+            | Self::Update { .. }
+            | Self::Project { .. }
+            | Self::Rewrite { .. }
+            | Self::DataReplacement { .. }
+            | Self::Restore { .. }
+            | Self::ReserveFragments { .. }
+            | Self::RelaxNullability { .. }
+            | Self::UpdateConfig { .. }
+            | Self::UpdateMemWalState { .. } => false,
+        }
+    }
+
+    /// Add, replace, and drop indices the way this operation does.
+    ///
+    /// `schema` and `final_fragments` describe the dataset after the operation.
+    /// Remapping the fragment bitmaps of a rewrite and updating the MemWAL
+    /// index need the new fragment ids and versions, so they are left to
+    /// [`Transaction::build_manifest`].
+    fn apply_to_indices(
+        &self,
+        indices: &mut Vec<Index>,
+        schema: &Schema,
+        final_fragments: &[Fragment],
+    ) {
+        match self {
+            Self::Update {
+                updated_fragments,
+                fields_modified,
+                ..
+            } => {
+                // If we updated any fields, remove those fragments from indices covering those fields
+                Transaction::prune_updated_fields_from_indices(
+                    indices,
+                    updated_fragments,
+                    fields_modified,
+                );
+                Transaction::retain_relevant_indices(indices, schema, final_fragments);
+            }
+            // Some fields that have indices may have been removed, so we should
+            // remove those indices as well.
+            Self::Delete { .. } | Self::Merge { .. } | Self::Project { .. } => {
+                Transaction::retain_relevant_indices(indices, schema, final_fragments);
+            }
+            Self::Overwrite { .. } => indices.clear(),
+            Self::CreateIndex {
+                new_indices,
+                removed_indices,
+            } => {
+                indices.retain(|existing_index| {
+                    !new_indices
+                        .iter()
+                        .any(|new_index| new_index.name == existing_index.name)
+                        && !removed_indices
+                            .iter()
+                            .any(|old_index| old_index.uuid == existing_index.uuid)
+                });
+                indices.extend(new_indices.clone());
+            }
+            Self::Rewrite {
+                frag_reuse_index: Some(new_index),
+                ..
+            }
+            | Self::DataReplacement {
+                new_index: Some(new_index),
+                ..
+            } => {
+                // Replace any existing index of the same name
+                indices.retain(|idx| idx.name != new_index.name);
+                indices.push(new_index.clone());
+            }
+            Self::Append { .. }
+            | Self::Rewrite { .. }
+            | Self::ReserveFragments { .. }
+            | Self::Restore { .. }
+            | Self::RelaxNullability { .. }
+            | Self::UpdateConfig { .. }
+            | Self::DataReplacement { .. }
+            | Self::UpdateMemWalState { .. } => {}
+        }
+    }
+
+    /// Names of the current indices this operation would keep, and of those
+    /// it would drop.
+    ///
+    /// `schema` and `final_fragments` describe the dataset after the operation.
+    /// This applies the same index changes as committing would: an index is
+    /// dropped if it covers a field that no longer exists, or if none of the
+    /// fragments it covers remain (after an update removes the fragments whose
+    /// indexed fields were modified).  An overwrite drops every index.  An
+    /// index that is removed, or replaced by a new index of the same name, is
+    /// also dropped.  Nothing is modified.
+    pub fn indices_affected(
+        &self,
+        schema: &Schema,
+        final_fragments: &[Fragment],
+        current_indices: &[Index],
+    ) -> (Vec<String>, Vec<String>) {
+        let mut final_indices = current_indices.to_vec();
+        self.apply_to_indices(&mut final_indices, schema, final_fragments);
+
+        let retained_uuids = final_indices
+            .iter()
+            .map(|idx| idx.uuid)
+            .collect::<HashSet<_>>();
+        let (retained, dropped): (Vec<&Index>, Vec<&Index>) = current_indices
+            .iter()
+            .partition(|idx| retained_uuids.contains(&idx.uuid));
+        (
+            retained.into_iter().map(|idx| idx.name.clone()).collect(),
+            dropped.into_iter().map(|idx| idx.name.clone()).collect(),
+        )
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Append { .. } => "Append",
+            Self::Delete { .. } => "Delete",
+            Self::Overwrite { .. } => "Overwrite",
+            Self::CreateIndex { .. } => "CreateIndex",
             Self::Rewrite { .. } => "Rewrite",
             Self::Merge { .. } => "Merge",
             Self::ReserveFragments { .. } => "ReserveFragments",
             Self::Restore { .. } => "Restore",
             Self::Update { .. } => "Update",
             Self::Project { .. } => "Project",
+            Self::RelaxNullability { .. } => "RelaxNullability",
             Self::UpdateConfig { .. } => "UpdateConfig",
             Self::DataReplacement { .. } => "DataReplacement",
             Self::UpdateMemWalState { .. } => "UpdateMemWalState",
         }
     }
+
+    /// A stable integer code identifying the kind of operation.
+    ///
+    /// This is the field number of the operation in the `operation` oneof of
+    /// the `Transaction` protobuf message, e.g. 100 for Append, so codes are
+    /// never reused or changed.  New variants must be given the code of their
+    /// protobuf field.
+    pub fn op_code(&self) -> u16 {
+        // No wildcard, so adding a variant fails to compile until it has a code.
+        match self {
+            Self::Append { .. } => 100,
+            Self::Delete { .. } => 101,
+            Self::Overwrite { .. } => 102,
+            Self::CreateIndex { .. } => 103,
+            Self::Rewrite { .. } => 104,
+            Self::Merge { .. } => 105,
+            Self::Restore { .. } => 106,
+            Self::ReserveFragments { .. } => 107,
+            Self::Update { .. } => 108,
+            Self::Project { .. } => 109,
+            Self::UpdateConfig { .. } => 110,
+            Self::DataReplacement { .. } => 111,
+            Self::UpdateMemWalState { .. } => 112,
+            Self::RelaxNullability { .. } => 113,
+        }
+    }
+
+    /// Counts of what this operation changes, for observability.
+    ///
+    /// The counts only look at the operation itself, see [`OperationMetrics`].
+    pub fn metrics(&self) -> OperationMetrics {
+        // Every variant is spelled out so that new operations must be counted.
+        match self {
+            Self::Append { fragments } => OperationMetrics {
+                fragments_added: fragments.len(),
+                ..Default::default()
+            },
+            Self::Delete {
+                updated_fragments,
+                deleted_fragment_ids,
+                ..
+            } => OperationMetrics {
+                fragments_removed: deleted_fragment_ids.len(),
+                fragments_updated: updated_fragments.len(),
+                ..Default::default()
+            },
+            Self::Overwrite {
+                fragments,
+                config_upsert_values,
+                ..
+            } => OperationMetrics {
+                fragments_added: fragments.len(),
+                config_keys_changed: config_upsert_values.as_ref().map_or(0, |c| c.len()),
+                ..Default::default()
+            },
+            Self::CreateIndex {
+                new_indices,
+                removed_indices,
+            } => OperationMetrics {
+                indices_added: new_indices.len(),
+                indices_removed: removed_indices.len(),
+                ..Default::default()
+            },
+            Self::Rewrite {
+                groups,
+                frag_reuse_index,
+                ..
+            } => OperationMetrics {
+                fragments_added: groups.iter().map(|g| g.new_fragments.len()).sum(),
+                fragments_removed: groups.iter().map(|g| g.old_fragments.len()).sum(),
+                indices_added: usize::from(frag_reuse_index.is_some()),
+                ..Default::default()
+            },
+            Self::DataReplacement {
+                replacements,
+                new_index,
+            } => OperationMetrics {
+                fragments_updated: replacements.len(),
+                indices_added: usize::from(new_index.is_some()),
+                ..Default::default()
+            },
+            Self::Merge { fragments, .. } => OperationMetrics {
+                fragments_updated: fragments.len(),
+                ..Default::default()
+            },
+            Self::Update {
+                removed_fragment_ids,
+                updated_fragments,
+                new_fragments,
+                ..
+            } => OperationMetrics {
+                fragments_added: new_fragments.len(),
+                fragments_removed: removed_fragment_ids.len(),
+                fragments_updated: updated_fragments.len(),
+                ..Default::default()
+            },
+            Self::UpdateConfig {
+                upsert_values,
+                delete_keys,
+                delete_key_prefixes,
+                ..
+            } => OperationMetrics {
+                config_keys_changed: upsert_values.as_ref().map_or(0, |v| v.len())
+                    + delete_keys.as_ref().map_or(0, |k| k.len())
+                    + delete_key_prefixes.as_ref().map_or(0, |p| p.len()),
+                ..Default::default()
+            },
+            Self::Restore { .. }
+            | Self::ReserveFragments { .. }
+            | Self::Project { .. }
+            | Self::RelaxNullability { .. }
+            | Self::UpdateMemWalState { .. } => OperationMetrics::default(),
+        }
+    }
+}
+
+/// A summary of the manifest a transaction would produce, see [`Transaction::preview`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestPreview {
+    /// The number of fragments in the new manifest
+    pub num_fragments: usize,
+    /// Names of the existing indices that would be kept (possibly modified)
+    pub retained_indices: Vec<String>,
+    /// Names of the existing indices that would be dropped
+    pub dropped_indices: Vec<String>,
+    /// The schema of the new manifest
+    pub schema: Schema,
+}
+
+/// Counts of what an operation changes, see [`Operation::metrics`].
+///
+/// Only the operation itself is looked at, so fragments that are dropped
+/// implicitly, such as the ones replaced by an [`Operation::Overwrite`], are
+/// not counted.  Indices remapped by a rewrite are not counted either.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OperationMetrics {
+    /// Fragments added to the dataset
+    pub fragments_added: usize,
+    /// Existing fragments removed from the dataset
+    pub fragments_removed: usize,
+    /// Existing fragments modified in place, e.g. with new deletions or files
+    pub fragments_updated: usize,
+    /// Indices added, including indices that replace one of the same name
+    pub indices_added: usize,
+    /// Indices explicitly removed
+    pub indices_removed: usize,
+    /// Config keys upserted or deleted, with each deleted prefix counted once
+    pub config_keys_changed: usize,
+}
+
+/// How two concurrent operations interact, see [`Transaction::classify_conflict`].
+///
+/// The overlaps follow the footnotes of the compatibility table in the
+/// [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictReason {
+    /// The operations could conflict depending on the fragments, config keys,
+    /// metadata, or regions they touch, but they don't. The transaction can be
+    /// committed.
+    DisjointOk,
+    /// Delete, update, rewrite, or data replacement of the same fragments (1️⃣).
+    SameFragments,
+    /// One operation upserts a config key that the other upserts or deletes (2️⃣).
+    ConfigKeyOverlap,
+    /// Both operations modify the schema metadata or the same field metadata,
+    /// or an update of the metadata meets an overwrite (2️⃣).
+    SchemaMetadataOverlap,
+    /// The other operation modifies the region of a column being replaced (3️⃣).
+    ReplacedRegionOverlap,
+    /// The new index covers a rewritten fragment (4️⃣).
+    IndexVsRewrite,
+    /// Both operations are restores, to different versions.
+    Restore,
+    /// Both operations reference the same MemWAL.
+    SameMemWal,
+    /// A merge or project drops a field made nullable by the other operation,
+    /// or keeps it non-nullable.
+    RelaxedFieldOverlap,
+    /// The operations conflict whatever they touch.
+    Incompatible,
+}
+
+/// Whether `index` covers the fragment. An index without a fragment bitmap
+/// covers every fragment.
+pub(crate) fn index_covers(index: &Index, fragment_id: u64) -> bool {
+    index
+        .fragment_bitmap
+        .as_ref()
+        .is_none_or(|bitmap| bitmap.contains(fragment_id as u32))
 }
 
 impl Transaction {
@@ -1030,10 +2214,32 @@ impl Transaction {
         }
     }
 
+    /// Create a compaction transaction, see [`Operation::compact`].
+    pub fn compaction(read_version: u64, groups: Vec<RewriteGroup>) -> Self {
+        Self::new_from_version(read_version, Operation::compact(groups))
+    }
+
     pub fn with_blobs_op(self, blobs_op: Option<Operation>) -> Self {
         Self { blobs_op, ..self }
     }
 
+    /// Replace the randomly generated uuid of the transaction, e.g. to get
+    /// reproducible serialized transactions in tests.
+    ///
+    /// Returns an error if `uuid` is not a hyphenated UUID such as
+    /// `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+    pub fn with_uuid(self, uuid: impl Into<String>) -> Result<Self> {
+        let uuid = uuid.into();
+        // The hyphenated form is the only one that is 36 characters long
+        if uuid.len() != 36 || Uuid::try_parse(&uuid).is_err() {
+            return Err(Error::invalid_input(
+                format!("Transaction uuid {} is not a hyphenated UUID", uuid),
+                location!(),
+            ));
+        }
+        Ok(Self { uuid, ..self })
+    }
+
     pub fn new(
         read_version: u64,
         operation: Operation,
@@ -1050,42 +2256,322 @@ impl Transaction {
         }
     }
 
-    fn fragments_with_ids<'a, T>(
-        new_fragments: T,
-        fragment_id: &'a mut u64,
-    ) -> impl Iterator<Item = Fragment> + 'a
-    where
-        T: IntoIterator<Item = Fragment> + 'a,
-    {
-        new_fragments.into_iter().map(move |mut f| {
-            if f.id == 0 {
-                f.id = *fragment_id;
-                *fragment_id += 1;
-            }
-            f
-        })
-    }
+    /// Coalesce several [`Operation::Append`] transactions into one.
+    ///
+    /// All transactions must be appends based on the same version. The new
+    /// transaction appends all of their fragments in order, and likewise for
+    /// the blobs operations. It keeps the tag only if every transaction has
+    /// the same tag, and gets a new uuid.
+    pub fn try_coalesce(txs: &[Self]) -> Result<Self> {
+        let Some(first) = txs.first() else {
+            return Err(Error::invalid_input(
+                "No transactions to coalesce",
+                location!(),
+            ));
+        };
 
-    fn data_storage_format_from_files(
-        fragments: &[Fragment],
-        user_requested: Option<LanceFileVersion>,
-    ) -> Result<DataStorageFormat> {
-        if let Some(file_version) = Fragment::try_infer_version(fragments)? {
-            // Ensure user-requested matches data files
-            if let Some(user_requested) = user_requested {
-                if user_requested != file_version {
+        let mut fragments = Vec::new();
+        let mut blob_fragments = Vec::new();
+        for tx in txs {
+            if tx.read_version != first.read_version {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Cannot coalesce transactions based on different versions {} and {}",
+                        first.read_version, tx.read_version
+                    ),
+                    location!(),
+                ));
+            }
+            match &tx.operation {
+                Operation::Append {
+                    fragments: new_fragments,
+                } => fragments.extend(new_fragments.iter().cloned()),
+                other => {
                     return Err(Error::invalid_input(
-                        format!("User requested data storage version ({}) does not match version in data files ({})", user_requested, file_version),
+                        format!(
+                            "Only Append transactions can be coalesced, found {}",
+                            other.name()
+                        ),
                         location!(),
-                    ));
+                    ))
+                }
+            }
+            match &tx.blobs_op {
+                None => {}
+                Some(Operation::Append {
+                    fragments: new_fragments,
+                }) => blob_fragments.extend(new_fragments.iter().cloned()),
+                Some(other) => {
+                    return Err(Error::invalid_input(
+                        format!(
+                            "Only Append blobs operations can be coalesced, found {}",
+                            other.name()
+                        ),
+                        location!(),
+                    ))
                 }
             }
-            Ok(DataStorageFormat::new(file_version))
-        } else {
-            // If no files use user-requested or default
-            Ok(user_requested
-                .map(DataStorageFormat::new)
-                .unwrap_or_default())
+        }
+
+        let blobs_op = if blob_fragments.is_empty() {
+            None
+        } else {
+            Some(Operation::Append {
+                fragments: blob_fragments,
+            })
+        };
+        let tag = first
+            .tag
+            .clone()
+            .filter(|tag| txs.iter().all(|tx| tx.tag.as_ref() == Some(tag)));
+        Ok(Self::new(
+            first.read_version,
+            Operation::Append { fragments },
+            blobs_op,
+            tag,
+        ))
+    }
+
+    /// Classify how this transaction interacts with `other`, a concurrent
+    /// transaction that has already been committed.
+    ///
+    /// Whether they conflict is decided by the checks the conflict resolver
+    /// runs when committing, without looking at the dataset. So a delete or
+    /// update of the same fragments as `other` is a conflict, even though
+    /// committing might be able to rebase it.
+    ///
+    /// Returns `None` if the operations are compatible whatever they touch,
+    /// and [`ConflictReason::DisjointOk`] if they are compatible but could
+    /// conflict if they overlapped. Any other reason is a conflict.
+    pub fn classify_conflict(&self, other: &Self) -> Option<ConflictReason> {
+        let mut rebase = TransactionRebase::without_dataset(self.clone());
+        let compatible = rebase.check_txn(other, other.read_version + 1).is_ok();
+        match (compatible, rebase.conflict_condition()) {
+            (true, condition) => condition.map(|_| ConflictReason::DisjointOk),
+            (false, Some((reason, true))) => Some(reason),
+            (false, _) => Some(ConflictReason::Incompatible),
+        }
+    }
+
+    /// Check that the transaction is well-formed on its own, without looking at
+    /// the dataset it will be committed to.
+    ///
+    /// This checks that:
+    /// * the blobs operation, if any, is an [`Operation::Append`] or
+    ///   [`Operation::Overwrite`]
+    /// * a [`Operation::Rewrite`] has at least one group, and every group
+    ///   replaces at least one fragment
+    /// * a [`Operation::DataReplacement`] has at least one replacement
+    /// * no fragment id appears more than once in the operation. New fragments
+    ///   with id 0 have not been assigned an id yet and are not checked.
+    /// * the tag, if any, is non-empty, has no control characters, and is at
+    ///   most [`MAX_TAG_LENGTH`] bytes long
+    ///
+    /// See [`validate_operation`] for checks against the current manifest.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(tag) = &self.tag {
+            Self::validate_tag(tag)?;
+        }
+
+        if let Some(blobs_op) = &self.blobs_op {
+            if !matches!(
+                blobs_op,
+                Operation::Append { .. } | Operation::Overwrite { .. }
+            ) {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Blobs operation must be Append or Overwrite, but was {}",
+                        blobs_op.name()
+                    ),
+                    location!(),
+                ));
+            }
+            Self::validate_unique_fragment_ids(blobs_op)?;
+        }
+
+        match &self.operation {
+            Operation::Rewrite { groups, .. } => {
+                if groups.is_empty() {
+                    return Err(Error::invalid_input(
+                        "Rewrite operation must have at least one group",
+                        location!(),
+                    ));
+                }
+                if groups.iter().any(|g| g.old_fragments.is_empty()) {
+                    return Err(Error::invalid_input(
+                        "Rewrite group must replace at least one fragment",
+                        location!(),
+                    ));
+                }
+            }
+            Operation::DataReplacement { replacements, .. } => {
+                if replacements.is_empty() {
+                    return Err(Error::invalid_input(
+                        "DataReplacement operation must have at least one replacement",
+                        location!(),
+                    ));
+                }
+            }
+            _ => {}
+        }
+        Self::validate_unique_fragment_ids(&self.operation)
+    }
+
+    fn validate_tag(tag: &str) -> Result<()> {
+        if tag.is_empty() {
+            // An empty tag can't be told apart from no tag once serialized.
+            return Err(Error::invalid_input(
+                "Transaction tag must not be empty, use no tag instead",
+                location!(),
+            ));
+        }
+        if tag.len() > MAX_TAG_LENGTH {
+            return Err(Error::invalid_input(
+                format!(
+                    "Transaction tag must be at most {} bytes, but was {} bytes",
+                    MAX_TAG_LENGTH,
+                    tag.len()
+                ),
+                location!(),
+            ));
+        }
+        if tag.chars().any(char::is_control) {
+            return Err(Error::invalid_input(
+                format!(
+                    "Transaction tag must not contain control characters: {:?}",
+                    tag
+                ),
+                location!(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_unique_fragment_ids(operation: &Operation) -> Result<()> {
+        let (existing_ids, new_fragments): (Vec<u64>, Vec<&Fragment>) = match operation {
+            Operation::Append { fragments } | Operation::Overwrite { fragments, .. } => {
+                (vec![], fragments.iter().collect())
+            }
+            Operation::Delete {
+                updated_fragments,
+                deleted_fragment_ids,
+                ..
+            } => (
+                updated_fragments
+                    .iter()
+                    .map(|f| f.id)
+                    .chain(deleted_fragment_ids.iter().copied())
+                    .collect(),
+                vec![],
+            ),
+            Operation::Update {
+                removed_fragment_ids,
+                updated_fragments,
+                new_fragments,
+                ..
+            } => (
+                updated_fragments
+                    .iter()
+                    .map(|f| f.id)
+                    .chain(removed_fragment_ids.iter().copied())
+                    .collect(),
+                new_fragments.iter().collect(),
+            ),
+            Operation::Rewrite { groups, .. } => (
+                groups
+                    .iter()
+                    .flat_map(|g| g.old_fragments.iter().map(|f| f.id))
+                    .collect(),
+                groups.iter().flat_map(|g| g.new_fragments.iter()).collect(),
+            ),
+            Operation::Merge { fragments, .. } => {
+                (fragments.iter().map(|f| f.id).collect(), vec![])
+            }
+            Operation::DataReplacement { replacements, .. } => {
+                (replacements.iter().map(|r| r.0).collect(), vec![])
+            }
+            _ => (vec![], vec![]),
+        };
+
+        let mut seen = HashSet::new();
+        let assigned_new_ids = new_fragments.iter().map(|f| f.id).filter(|id| *id != 0);
+        if let Some(duplicate) = existing_ids
+            .into_iter()
+            .chain(assigned_new_ids)
+            .find(|id| !seen.insert(*id))
+        {
+            return Err(Error::invalid_input(
+                format!(
+                    "Fragment id {} appears more than once in {} operation",
+                    duplicate,
+                    operation.name()
+                ),
+                location!(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Assign ids to new fragments, starting from `fragment_id`.
+    ///
+    /// Fragment id 0 is reserved to mean "unassigned": writers create new
+    /// fragments with id 0 and they receive the next available id here. Any
+    /// other id was already reserved (see [`Operation::ReserveFragments`]) and
+    /// is kept as is. Ids are handed out in increasing order, and running out
+    /// of ids is an error rather than wrapping around.
+    fn fragments_with_ids<'a, T>(
+        new_fragments: T,
+        fragment_id: &'a mut u64,
+    ) -> impl Iterator<Item = Result<Fragment>> + 'a
+    where
+        T: IntoIterator<Item = Fragment> + 'a,
+    {
+        new_fragments.into_iter().map(move |mut f| {
+            if f.id == 0 {
+                f.id = *fragment_id;
+                *fragment_id = fragment_id.checked_add(1).ok_or_else(|| Error::Internal {
+                    message: format!("Ran out of fragment ids after assigning id {}", f.id),
+                    location: location!(),
+                })?;
+            }
+            Ok(f)
+        })
+    }
+
+    /// Assign ids to new fragments, and row ids if `next_row_id` is set.
+    fn new_fragments_with_ids(
+        fragments: &[Fragment],
+        fragment_id: &mut u64,
+        next_row_id: Option<&mut u64>,
+    ) -> Result<Vec<Fragment>> {
+        let mut new_fragments = Self::fragments_with_ids(fragments.to_vec(), fragment_id)
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(next_row_id) = next_row_id {
+            Self::assign_row_ids(next_row_id, new_fragments.as_mut_slice())?;
+        }
+        Ok(new_fragments)
+    }
+
+    fn data_storage_format_from_files(
+        fragments: &[Fragment],
+        user_requested: Option<LanceFileVersion>,
+    ) -> Result<DataStorageFormat> {
+        if let Some(file_version) = Fragment::try_infer_version(fragments)? {
+            // Ensure user-requested matches data files
+            if let Some(user_requested) = user_requested {
+                if user_requested != file_version {
+                    return Err(Error::invalid_input(
+                        format!("User requested data storage version ({}) does not match version in data files ({})", user_requested, file_version),
+                        location!(),
+                    ));
+                }
+            }
+            Ok(DataStorageFormat::new(file_version))
+        } else {
+            // If no files use user-requested or default
+            Ok(user_requested
+                .map(DataStorageFormat::new)
+                .unwrap_or_default())
         }
     }
 
@@ -1107,6 +2593,75 @@ impl Transaction {
         Ok((manifest, indices))
     }
 
+    /// Preview the manifest that this transaction would produce, without
+    /// committing anything.
+    ///
+    /// This is useful for debugging conflicts or seeing which indices would be
+    /// pruned by an operation.  No transaction file is assigned and nothing is
+    /// written.
+    ///
+    /// `current_manifest` should only be None if the dataset does not yet exist.
+    pub fn preview(
+        &self,
+        current_manifest: Option<&Manifest>,
+        current_indices: &[Index],
+    ) -> Result<ManifestPreview> {
+        if matches!(self.operation, Operation::Restore { .. }) {
+            return Err(Error::NotSupported {
+                source: "Cannot preview a Restore operation, it loads an old manifest".into(),
+                location: location!(),
+            });
+        }
+        let (manifest, final_indices) = self.build_manifest(
+            current_manifest,
+            current_indices.to_vec(),
+            "",
+            &ManifestWriteConfig::default(),
+            None,
+        )?;
+
+        let retained_uuids = final_indices
+            .iter()
+            .map(|idx| idx.uuid)
+            .collect::<HashSet<_>>();
+        let retained_names = final_indices
+            .iter()
+            .map(|idx| idx.name.as_str())
+            .collect::<HashSet<_>>();
+        let (retained, dropped): (Vec<&Index>, Vec<&Index>) =
+            current_indices.iter().partition(|idx| {
+                // Rewritten indices get a new uuid but keep their name
+                retained_uuids.contains(&idx.uuid) || retained_names.contains(idx.name.as_str())
+            });
+
+        Ok(ManifestPreview {
+            num_fragments: manifest.fragments.len(),
+            retained_indices: retained.into_iter().map(|idx| idx.name.clone()).collect(),
+            dropped_indices: dropped.into_iter().map(|idx| idx.name.clone()).collect(),
+            schema: manifest.schema,
+        })
+    }
+
+    /// The fragment ids reserved by committing this transaction on top of
+    /// `current_manifest`.
+    ///
+    /// Only [`Operation::ReserveFragments`] reserves ids; any other operation
+    /// returns an empty range.  The reserved ids start right after the current
+    /// max fragment id and are never handed out to new fragments once the
+    /// reservation is committed, so callers can assign them to fragments they
+    /// write themselves.
+    pub fn reserved_fragment_range(&self, current_manifest: Option<&Manifest>) -> Range<u64> {
+        // Id 0 means "unassigned", so an empty dataset reserves from 1
+        let start = current_manifest
+            .and_then(|m| m.max_fragment_id())
+            .unwrap_or(0)
+            + 1;
+        match self.operation {
+            Operation::ReserveFragments { num_fragments } => start..start + num_fragments as u64,
+            _ => start..start,
+        }
+    }
+
     /// Create a new manifest from the current manifest and the transaction.
     ///
     /// `current_manifest` should only be None if the dataset does not yet exist.
@@ -1123,28 +2678,39 @@ impl Transaction {
                 .map(|m| !m.uses_move_stable_row_ids())
                 .unwrap_or_default()
         {
-            return Err(Error::NotSupported {
-                source: "Cannot enable stable row ids on existing dataset".into(),
-                location: location!(),
-            });
+            return Err(Error::stable_row_id_incompatibility(location!()));
         }
 
         // Get the schema and the final fragment list
-        let schema = match self.operation {
-            Operation::Overwrite { ref schema, .. } => schema.clone(),
-            Operation::Merge { ref schema, .. } => schema.clone(),
-            Operation::Project { ref schema, .. } => schema.clone(),
-            _ => {
-                if let Some(current_manifest) = current_manifest {
-                    current_manifest.schema.clone()
-                } else {
-                    return Err(Error::Internal {
-                        message: "Cannot create a new dataset without a schema".to_string(),
-                        location: location!(),
-                    });
-                }
+        let mut schema = match (self.operation.schema(), current_manifest) {
+            (Some(schema), _) => schema.clone(),
+            (None, Some(current_manifest)) => current_manifest.schema.clone(),
+            (None, None) => {
+                return Err(Error::Internal {
+                    message: "Cannot create a new dataset without a schema".to_string(),
+                    location: location!(),
+                });
             }
         };
+        if let Operation::RelaxNullability { ref field_ids } = self.operation {
+            for field_id in field_ids {
+                let field = schema.field_by_id_mut(*field_id as i32).ok_or_else(|| {
+                    Error::invalid_input(
+                        format!("Cannot make field {} nullable, it does not exist", field_id),
+                        location!(),
+                    )
+                })?;
+                field.nullable = true;
+            }
+        }
+        if let Operation::UpdateConfig {
+            field_metadata: Some(ref field_metadata),
+            ..
+        } = self.operation
+        {
+            // Check every field exists before applying any of the updates
+            validate_field_metadata_ids(&schema, field_metadata)?;
+        }
 
         let mut fragment_id = if matches!(self.operation, Operation::Overwrite { .. }) {
             0
@@ -1168,10 +2734,7 @@ impl Transaction {
                 (None, true) => Some(0),
                 (_, false) => None,
                 (Some(_), true) => {
-                    return Err(Error::NotSupported {
-                        source: "Cannot enable stable row ids on existing dataset".into(),
-                        location: location!(),
-                    });
+                    return Err(Error::stable_row_id_incompatibility(location!()));
                 }
             }
         };
@@ -1189,65 +2752,41 @@ impl Transaction {
 
         match &self.operation {
             Operation::Append { ref fragments } => {
-                final_fragments.extend(maybe_existing_fragments?.clone());
-                let mut new_fragments =
-                    Self::fragments_with_ids(fragments.clone(), &mut fragment_id)
-                        .collect::<Vec<_>>();
-                if let Some(next_row_id) = &mut next_row_id {
-                    Self::assign_row_ids(next_row_id, new_fragments.as_mut_slice())?;
-                }
-                final_fragments.extend(new_fragments);
+                final_fragments = Self::apply_append_fragments(
+                    maybe_existing_fragments?,
+                    fragments,
+                    &mut fragment_id,
+                    next_row_id.as_mut(),
+                )?;
             }
             Operation::Delete {
                 ref updated_fragments,
                 ref deleted_fragment_ids,
                 ..
             } => {
-                // Remove the deleted fragments
-                final_fragments.extend(maybe_existing_fragments?.clone());
-                final_fragments.retain(|f| !deleted_fragment_ids.contains(&f.id));
-                final_fragments.iter_mut().for_each(|f| {
-                    for updated in updated_fragments {
-                        if updated.id == f.id {
-                            *f = updated.clone();
-                        }
-                    }
-                });
-                Self::retain_relevant_indices(&mut final_indices, &schema, &final_fragments)
+                final_fragments.extend(Self::apply_delete_fragments(
+                    maybe_existing_fragments?,
+                    updated_fragments,
+                    deleted_fragment_ids,
+                ));
             }
             Operation::Update {
                 removed_fragment_ids,
                 updated_fragments,
                 new_fragments,
-                fields_modified,
                 mem_wal_to_flush,
+                ..
             } => {
-                final_fragments.extend(maybe_existing_fragments?.iter().filter_map(|f| {
-                    if removed_fragment_ids.contains(&f.id) {
-                        return None;
-                    }
-                    if let Some(updated) = updated_fragments.iter().find(|uf| uf.id == f.id) {
-                        Some(updated.clone())
-                    } else {
-                        Some(f.clone())
-                    }
-                }));
-
-                // If we updated any fields, remove those fragments from indices covering those fields
-                Self::prune_updated_fields_from_indices(
-                    &mut final_indices,
+                final_fragments.extend(Self::apply_update_fragments(
+                    maybe_existing_fragments?,
+                    removed_fragment_ids,
                     updated_fragments,
-                    fields_modified,
-                );
-
-                let mut new_fragments =
-                    Self::fragments_with_ids(new_fragments.clone(), &mut fragment_id)
-                        .collect::<Vec<_>>();
-                if let Some(next_row_id) = &mut next_row_id {
-                    Self::assign_row_ids(next_row_id, new_fragments.as_mut_slice())?;
-                }
-                final_fragments.extend(new_fragments);
-                Self::retain_relevant_indices(&mut final_indices, &schema, &final_fragments);
+                ));
+                final_fragments.extend(Self::new_fragments_with_ids(
+                    new_fragments,
+                    &mut fragment_id,
+                    next_row_id.as_mut(),
+                )?);
 
                 if let Some(mem_wal_to_flush) = mem_wal_to_flush {
                     update_mem_wal_index_in_indices_list(
@@ -1264,28 +2803,26 @@ impl Transaction {
                 }
             }
             Operation::Overwrite { ref fragments, .. } => {
-                let mut new_fragments =
-                    Self::fragments_with_ids(fragments.clone(), &mut fragment_id)
-                        .collect::<Vec<_>>();
-                if let Some(next_row_id) = &mut next_row_id {
-                    Self::assign_row_ids(next_row_id, new_fragments.as_mut_slice())?;
-                }
-                final_fragments.extend(new_fragments);
-                final_indices = Vec::new();
+                final_fragments = Self::new_fragments_with_ids(
+                    fragments,
+                    &mut fragment_id,
+                    next_row_id.as_mut(),
+                )?;
             }
             Operation::Rewrite {
                 ref groups,
                 ref rewritten_indices,
-                ref frag_reuse_index,
+                ..
             } => {
-                final_fragments.extend(maybe_existing_fragments?.clone());
                 let current_version = current_manifest.map(|m| m.version).unwrap_or_default();
-                Self::handle_rewrite_fragments(
-                    &mut final_fragments,
+                let (fragments, groups) = Self::apply_rewrite_fragments(
+                    maybe_existing_fragments?,
                     groups,
                     &mut fragment_id,
                     current_version,
                 )?;
+                final_fragments = fragments;
+                let groups = &groups;
 
                 if next_row_id.is_some() {
                     // We can re-use indices, but need to rewrite the fragment bitmaps
@@ -1299,36 +2836,15 @@ impl Transaction {
                 } else {
                     Self::handle_rewrite_indices(&mut final_indices, rewritten_indices, groups)?;
                 }
-
-                if let Some(frag_reuse_index) = frag_reuse_index {
-                    final_indices.retain(|idx| idx.name != frag_reuse_index.name);
-                    final_indices.push(frag_reuse_index.clone());
-                }
-            }
-            Operation::CreateIndex {
-                new_indices,
-                removed_indices,
-            } => {
-                final_fragments.extend(maybe_existing_fragments?.clone());
-                final_indices.retain(|existing_index| {
-                    !new_indices
-                        .iter()
-                        .any(|new_index| new_index.name == existing_index.name)
-                        && !removed_indices
-                            .iter()
-                            .any(|old_index| old_index.uuid == existing_index.uuid)
-                });
-                final_indices.extend(new_indices.clone());
             }
-            Operation::ReserveFragments { .. } | Operation::UpdateConfig { .. } => {
+            Operation::CreateIndex { .. }
+            | Operation::ReserveFragments { .. }
+            | Operation::UpdateConfig { .. }
+            | Operation::RelaxNullability { .. } => {
                 final_fragments.extend(maybe_existing_fragments?.clone());
             }
             Operation::Merge { ref fragments, .. } => {
                 final_fragments.extend(fragments.clone());
-
-                // Some fields that have indices may have been removed, so we should
-                // remove those indices as well.
-                Self::retain_relevant_indices(&mut final_indices, &schema, &final_fragments)
             }
             Operation::Project { .. } => {
                 final_fragments.extend(maybe_existing_fragments?.clone());
@@ -1346,20 +2862,16 @@ impl Transaction {
                             .any(|field_id| remaining_field_ids.contains(field_id))
                     });
                 }
-
-                // Some fields that have indices may have been removed, so we should
-                // remove those indices as well.
-                Self::retain_relevant_indices(&mut final_indices, &schema, &final_fragments)
             }
             Operation::Restore { .. } => {
                 unreachable!()
             }
-            Operation::DataReplacement { replacements } => {
+            Operation::DataReplacement { replacements, .. } => {
                 log::warn!("Building manifest with DataReplacement operation. This operation is not stable yet, please use with caution.");
 
                 let (old_fragment_ids, new_datafiles): (Vec<&u64>, Vec<&DataFile>) = replacements
                     .iter()
-                    .map(|DataReplacementGroup(fragment_id, new_file)| (fragment_id, new_file))
+                    .map(|DataReplacementGroup(fragment_id, new_file, _)| (fragment_id, new_file))
                     .unzip();
 
                 // 1. make sure the new files all have the same fields / or empty
@@ -1392,20 +2904,29 @@ impl Transaction {
 
                 // 2. check that the fragments being modified have isomorphic layouts along the columns being replaced
                 // 3. add modified fragments to final_fragments
-                for (frag_id, new_file) in old_fragment_ids.iter().zip(new_datafiles) {
+                for DataReplacementGroup(frag_id, new_file, num_rows) in replacements {
                     let frag = existing_fragments
                         .iter()
-                        .find(|f| f.id == **frag_id)
+                        .find(|f| f.id == *frag_id)
                         .ok_or_else(|| {
                             Error::invalid_input(
                                 "Fragment being replaced not found in existing fragments",
                                 location!(),
                             )
                         })?;
+                    if let (Some(num_rows), Some(physical_rows)) = (num_rows, frag.physical_rows) {
+                        if *num_rows != physical_rows as u64 {
+                            return Err(Error::invalid_input(
+                                format!(
+                                    "Data file {} has {} rows, but fragment {} being replaced has {} rows",
+                                    new_file.path, num_rows, frag_id, physical_rows
+                                ),
+                                location!(),
+                            ));
+                        }
+                    }
                     let mut new_frag = frag.clone();
 
-                    // TODO(rmeng): check new file and fragment are the same length
-
                     let mut columns_covered = HashSet::new();
                     for file in &mut new_frag.files {
                         if file.fields == new_file.fields
@@ -1475,6 +2996,8 @@ impl Transaction {
                 )?;
             }
         };
+        self.operation
+            .apply_to_indices(&mut final_indices, &schema, &final_fragments);
 
         // If a fragment was reserved then it may not belong at the end of the fragments list.
         final_fragments.sort_by_key(|frag| frag.id);
@@ -1531,8 +3054,8 @@ impl Transaction {
             Operation::UpdateConfig {
                 upsert_values,
                 delete_keys,
-                schema_metadata,
-                field_metadata,
+                delete_key_prefixes,
+                ..
             } => {
                 // Delete is handled first. If the same key is referenced by upsert and
                 // delete, then upserted key-value pair will remain.
@@ -1545,17 +3068,20 @@ impl Transaction {
                             .as_slice(),
                     )
                 }
+                if let Some(delete_key_prefixes) = delete_key_prefixes {
+                    manifest.delete_config_keys_by_prefix(
+                        delete_key_prefixes
+                            .iter()
+                            .map(|s| s.as_str())
+                            .collect::<Vec<_>>()
+                            .as_slice(),
+                    )
+                }
                 if let Some(upsert_values) = upsert_values {
                     manifest.update_config(upsert_values.clone());
                 }
-                if let Some(schema_metadata) = schema_metadata {
-                    manifest.replace_schema_metadata(schema_metadata.clone());
-                }
-                if let Some(field_metadata) = field_metadata {
-                    for (field_id, metadata) in field_metadata {
-                        manifest.replace_field_metadata(*field_id as i32, metadata.clone());
-                    }
-                }
+                self.operation
+                    .apply_metadata_to_schema(&mut manifest.schema);
             }
             _ => {}
         }
@@ -1635,28 +3161,28 @@ impl Transaction {
     ) -> Result<RoaringBitmap> {
         let mut new_bitmap = old.clone();
         for group in groups {
-            let any_in_index = group
-                .old_fragments
-                .iter()
-                .any(|frag| old.contains(frag.id as u32));
-            let all_in_index = group
+            // Single pass over the group to count how many of the old fragments
+            // were covered by the index.
+            let num_in_index = group
                 .old_fragments
                 .iter()
-                .all(|frag| old.contains(frag.id as u32));
+                .filter(|frag| old.contains(frag.id as u32))
+                .count();
             // Any rewrite group may or may not be covered by the index.  However, if any fragment
             // in a rewrite group was previously covered by the index then all fragments in the rewrite
             // group must have been previously covered by the index.  plan_compaction takes care of
             // this for us so this should be safe to assume.
-            if any_in_index {
-                if all_in_index {
-                    for frag_id in group.old_fragments.iter().map(|frag| frag.id as u32) {
-                        new_bitmap.remove(frag_id);
-                    }
-                    new_bitmap.extend(group.new_fragments.iter().map(|frag| frag.id as u32));
-                } else {
-                    return Err(Error::invalid_input("The compaction plan included a rewrite group that was a split of indexed and non-indexed data", location!()));
-                }
+            if num_in_index == 0 {
+                // Group is entirely disjoint from the index, nothing to do
+                continue;
+            }
+            if num_in_index != group.old_fragments.len() {
+                return Err(Error::invalid_input("The compaction plan included a rewrite group that was a split of indexed and non-indexed data", location!()));
+            }
+            for frag_id in group.old_fragments.iter().map(|frag| frag.id as u32) {
+                new_bitmap.remove(frag_id);
             }
+            new_bitmap.extend(group.new_fragments.iter().map(|frag| frag.id as u32));
         }
         Ok(new_bitmap)
     }
@@ -1703,6 +3229,98 @@ impl Transaction {
         Ok(())
     }
 
+    /// Add the new fragments after the existing ones.
+    fn apply_append_fragments(
+        existing_fragments: &[Fragment],
+        fragments: &[Fragment],
+        fragment_id: &mut u64,
+        next_row_id: Option<&mut u64>,
+    ) -> Result<Vec<Fragment>> {
+        let mut final_fragments = existing_fragments.to_vec();
+        final_fragments.extend(Self::new_fragments_with_ids(
+            fragments,
+            fragment_id,
+            next_row_id,
+        )?);
+        Ok(final_fragments)
+    }
+
+    /// Replace the old fragments of each group with its new ones. Returns the
+    /// fragments and the groups with ids assigned to their new fragments.
+    fn apply_rewrite_fragments(
+        existing_fragments: &[Fragment],
+        groups: &[RewriteGroup],
+        fragment_id: &mut u64,
+        version: u64,
+    ) -> Result<(Vec<Fragment>, Vec<RewriteGroup>)> {
+        let mut final_fragments = existing_fragments.to_vec();
+        // Assign ids up front so fragment bitmaps see the final ids
+        let groups = Self::assign_rewrite_fragment_ids(groups, fragment_id)?;
+        Self::handle_rewrite_fragments(&mut final_fragments, &groups, fragment_id, version)?;
+        Ok((final_fragments, groups))
+    }
+
+    /// Remove the deleted fragments and replace the updated ones in a single
+    /// pass. If a fragment is updated more than once, the last update wins.
+    fn apply_delete_fragments(
+        existing_fragments: &[Fragment],
+        updated_fragments: &[Fragment],
+        deleted_fragment_ids: &[u64],
+    ) -> Vec<Fragment> {
+        let deleted_ids = deleted_fragment_ids.iter().collect::<HashSet<_>>();
+        let updated_by_id = updated_fragments
+            .iter()
+            .map(|f| (f.id, f))
+            .collect::<HashMap<_, _>>();
+        existing_fragments
+            .iter()
+            .filter(|f| !deleted_ids.contains(&f.id))
+            .map(|f| updated_by_id.get(&f.id).copied().unwrap_or(f).clone())
+            .collect()
+    }
+
+    /// Remove the removed fragments and replace the updated ones. New fragments
+    /// are not included.
+    fn apply_update_fragments(
+        existing_fragments: &[Fragment],
+        removed_fragment_ids: &[u64],
+        updated_fragments: &[Fragment],
+    ) -> Vec<Fragment> {
+        existing_fragments
+            .iter()
+            .filter_map(|f| {
+                if removed_fragment_ids.contains(&f.id) {
+                    return None;
+                }
+                if let Some(updated) = updated_fragments.iter().find(|uf| uf.id == f.id) {
+                    Some(updated.clone())
+                } else {
+                    Some(f.clone())
+                }
+            })
+            .collect()
+    }
+
+    /// Returns a copy of `groups` with ids assigned to any unassigned new fragments
+    fn assign_rewrite_fragment_ids(
+        groups: &[RewriteGroup],
+        fragment_id: &mut u64,
+    ) -> Result<Vec<RewriteGroup>> {
+        groups
+            .iter()
+            .map(|group| {
+                Ok(RewriteGroup {
+                    old_fragments: group.old_fragments.clone(),
+                    new_fragments: Self::fragments_with_ids(
+                        group.new_fragments.clone(),
+                        fragment_id,
+                    )
+                    .collect::<Result<Vec<_>>>()?,
+                })
+            })
+            .collect()
+    }
+
     fn handle_rewrite_fragments(
         final_fragments: &mut Vec<Fragment>,
         groups: &[RewriteGroup],
@@ -1722,21 +3340,35 @@ impl Transaction {
                     if i == group.old_fragments.len() {
                         break Some(start..start + i);
                     }
-                    if final_fragments[start + i].id != group.old_fragments[i].id {
+                    let next_id = final_fragments.get(start + i).map(|f| f.id);
+                    if next_id != Some(group.old_fragments[i].id) {
                         break None;
                     }
                     i += 1;
                 }
             };
 
-            let new_fragments = Self::fragments_with_ids(group.new_fragments.clone(), fragment_id);
+            let new_fragments = Self::fragments_with_ids(group.new_fragments.clone(), fragment_id)
+                .collect::<Result<Vec<_>>>()?;
             if let Some(replace_range) = replace_range {
                 // Efficiently path using slice
                 final_fragments.splice(replace_range, new_fragments);
             } else {
                 // Slower path for non-contiguous ranges
                 for fragment in group.old_fragments.iter() {
+                    let num_fragments = final_fragments.len();
                     final_fragments.retain(|f| f.id != fragment.id);
+                    if final_fragments.len() == num_fragments {
+                        return Err(Error::CommitConflict {
+                            version,
+                            source: format!(
+                                "dataset does not contain a fragment a rewrite operation wants to replace: id={}",
+                                fragment.id
+                            )
+                            .into(),
+                            location: location!(),
+                        });
+                    }
                 }
                 final_fragments.extend(new_fragments);
             }
@@ -1752,7 +3384,9 @@ impl Transaction {
             })? as u64;
             let row_ids = *next_row_id..(*next_row_id + physical_rows);
             let sequence = RowIdSequence::from(row_ids);
-            // TODO: write to a separate file if large. Possibly share a file with other fragments.
+            // Inline for now. When a commit creates several fragments, their
+            // sequences are moved into one shared file before the manifest is
+            // written (see `write_shared_row_ids`).
             let serialized = write_row_ids(&sequence);
             fragment.row_id_meta = Some(RowIdMeta::Inline(serialized));
             *next_row_id += physical_rows;
@@ -1762,10 +3396,11 @@ impl Transaction {
 }
 
 impl From<&DataReplacementGroup> for pb::transaction::DataReplacementGroup {
-    fn from(DataReplacementGroup(fragment_id, new_file): &DataReplacementGroup) -> Self {
+    fn from(group: &DataReplacementGroup) -> Self {
         Self {
-            fragment_id: *fragment_id,
-            new_file: Some(new_file.into()),
+            fragment_id: group.0,
+            new_file: Some((&group.1).into()),
+            num_rows: group.num_rows(),
         }
     }
 }
@@ -1776,7 +3411,7 @@ impl TryFrom<pb::transaction::DataReplacementGroup> for DataReplacementGroup {
     type Error = Error;
 
     fn try_from(message: pb::transaction::DataReplacementGroup) -> Result<Self> {
-        Ok(Self(
+        let group = Self::new(
             message.fragment_id,
             message
                 .new_file
@@ -1785,10 +3420,29 @@ impl TryFrom<pb::transaction::DataReplacementGroup> for DataReplacementGroup {
                     location!(),
                 ))?
                 .try_into()?,
-        ))
+        );
+        Ok(match message.num_rows {
+            Some(num_rows) => group.with_num_rows(num_rows),
+            None => group,
+        })
+    }
+}
+
+/// The tag is a plain string in protobuf, so a missing tag is stored as an
+/// empty string. This is why [`Transaction::validate`] rejects empty tags.
+fn tag_to_option(tag: &str) -> Option<String> {
+    if tag.is_empty() {
+        None
+    } else {
+        Some(tag.to_string())
     }
 }
 
+/// The inverse of [`tag_to_option`].
+fn tag_from_option(tag: Option<&str>) -> String {
+    tag.unwrap_or_default().to_string()
+}
+
 impl TryFrom<pb::Transaction> for Transaction {
     type Error = Error;
 
@@ -1817,13 +3471,13 @@ impl TryFrom<pb::Transaction> for Transaction {
             Some(pb::transaction::Operation::Overwrite(pb::transaction::Overwrite {
                 fragments,
                 schema,
-                schema_metadata: _schema_metadata, // TODO: handle metadata
+                schema_metadata,
                 config_upsert_values,
             })) => {
                 let config_upsert_option = if config_upsert_values.is_empty() {
-                    Some(config_upsert_values)
-                } else {
                     None
+                } else {
+                    Some(config_upsert_values)
                 };
 
                 Operation::Overwrite {
@@ -1831,7 +3485,10 @@ impl TryFrom<pb::Transaction> for Transaction {
                         .into_iter()
                         .map(Fragment::try_from)
                         .collect::<Result<Vec<_>>>()?,
-                    schema: Schema::from(&Fields(schema)),
+                    schema: Schema::from(FieldsWithMeta {
+                        fields: Fields(schema),
+                        metadata: schema_metadata,
+                    }),
                     config_upsert_values: config_upsert_option,
                 }
             }
@@ -1843,6 +3500,7 @@ impl TryFrom<pb::Transaction> for Transaction {
                 new_fragments,
                 groups,
                 rewritten_indices,
+                frag_reuse_index,
             })) => {
                 let groups = if !groups.is_empty() {
                     groups
@@ -1869,7 +3527,7 @@ impl TryFrom<pb::Transaction> for Transaction {
                 Operation::Rewrite {
                     groups,
                     rewritten_indices,
-                    frag_reuse_index: None,
+                    frag_reuse_index: frag_reuse_index.map(Index::try_from).transpose()?,
                 }
             }
             Some(pb::transaction::Operation::CreateIndex(pb::transaction::CreateIndex {
@@ -1888,13 +3546,16 @@ impl TryFrom<pb::Transaction> for Transaction {
             Some(pb::transaction::Operation::Merge(pb::transaction::Merge {
                 fragments,
                 schema,
-                schema_metadata: _schema_metadata, // TODO: handle metadata
+                schema_metadata,
             })) => Operation::Merge {
                 fragments: fragments
                     .into_iter()
                     .map(Fragment::try_from)
                     .collect::<Result<Vec<_>>>()?,
-                schema: Schema::from(&Fields(schema)),
+                schema: Schema::from(FieldsWithMeta {
+                    fields: Fields(schema),
+                    metadata: schema_metadata,
+                }),
             },
             Some(pb::transaction::Operation::Restore(pb::transaction::Restore { version })) => {
                 Operation::Restore { version }
@@ -1916,17 +3577,26 @@ impl TryFrom<pb::Transaction> for Transaction {
                     .map(Fragment::try_from)
                     .collect::<Result<Vec<_>>>()?,
                 fields_modified,
-                mem_wal_to_flush: mem_wal_to_flush.map(|m| MemWal::try_from(m).unwrap()),
+                mem_wal_to_flush: mem_wal_to_flush.map(MemWal::try_from).transpose()?,
             },
-            Some(pb::transaction::Operation::Project(pb::transaction::Project { schema })) => {
-                Operation::Project {
-                    schema: Schema::from(&Fields(schema)),
-                }
-            }
+            Some(pb::transaction::Operation::Project(pb::transaction::Project {
+                schema,
+                schema_metadata,
+            })) => Operation::Project {
+                schema: Schema::from(FieldsWithMeta {
+                    fields: Fields(schema),
+                    metadata: schema_metadata,
+                }),
+            },
+            Some(pb::transaction::Operation::RelaxNullability(
+                pb::transaction::RelaxNullability { field_ids },
+            )) => Operation::RelaxNullability { field_ids },
             Some(pb::transaction::Operation::UpdateConfig(pb::transaction::UpdateConfig {
                 upsert_values,
                 delete_keys,
+                delete_key_prefixes,
                 schema_metadata,
+                schema_metadata_delete_keys,
                 field_metadata,
             })) => {
                 let upsert_values = match upsert_values.len() {
@@ -1937,10 +3607,18 @@ impl TryFrom<pb::Transaction> for Transaction {
                     0 => None,
                     _ => Some(delete_keys),
                 };
+                let delete_key_prefixes = match delete_key_prefixes.len() {
+                    0 => None,
+                    _ => Some(delete_key_prefixes),
+                };
                 let schema_metadata = match schema_metadata.len() {
                     0 => None,
                     _ => Some(schema_metadata),
                 };
+                let schema_metadata_delete_keys = match schema_metadata_delete_keys.len() {
+                    0 => None,
+                    _ => Some(schema_metadata_delete_keys),
+                };
                 let field_metadata = match field_metadata.len() {
                     0 => None,
                     _ => Some(
@@ -1955,17 +3633,23 @@ impl TryFrom<pb::Transaction> for Transaction {
                 Operation::UpdateConfig {
                     upsert_values,
                     delete_keys,
+                    delete_key_prefixes,
                     schema_metadata,
+                    schema_metadata_delete_keys,
                     field_metadata,
                 }
             }
             Some(pb::transaction::Operation::DataReplacement(
-                pb::transaction::DataReplacement { replacements },
+                pb::transaction::DataReplacement {
+                    replacements,
+                    new_index,
+                },
             )) => Operation::DataReplacement {
                 replacements: replacements
                     .into_iter()
                     .map(DataReplacementGroup::try_from)
                     .collect::<Result<Vec<_>>>()?,
+                new_index: new_index.map(Index::try_from).transpose()?,
             },
             Some(pb::transaction::Operation::UpdateMemWalState(
                 pb::transaction::UpdateMemWalState {
@@ -1976,16 +3660,16 @@ impl TryFrom<pb::Transaction> for Transaction {
             )) => Operation::UpdateMemWalState {
                 added: added
                     .into_iter()
-                    .map(|m| MemWal::try_from(m).unwrap())
-                    .collect(),
+                    .map(MemWal::try_from)
+                    .collect::<Result<_>>()?,
                 updated: updated
                     .into_iter()
-                    .map(|m| MemWal::try_from(m).unwrap())
-                    .collect(),
+                    .map(MemWal::try_from)
+                    .collect::<Result<_>>()?,
                 removed: removed
                     .into_iter()
-                    .map(|m| MemWal::try_from(m).unwrap())
-                    .collect(),
+                    .map(MemWal::try_from)
+                    .collect::<Result<_>>()?,
             },
             None => {
                 return Err(Error::Internal {
@@ -2008,13 +3692,13 @@ impl TryFrom<pb::Transaction> for Transaction {
                 pb::transaction::BlobOperation::BlobOverwrite(pb::transaction::Overwrite {
                     fragments,
                     schema,
-                    schema_metadata: _schema_metadata, // TODO: handle metadata
+                    schema_metadata,
                     config_upsert_values,
                 }) => {
                     let config_upsert_option = if config_upsert_values.is_empty() {
-                        Some(config_upsert_values)
-                    } else {
                         None
+                    } else {
+                        Some(config_upsert_values)
                     };
 
                     Ok(Operation::Overwrite {
@@ -2022,7 +3706,10 @@ impl TryFrom<pb::Transaction> for Transaction {
                             .into_iter()
                             .map(Fragment::try_from)
                             .collect::<Result<Vec<_>>>()?,
-                        schema: Schema::from(&Fields(schema)),
+                        schema: Schema::from(FieldsWithMeta {
+                            fields: Fields(schema),
+                            metadata: schema_metadata,
+                        }),
                         config_upsert_values: config_upsert_option,
                     })
                 }
@@ -2033,11 +3720,7 @@ impl TryFrom<pb::Transaction> for Transaction {
             uuid: message.uuid.clone(),
             operation,
             blobs_op,
-            tag: if message.tag.is_empty() {
-                None
-            } else {
-                Some(message.tag.clone())
-            },
+            tag: tag_to_option(&message.tag),
         })
     }
 }
@@ -2115,10 +3798,11 @@ impl From<&Transaction> for pb::Transaction {
                 schema,
                 config_upsert_values,
             } => {
+                let FieldsWithMeta { fields, metadata } = FieldsWithMeta::from(schema);
                 pb::transaction::Operation::Overwrite(pb::transaction::Overwrite {
                     fragments: fragments.iter().map(pb::DataFragment::from).collect(),
-                    schema: Fields::from(schema).0,
-                    schema_metadata: Default::default(), // TODO: handle metadata
+                    schema: fields.0,
+                    schema_metadata: metadata,
                     config_upsert_values: config_upsert_values
                         .clone()
                         .unwrap_or(Default::default()),
@@ -2132,7 +3816,7 @@ impl From<&Transaction> for pb::Transaction {
             Operation::Rewrite {
                 groups,
                 rewritten_indices,
-                frag_reuse_index: _,
+                frag_reuse_index,
             } => pb::transaction::Operation::Rewrite(pb::transaction::Rewrite {
                 groups: groups
                     .iter()
@@ -2142,6 +3826,7 @@ impl From<&Transaction> for pb::Transaction {
                     .iter()
                     .map(|rewritten| rewritten.into())
                     .collect(),
+                frag_reuse_index: frag_reuse_index.as_ref().map(IndexMetadata::from),
                 ..Default::default()
             }),
             Operation::CreateIndex {
@@ -2152,10 +3837,11 @@ impl From<&Transaction> for pb::Transaction {
                 removed_indices: removed_indices.iter().map(IndexMetadata::from).collect(),
             }),
             Operation::Merge { fragments, schema } => {
+                let FieldsWithMeta { fields, metadata } = FieldsWithMeta::from(schema);
                 pb::transaction::Operation::Merge(pb::transaction::Merge {
                     fragments: fragments.iter().map(pb::DataFragment::from).collect(),
-                    schema: Fields::from(schema).0,
-                    schema_metadata: Default::default(), // TODO: handle metadata
+                    schema: fields.0,
+                    schema_metadata: metadata,
                 })
             }
             Operation::Restore { version } => {
@@ -2180,19 +3866,32 @@ impl From<&Transaction> for pb::Transaction {
                     .map(pb::mem_wal_index_details::MemWal::from),
             }),
             Operation::Project { schema } => {
+                let FieldsWithMeta { fields, metadata } = FieldsWithMeta::from(schema);
                 pb::transaction::Operation::Project(pb::transaction::Project {
-                    schema: Fields::from(schema).0,
+                    schema: fields.0,
+                    schema_metadata: metadata,
+                })
+            }
+            Operation::RelaxNullability { field_ids } => {
+                pb::transaction::Operation::RelaxNullability(pb::transaction::RelaxNullability {
+                    field_ids: field_ids.clone(),
                 })
             }
             Operation::UpdateConfig {
                 upsert_values,
                 delete_keys,
+                delete_key_prefixes,
                 schema_metadata,
+                schema_metadata_delete_keys,
                 field_metadata,
             } => pb::transaction::Operation::UpdateConfig(pb::transaction::UpdateConfig {
                 upsert_values: upsert_values.clone().unwrap_or(Default::default()),
                 delete_keys: delete_keys.clone().unwrap_or(Default::default()),
+                delete_key_prefixes: delete_key_prefixes.clone().unwrap_or(Default::default()),
                 schema_metadata: schema_metadata.clone().unwrap_or(Default::default()),
+                schema_metadata_delete_keys: schema_metadata_delete_keys
+                    .clone()
+                    .unwrap_or(Default::default()),
                 field_metadata: field_metadata
                     .as_ref()
                     .map(|field_metadata| {
@@ -2210,14 +3909,16 @@ impl From<&Transaction> for pb::Transaction {
                     })
                     .unwrap_or(Default::default()),
             }),
-            Operation::DataReplacement { replacements } => {
-                pb::transaction::Operation::DataReplacement(pb::transaction::DataReplacement {
-                    replacements: replacements
-                        .iter()
-                        .map(pb::transaction::DataReplacementGroup::from)
-                        .collect(),
-                })
-            }
+            Operation::DataReplacement {
+                replacements,
+                new_index,
+            } => pb::transaction::Operation::DataReplacement(pb::transaction::DataReplacement {
+                replacements: replacements
+                    .iter()
+                    .map(pb::transaction::DataReplacementGroup::from)
+                    .collect(),
+                new_index: new_index.as_ref().map(IndexMetadata::from),
+            }),
             Operation::UpdateMemWalState {
                 added,
                 updated,
@@ -2251,10 +3952,11 @@ impl From<&Transaction> for pb::Transaction {
                 schema,
                 config_upsert_values,
             } => {
+                let FieldsWithMeta { fields, metadata } = FieldsWithMeta::from(schema);
                 pb::transaction::BlobOperation::BlobOverwrite(pb::transaction::Overwrite {
                     fragments: fragments.iter().map(pb::DataFragment::from).collect(),
-                    schema: Fields::from(schema).0,
-                    schema_metadata: Default::default(), // TODO: handle metadata
+                    schema: fields.0,
+                    schema_metadata: metadata,
                     config_upsert_values: config_upsert_values
                         .clone()
                         .unwrap_or(Default::default()),
@@ -2268,7 +3970,7 @@ impl From<&Transaction> for pb::Transaction {
             uuid: value.uuid.clone(),
             operation: Some(operation),
             blob_operation,
-            tag: value.tag.clone().unwrap_or("".to_string()),
+            tag: tag_from_option(value.tag.as_deref()),
         }
     }
 }
@@ -2299,6 +4001,81 @@ impl From<&RewriteGroup> for pb::transaction::rewrite::RewriteGroup {
     }
 }
 
+/// Check that every field id in an [`Operation::UpdateConfig`]'s
+/// `field_metadata` exists in the schema.
+fn validate_field_metadata_ids(
+    schema: &Schema,
+    field_metadata: &HashMap<u32, HashMap<String, String>>,
+) -> Result<()> {
+    let missing = field_metadata
+        .keys()
+        .filter(|id| {
+            i32::try_from(**id)
+                .ok()
+                .and_then(|id| schema.field_by_id(id))
+                .is_none()
+        })
+        .min();
+    if let Some(missing) = missing {
+        return Err(Error::invalid_input(
+            format!(
+                "Cannot update metadata of field {}, it does not exist",
+                missing
+            ),
+            location!(),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that an [`Operation::Merge`] schema keeps every field of the current
+/// schema.
+///
+/// Merge replaces the whole fragment list, so a missing field would silently
+/// drop a column; columns are dropped with [`Operation::Project`] instead.  A
+/// field counts as kept if its id or its path is still in the schema, or if a
+/// new field took its place under the same parent. Altering a column's type
+/// rewrites it under a new id, and possibly a new name.
+fn validate_merge_keeps_fields(current: &Schema, merged: &Schema) -> Result<()> {
+    fn siblings(schema: &Schema, parent_id: i32) -> Option<&[Field]> {
+        if parent_id < 0 {
+            Some(&schema.fields)
+        } else {
+            schema
+                .field_by_id(parent_id)
+                .map(|parent| parent.children.as_slice())
+        }
+    }
+
+    // Fields replaced by a new field, whose children are replaced with them
+    let mut replaced = HashSet::new();
+    for field in current.fields_pre_order() {
+        let path = current.field_path_by_id(field.id).unwrap();
+        if merged.field_by_id(field.id).is_some() || merged.field(&path).is_some() {
+            continue;
+        }
+        let position = siblings(current, field.parent_id)
+            .and_then(|fields| fields.iter().position(|f| f.id == field.id));
+        let replacement = position.and_then(|position| {
+            siblings(merged, field.parent_id).and_then(|fields| fields.get(position))
+        });
+        let is_replaced = replaced.contains(&field.parent_id)
+            || replacement.is_some_and(|f| current.field_by_id(f.id).is_none());
+        if is_replaced {
+            replaced.insert(field.id);
+        } else {
+            return Err(Error::invalid_input(
+                format!(
+                    "Merge cannot remove column \"{}\", use Project to drop columns",
+                    path
+                ),
+                location!(),
+            ));
+        }
+    }
+    Ok(())
+}
+
 /// Validate the operation is valid for the given manifest.
 pub fn validate_operation(manifest: Option<&Manifest>, operation: &Operation) -> Result<()> {
     let manifest = match (manifest, operation) {
@@ -2311,7 +4088,7 @@ pub fn validate_operation(manifest: Option<&Manifest>, operation: &Operation) ->
             },
         ) => {
             // Validate here because we are going to return early.
-            schema_fragments_valid(schema, fragments)?;
+            validate_schema_covers_fragments(schema, fragments)?;
 
             return Ok(());
         }
@@ -2329,26 +4106,84 @@ pub fn validate_operation(manifest: Option<&Manifest>, operation: &Operation) ->
 
     match operation {
         Operation::Append { fragments } => {
+            // The same fragment passed twice would add its data twice
+            Transaction::validate_unique_fragment_ids(operation)?;
+            let mut seen_paths = HashSet::new();
+            if let Some(path) = fragments
+                .iter()
+                .flat_map(|f| f.files.iter().map(|file| file.path.as_str()))
+                .find(|path| !seen_paths.insert(*path))
+            {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Data file {} appears more than once in Append operation",
+                        path
+                    ),
+                    location!(),
+                ));
+            }
             // Fragments must contain all fields in the schema
-            schema_fragments_valid(&manifest.schema, fragments)
+            validate_schema_covers_fragments(&manifest.schema, fragments)
         }
         Operation::Project { schema } => {
-            schema_fragments_valid(schema, manifest.fragments.as_ref())
+            // Project only changes the schema, so it cannot introduce new fields
+            if let Some(field) = schema
+                .fields_pre_order()
+                .find(|f| manifest.schema.field_by_id(f.id).is_none())
+            {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Cannot project to field {} (id={}), it does not exist in the current schema",
+                        schema.field_path_by_id(field.id).unwrap(),
+                        field.id
+                    ),
+                    location!(),
+                ));
+            }
+            validate_schema_covers_fragments(schema, manifest.fragments.as_ref())
+        }
+        Operation::Merge { fragments, schema } => {
+            validate_merge_keeps_fields(&manifest.schema, schema)?;
+            validate_schema_covers_fragments(schema, fragments)
         }
-        Operation::Merge { fragments, schema }
-        | Operation::Overwrite {
+        Operation::Overwrite {
             fragments,
             schema,
             config_upsert_values: None,
-        } => schema_fragments_valid(schema, fragments),
+        } => validate_schema_covers_fragments(schema, fragments),
         Operation::Update {
             updated_fragments,
             new_fragments,
             ..
         } => {
-            schema_fragments_valid(&manifest.schema, updated_fragments)?;
-            schema_fragments_valid(&manifest.schema, new_fragments)
+            validate_schema_covers_fragments(&manifest.schema, updated_fragments)?;
+            validate_schema_covers_fragments(&manifest.schema, new_fragments)
         }
+        Operation::RelaxNullability { field_ids } => {
+            if let Some(missing) = field_ids
+                .iter()
+                .find(|id| manifest.schema.field_by_id(**id as i32).is_none())
+            {
+                return Err(Error::invalid_input(
+                    format!("Cannot make field {} nullable, it does not exist", missing),
+                    location!(),
+                ));
+            }
+            Ok(())
+        }
+        Operation::UpdateConfig {
+            field_metadata: Some(field_metadata),
+            ..
+        } => validate_field_metadata_ids(&manifest.schema, field_metadata),
+        // Whether an older version still exists can only be checked with IO,
+        // when the restore is committed.
+        Operation::Restore { version } if *version > manifest.version => Err(Error::invalid_input(
+            format!(
+                "Cannot restore version {}, the current version is {}",
+                version, manifest.version
+            ),
+            location!(),
+        )),
         _ => Ok(()),
     }
 }
@@ -2356,7 +4191,29 @@ pub fn validate_operation(manifest: Option<&Manifest>, operation: &Operation) ->
 /// Check that each fragment contains all fields in the schema.
 /// It is not required that the schema contains all fields in the fragment.
 /// There may be masked fields.
-fn schema_fragments_valid(schema: &Schema, fragments: &[Fragment]) -> Result<()> {
+///
+/// This is the check performed when committing an `Append`, `Overwrite`,
+/// `Merge` or `Update`, so it can be used to validate fragments before
+/// building one of those operations. Missing fields are reported by their
+/// dotted path.
+///
+/// ```rust
+/// # use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+/// # use lance_core::datatypes::Schema;
+/// # use lance_table::format::Fragment;
+/// use lance::dataset::transaction::validate_schema_covers_fragments;
+///
+/// let arrow_schema = ArrowSchema::new(vec![Field::new("a", DataType::Int32, false)]);
+/// let schema = Schema::try_from(&arrow_schema).unwrap();
+///
+/// let fragment = Fragment::with_file_legacy(0, "path.lance", &schema, Some(10));
+/// assert!(validate_schema_covers_fragments(&schema, &[fragment]).is_ok());
+///
+/// let empty = Fragment::new(1);
+/// let err = validate_schema_covers_fragments(&schema, &[empty]).unwrap_err();
+/// assert!(err.to_string().contains("Fragment 1 missing field a"));
+/// ```
+pub fn validate_schema_covers_fragments(schema: &Schema, fragments: &[Fragment]) -> Result<()> {
     // TODO: add additional validation. Consider consolidating with various
     // validate() methods in the codebase.
     for fragment in fragments {
@@ -2367,10 +4224,13 @@ fn schema_fragments_valid(schema: &Schema, fragments: &[Fragment]) -> Result<()>
                 .flat_map(|f| f.fields.iter())
                 .any(|f_id| f_id == &field.id)
             {
+                let field_path = schema
+                    .field_path_by_id(field.id)
+                    .unwrap_or_else(|| field.name.clone());
                 return Err(Error::invalid_input(
                     format!(
-                        "Fragment {} does not contain field {:?}",
-                        fragment.id, field
+                        "Fragment {} missing field {} (id={})",
+                        fragment.id, field_path, field.id
                     ),
                     location!(),
                 ));
@@ -2384,6 +4244,392 @@ fn schema_fragments_valid(schema: &Schema, fragments: &[Fragment]) -> Result<()>
 mod tests {
     use super::*;
 
+    /// A schema with nested fields and schema metadata.
+    fn sample_schema() -> Schema {
+        use arrow_schema::{
+            DataType, Field as ArrowField, Fields as ArrowFields, Schema as ArrowSchema,
+        };
+
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+        ]);
+        Schema::try_from(&arrow_schema)
+            .unwrap()
+            .with_metadata_entry("key", "value")
+    }
+
+    fn sample_fragment(id: u64) -> Fragment {
+        Fragment::with_file_legacy(id, &format!("path{}", id), &sample_schema(), Some(10))
+    }
+
+    fn sample_index(name: &str) -> Index {
+        Index {
+            uuid: Uuid::new_v4(),
+            name: name.to_string(),
+            fields: vec![0],
+            dataset_version: 1,
+            fragment_bitmap: Some(RoaringBitmap::from_iter([0, 1])),
+            index_details: None,
+            index_version: 1,
+            created_at: None,
+        }
+    }
+
+    fn sample_mem_wal(generation: u64) -> MemWal {
+        MemWal::new_empty(
+            lance_index::mem_wal::MemWalId::new("GLOBAL", generation),
+            "mem_table_location",
+            "wal_location",
+            "owner",
+        )
+    }
+
+    /// One operation of every kind, each with representative non-empty data.
+    fn sample_operations() -> Vec<Operation> {
+        vec![
+            Operation::Append {
+                fragments: vec![sample_fragment(0), sample_fragment(0)],
+            },
+            Operation::Delete {
+                updated_fragments: vec![sample_fragment(1)],
+                deleted_fragment_ids: vec![2, 3],
+                predicate: "a > 2".to_string(),
+            },
+            Operation::Overwrite {
+                fragments: vec![sample_fragment(0)],
+                schema: sample_schema(),
+                config_upsert_values: Some(HashMap::from([("k".to_string(), "v".to_string())])),
+            },
+            Operation::CreateIndex {
+                new_indices: vec![sample_index("new")],
+                removed_indices: vec![sample_index("old")],
+            },
+            Operation::Rewrite {
+                groups: vec![RewriteGroup {
+                    old_fragments: vec![sample_fragment(1), sample_fragment(2)],
+                    new_fragments: vec![sample_fragment(5)],
+                }],
+                rewritten_indices: vec![RewrittenIndex {
+                    old_id: Uuid::new_v4(),
+                    new_id: Uuid::new_v4(),
+                }],
+                frag_reuse_index: Some(sample_index("__lance_frag_reuse")),
+            },
+            Operation::Merge {
+                fragments: vec![sample_fragment(1), sample_fragment(2)],
+                schema: sample_schema(),
+            },
+            Operation::Restore { version: 3 },
+            Operation::ReserveFragments { num_fragments: 4 },
+            Operation::Update {
+                removed_fragment_ids: vec![1],
+                updated_fragments: vec![sample_fragment(2)],
+                new_fragments: vec![sample_fragment(0)],
+                fields_modified: vec![0, 2],
+                mem_wal_to_flush: Some(sample_mem_wal(0)),
+            },
+            Operation::Project {
+                schema: sample_schema(),
+            },
+            Operation::RelaxNullability {
+                field_ids: vec![0, 3],
+            },
+            Operation::UpdateConfig {
+                upsert_values: Some(HashMap::from([("a".to_string(), "1".to_string())])),
+                delete_keys: Some(vec!["b".to_string()]),
+                delete_key_prefixes: Some(vec!["c.".to_string()]),
+                schema_metadata: Some(HashMap::from([("d".to_string(), "2".to_string())])),
+                schema_metadata_delete_keys: Some(vec!["f".to_string()]),
+                field_metadata: Some(HashMap::from([(
+                    1,
+                    HashMap::from([("e".to_string(), "3".to_string())]),
+                )])),
+            },
+            Operation::DataReplacement {
+                replacements: vec![DataReplacementGroup::new(
+                    1,
+                    DataFile::new_legacy_from_fields("path_replaced", vec![0]),
+                )
+                .with_num_rows(10)],
+                new_index: Some(sample_index("replaced")),
+            },
+            Operation::UpdateMemWalState {
+                added: vec![sample_mem_wal(1)],
+                updated: vec![sample_mem_wal(0)],
+                removed: vec![sample_mem_wal(0)],
+            },
+        ]
+    }
+
+    /// Every operation survives a round trip through protobuf.
+    ///
+    /// Gaps closed to make this pass:
+    /// * `Overwrite` dropped `config_upsert_values` (the empty check was inverted)
+    /// * `Overwrite`, `Merge` and `Project` dropped the schema metadata
+    /// * `Rewrite` dropped the `frag_reuse_index`
+    ///
+    /// Known remaining gap: empty `Some` values in `UpdateConfig` and
+    /// `Overwrite::config_upsert_values` come back as `None`, since protobuf
+    /// can't tell an empty map or list from a missing one.
+    #[test]
+    fn test_operation_pb_round_trip() {
+        let operations = sample_operations();
+        let codes = operations
+            .iter()
+            .map(|op| op.op_code())
+            .collect::<HashSet<_>>();
+        // Make sure every kind of operation is covered
+        assert_eq!(codes.len(), 14);
+
+        for operation in operations {
+            let transaction = Transaction::new(2, operation, None, Some("tag".to_string()));
+            let round_tripped = Transaction::try_from(pb::Transaction::from(&transaction)).unwrap();
+            assert_eq!(round_tripped.operation, transaction.operation);
+            assert_eq!(round_tripped.read_version, transaction.read_version);
+            assert_eq!(round_tripped.uuid, transaction.uuid);
+            assert_eq!(round_tripped.tag, transaction.tag);
+
+            // Schema equality ignores metadata, so check it separately
+            match (&round_tripped.operation, &transaction.operation) {
+                (
+                    Operation::Overwrite { schema: a, .. },
+                    Operation::Overwrite { schema: b, .. },
+                )
+                | (Operation::Merge { schema: a, .. }, Operation::Merge { schema: b, .. })
+                | (Operation::Project { schema: a }, Operation::Project { schema: b }) => {
+                    assert_eq!(a.metadata, b.metadata)
+                }
+                _ => {}
+            }
+        }
+
+        // Blob operations round trip too
+        for blobs_op in sample_operations()
+            .into_iter()
+            .filter(|op| matches!(op, Operation::Append { .. } | Operation::Overwrite { .. }))
+        {
+            let transaction =
+                Transaction::new(2, Operation::Restore { version: 1 }, Some(blobs_op), None);
+            let round_tripped = Transaction::try_from(pb::Transaction::from(&transaction)).unwrap();
+            assert_eq!(round_tripped.blobs_op, transaction.blobs_op);
+        }
+    }
+
+    #[test]
+    fn test_operation_metrics() {
+        let metrics =
+            |fragments: [usize; 3], indices: [usize; 2], config_keys_changed| OperationMetrics {
+                fragments_added: fragments[0],
+                fragments_removed: fragments[1],
+                fragments_updated: fragments[2],
+                indices_added: indices[0],
+                indices_removed: indices[1],
+                config_keys_changed,
+            };
+        for operation in sample_operations() {
+            let expected = match operation {
+                Operation::Append { .. } => metrics([2, 0, 0], [0, 0], 0),
+                Operation::Delete { .. } => metrics([0, 2, 1], [0, 0], 0),
+                Operation::Overwrite { .. } => metrics([1, 0, 0], [0, 0], 1),
+                Operation::CreateIndex { .. } => metrics([0, 0, 0], [1, 1], 0),
+                Operation::Rewrite { .. } => metrics([1, 2, 0], [1, 0], 0),
+                Operation::DataReplacement { .. } => metrics([0, 0, 1], [1, 0], 0),
+                Operation::Merge { .. } => metrics([0, 0, 2], [0, 0], 0),
+                Operation::Update { .. } => metrics([1, 1, 1], [0, 0], 0),
+                Operation::UpdateConfig { .. } => metrics([0, 0, 0], [0, 0], 3),
+                Operation::Restore { .. }
+                | Operation::ReserveFragments { .. }
+                | Operation::Project { .. }
+                | Operation::RelaxNullability { .. }
+                | Operation::UpdateMemWalState { .. } => OperationMetrics::default(),
+            };
+            assert_eq!(operation.metrics(), expected, "{}", operation.name());
+        }
+    }
+
+    #[test]
+    fn test_try_coalesce() {
+        let append = |ids: &[u64], blob_ids: &[u64]| {
+            let blobs_op = (!blob_ids.is_empty()).then(|| Operation::Append {
+                fragments: blob_ids.iter().map(|id| sample_fragment(*id)).collect(),
+            });
+            Transaction::new(
+                3,
+                Operation::Append {
+                    fragments: ids.iter().map(|id| sample_fragment(*id)).collect(),
+                },
+                blobs_op,
+                Some("tag".to_string()),
+            )
+        };
+
+        let txs = [append(&[0], &[]), append(&[0, 0], &[0]), append(&[], &[0])];
+        let coalesced = Transaction::try_coalesce(&txs).unwrap();
+        assert_eq!(coalesced.read_version, 3);
+        assert_eq!(
+            coalesced.operation,
+            Operation::Append {
+                fragments: vec![sample_fragment(0); 3],
+            }
+        );
+        assert_eq!(
+            coalesced.blobs_op,
+            Some(Operation::Append {
+                fragments: vec![sample_fragment(0); 2],
+            })
+        );
+        assert_eq!(coalesced.tag.as_deref(), Some("tag"));
+        assert!(txs.iter().all(|tx| tx.uuid != coalesced.uuid));
+
+        // Mixed operations, read versions, or no transactions are rejected
+        let delete = Transaction::new(
+            3,
+            Operation::Delete {
+                updated_fragments: vec![],
+                deleted_fragment_ids: vec![1],
+                predicate: "a > 2".to_string(),
+            },
+            None,
+            None,
+        );
+        let other_version = Transaction {
+            read_version: 4,
+            ..append(&[0], &[])
+        };
+        for txs in [
+            vec![append(&[0], &[]), delete],
+            vec![append(&[0], &[]), other_version],
+            vec![],
+        ] {
+            let err = Transaction::try_coalesce(&txs).unwrap_err();
+            assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_eq_ignoring_ids() {
+        let mut renumbered = sample_fragment(1);
+        renumbered.id = 7;
+        let append = Operation::Append {
+            fragments: vec![sample_fragment(1)],
+        };
+        let renumbered_append = Operation::Append {
+            fragments: vec![renumbered.clone()],
+        };
+        assert_ne!(append, renumbered_append);
+        assert!(append.eq_ignoring_ids(&renumbered_append));
+
+        // The content of the fragments is still compared
+        let other_append = Operation::Append {
+            fragments: vec![sample_fragment(2)],
+        };
+        assert!(!append.eq_ignoring_ids(&other_append));
+
+        let rewrite = |new_fragment: Fragment| Operation::Rewrite {
+            groups: vec![RewriteGroup {
+                old_fragments: vec![sample_fragment(0)],
+                new_fragments: vec![new_fragment],
+            }],
+            rewritten_indices: vec![],
+            frag_reuse_index: None,
+        };
+        assert!(rewrite(sample_fragment(1)).eq_ignoring_ids(&rewrite(renumbered.clone())));
+
+        // The ids of existing fragments are still compared
+        let delete = |updated_fragment: Fragment| Operation::Delete {
+            updated_fragments: vec![updated_fragment],
+            deleted_fragment_ids: vec![],
+            predicate: "a > 0".to_string(),
+        };
+        assert!(!delete(sample_fragment(1)).eq_ignoring_ids(&delete(renumbered)));
+
+        for operation in sample_operations() {
+            assert!(operation.eq_ignoring_ids(&operation), "{}", operation);
+        }
+    }
+
+    #[test]
+    fn test_blobs_operation_new_version() {
+        assert_eq!(BlobsOperation::Unchanged.new_version(), None);
+        assert_eq!(BlobsOperation::Updated(3).new_version(), Some(3));
+    }
+
+    #[test]
+    fn test_transaction_with_uuid() {
+        let uuid = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let transaction = Transaction::new(3, Operation::Restore { version: 1 }, None, None)
+            .with_uuid(uuid)
+            .unwrap();
+        assert_eq!(transaction.uuid, uuid);
+
+        let message = pb::Transaction::from(&transaction);
+        assert_eq!(message.uuid, uuid);
+        let round_tripped = Transaction::try_from(message).unwrap();
+        assert_eq!(round_tripped.uuid, uuid);
+
+        for invalid in [
+            "",
+            "not-a-uuid",
+            "67e5504410b1426f9247bb680e5fe0c8",
+            "{67e55044-10b1-426f-9247-bb680e5fe0c8}",
+            "67e55044-10b1-426f-9247-bb680e5fe0cz",
+        ] {
+            let err = Transaction::new_from_version(3, Operation::Restore { version: 1 })
+                .with_uuid(invalid)
+                .unwrap_err();
+            assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_overwrite_config_upsert_values_round_trip() {
+        fn round_trip(config_upsert_values: Option<HashMap<String, String>>) -> [Operation; 2] {
+            let overwrite = Operation::Overwrite {
+                fragments: vec![sample_fragment(0)],
+                schema: sample_schema(),
+                config_upsert_values,
+            };
+            // Both as the main operation and as the blobs operation
+            let transaction = Transaction::new(1, overwrite.clone(), Some(overwrite), None);
+            let round_tripped = Transaction::try_from(pb::Transaction::from(&transaction)).unwrap();
+            [round_tripped.operation, round_tripped.blobs_op.unwrap()]
+        }
+
+        fn config_of(operation: &Operation) -> &Option<HashMap<String, String>> {
+            match operation {
+                Operation::Overwrite {
+                    config_upsert_values,
+                    ..
+                } => config_upsert_values,
+                _ => panic!("expected an overwrite, got {}", operation),
+            }
+        }
+
+        let config = HashMap::from([
+            ("lance.a".to_string(), "1".to_string()),
+            ("lance.b".to_string(), "2".to_string()),
+        ]);
+        for operation in round_trip(Some(config.clone())) {
+            assert_eq!(config_of(&operation), &Some(config.clone()));
+        }
+
+        for operation in round_trip(None) {
+            assert_eq!(config_of(&operation), &None);
+        }
+        // An empty map can't be told apart from a missing one
+        for operation in round_trip(Some(HashMap::new())) {
+            assert_eq!(config_of(&operation), &None);
+        }
+    }
+
     #[test]
     fn test_rewrite_fragments() {
         let existing_fragments: Vec<Fragment> = (0..10).map(Fragment::new).collect();
@@ -2433,4 +4679,1671 @@ mod tests {
 
         assert_eq!(final_fragments, expected_fragments);
     }
+
+    #[test]
+    fn test_rewrite_fragments_missing_non_contiguous() {
+        let mut final_fragments: Vec<Fragment> = (0..10).map(Fragment::new).collect();
+        // 5 exists, so the missing 42 is only found on the non-contiguous path
+        let rewrite_groups = vec![RewriteGroup {
+            old_fragments: vec![Fragment::new(5), Fragment::new(42)],
+            new_fragments: vec![Fragment::new(0)],
+        }];
+
+        let mut fragment_id = 20;
+        let err = Transaction::handle_rewrite_fragments(
+            &mut final_fragments,
+            &rewrite_groups,
+            &mut fragment_id,
+            3,
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&err, Error::CommitConflict { version: 3, .. }),
+            "{:?}",
+            err
+        );
+        assert!(err.to_string().contains("id=42"), "{}", err);
+
+        // A missing fragment past the end of the dataset is also an error
+        let mut final_fragments: Vec<Fragment> = (0..10).map(Fragment::new).collect();
+        let rewrite_groups = vec![RewriteGroup {
+            old_fragments: vec![Fragment::new(9), Fragment::new(10)],
+            new_fragments: vec![Fragment::new(0)],
+        }];
+        let err = Transaction::handle_rewrite_fragments(
+            &mut final_fragments,
+            &rewrite_groups,
+            &mut fragment_id,
+            3,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("id=10"), "{}", err);
+    }
+
+    #[test]
+    fn test_apply_append_to_fragments() {
+        let existing_fragments: Vec<Fragment> = (0..3).map(Fragment::new).collect();
+
+        let operation = Operation::Append {
+            // New fragments are not assigned ids yet
+            fragments: vec![Fragment::new(0), Fragment::new(0)],
+        };
+        let final_fragments = operation
+            .apply_to_fragments(&existing_fragments, 3)
+            .unwrap();
+
+        let expected_fragments: Vec<Fragment> = (0..5).map(Fragment::new).collect();
+        assert_eq!(final_fragments, expected_fragments);
+    }
+
+    #[test]
+    fn test_apply_delete_to_fragments() {
+        let existing_fragments: Vec<Fragment> = (0..5).map(Fragment::new).collect();
+
+        let mut updated_fragment = Fragment::new(3);
+        updated_fragment.physical_rows = Some(10);
+        let operation = Operation::Delete {
+            updated_fragments: vec![updated_fragment.clone()],
+            deleted_fragment_ids: vec![1, 4],
+            predicate: "x > 2".to_string(),
+        };
+        let final_fragments = operation
+            .apply_to_fragments(&existing_fragments, 5)
+            .unwrap();
+
+        let expected_fragments: Vec<Fragment> =
+            vec![Fragment::new(0), Fragment::new(2), updated_fragment];
+        assert_eq!(final_fragments, expected_fragments);
+    }
+
+    #[test]
+    fn test_apply_rewrite_to_fragments() {
+        let existing_fragments: Vec<Fragment> = (0..5).map(Fragment::new).collect();
+
+        let operation = Operation::compact(vec![RewriteGroup {
+            old_fragments: vec![Fragment::new(1), Fragment::new(2)],
+            new_fragments: vec![Fragment::new(0)],
+        }]);
+        let final_fragments = operation
+            .apply_to_fragments(&existing_fragments, 5)
+            .unwrap();
+        let expected_fragments: Vec<Fragment> = vec![
+            Fragment::new(0),
+            Fragment::new(5),
+            Fragment::new(3),
+            Fragment::new(4),
+        ];
+        assert_eq!(final_fragments, expected_fragments);
+    }
+
+    #[test]
+    fn test_apply_restore_to_fragments() {
+        // Restoring depends on the dataset history
+        let existing_fragments: Vec<Fragment> = (0..5).map(Fragment::new).collect();
+        assert!(matches!(
+            Operation::Restore { version: 1 }.apply_to_fragments(&existing_fragments, 5),
+            Err(Error::NotSupported { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_transaction() {
+        let append = Operation::Append {
+            fragments: vec![Fragment::new(0), Fragment::new(0)],
+        };
+        Transaction::new(0, append.clone(), Some(append.clone()), None)
+            .validate()
+            .unwrap();
+
+        // Blobs operation must be an append or overwrite
+        let delete = Operation::Delete {
+            updated_fragments: vec![Fragment::new(0)],
+            deleted_fragment_ids: vec![1],
+            predicate: "x > 2".to_string(),
+        };
+        let err = Transaction::new(0, append, Some(delete.clone()), None)
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("Blobs operation"), "{}", err);
+
+        // Rewrite without groups, or with a group that replaces nothing
+        let err = Transaction::compaction(0, vec![]).validate().unwrap_err();
+        assert!(err.to_string().contains("at least one group"), "{}", err);
+        let err = Transaction::compaction(
+            0,
+            vec![RewriteGroup {
+                old_fragments: vec![],
+                new_fragments: vec![Fragment::new(0)],
+            }],
+        )
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("at least one fragment"), "{}", err);
+
+        // DataReplacement without replacements
+        let err = Transaction::new_from_version(
+            0,
+            Operation::DataReplacement {
+                replacements: vec![],
+                new_index: None,
+            },
+        )
+        .validate()
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("at least one replacement"),
+            "{}",
+            err
+        );
+
+        // Duplicate fragment ids
+        Transaction::new_from_version(0, delete).validate().unwrap();
+        let err = Transaction::new_from_version(
+            0,
+            Operation::Delete {
+                updated_fragments: vec![Fragment::new(1)],
+                deleted_fragment_ids: vec![1],
+                predicate: "x > 2".to_string(),
+            },
+        )
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("Fragment id 1"), "{}", err);
+        let err = Transaction::compaction(
+            0,
+            vec![RewriteGroup {
+                old_fragments: vec![Fragment::new(1)],
+                new_fragments: vec![Fragment::new(5), Fragment::new(5)],
+            }],
+        )
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("Fragment id 5"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_tag() {
+        let transaction = |tag: &str| {
+            Transaction::new(
+                0,
+                Operation::ReserveFragments { num_fragments: 1 },
+                None,
+                Some(tag.to_string()),
+            )
+        };
+        let assert_invalid = |tag: &str| {
+            let err = transaction(tag).validate().unwrap_err();
+            assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        };
+
+        transaction("nightly-compaction").validate().unwrap();
+        transaction(&"a".repeat(MAX_TAG_LENGTH)).validate().unwrap();
+        Transaction::new(
+            0,
+            Operation::ReserveFragments { num_fragments: 1 },
+            None,
+            None,
+        )
+        .validate()
+        .unwrap();
+
+        assert_invalid("");
+        assert_invalid(&"a".repeat(MAX_TAG_LENGTH + 1));
+        assert_invalid("tag\n");
+        assert_invalid("tag\u{7}");
+
+        // No tag and an empty tag are the same once serialized
+        assert_eq!(tag_to_option(&tag_from_option(None)), None);
+        assert_eq!(
+            tag_to_option(&tag_from_option(Some("tag"))),
+            Some("tag".to_string())
+        );
+        let round_tripped = Transaction::try_from(pb::Transaction::from(&transaction(""))).unwrap();
+        assert_eq!(round_tripped.tag, None);
+    }
+
+    #[test]
+    fn test_rewrite_fragment_remap() {
+        let groups = vec![
+            // New fragments with reserved ids
+            RewriteGroup {
+                old_fragments: vec![Fragment::new(1), Fragment::new(2)],
+                new_fragments: vec![Fragment::new(15)],
+            },
+            // New fragments that still need ids
+            RewriteGroup {
+                old_fragments: vec![Fragment::new(5)],
+                new_fragments: vec![Fragment::new(0), Fragment::new(0)],
+            },
+        ];
+        let operation = Operation::compact(groups.clone());
+        assert_eq!(
+            operation.rewrite_fragment_remap(),
+            Some(vec![(1, 15), (2, 15), (5, 0), (5, 0)])
+        );
+        assert_eq!(
+            Operation::ReserveFragments { num_fragments: 1 }.rewrite_fragment_remap(),
+            None
+        );
+
+        // Once ids are assigned, the mapping reflects them
+        let mut fragment_id = 20;
+        let assigned = Transaction::assign_rewrite_fragment_ids(&groups, &mut fragment_id).unwrap();
+        assert_eq!(fragment_id, 22);
+        assert_eq!(
+            Operation::compact(assigned).rewrite_fragment_remap(),
+            Some(vec![(1, 15), (2, 15), (5, 20), (5, 21)])
+        );
+    }
+
+    #[test]
+    fn test_fragments_with_ids() {
+        // Id 0 means unassigned, so every fragment with id 0 gets the next id,
+        // in increasing order. Starting from 0 (as an overwrite does), the
+        // first one is assigned 0. Reserved ids are left untouched.
+        let mut fragment_id = 0;
+        let fragments = Transaction::fragments_with_ids(
+            vec![
+                Fragment::new(0),
+                Fragment::new(0),
+                Fragment::new(10),
+                Fragment::new(0),
+            ],
+            &mut fragment_id,
+        )
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+        assert_eq!(
+            fragments.iter().map(|f| f.id).collect::<Vec<_>>(),
+            vec![0, 1, 10, 2]
+        );
+        assert_eq!(fragment_id, 3);
+
+        // An explicit id 0 can't be kept, it is assigned like any new fragment
+        let mut fragment_id = 5;
+        let fragments = Transaction::fragments_with_ids(vec![Fragment::new(0)], &mut fragment_id)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(fragments[0].id, 5);
+        assert_eq!(fragment_id, 6);
+
+        // Running out of ids is an error rather than wrapping around.
+        let mut fragment_id = u64::MAX;
+        let result = Transaction::fragments_with_ids(vec![Fragment::new(0)], &mut fragment_id)
+            .collect::<Result<Vec<_>>>();
+        assert!(
+            matches!(result, Err(Error::Internal { .. })),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_try_merge_config() {
+        let first = Operation::UpdateConfig {
+            upsert_values: Some(HashMap::from([
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "1".to_string()),
+            ])),
+            delete_keys: Some(vec!["c".to_string()]),
+            delete_key_prefixes: None,
+            schema_metadata: Some(HashMap::from([("s".to_string(), "1".to_string())])),
+            schema_metadata_delete_keys: None,
+            field_metadata: Some(HashMap::from([(
+                0,
+                HashMap::from([("f".to_string(), "1".to_string())]),
+            )])),
+        };
+        let second = Operation::UpdateConfig {
+            upsert_values: Some(HashMap::from([("b".to_string(), "2".to_string())])),
+            delete_keys: Some(vec!["d".to_string()]),
+            delete_key_prefixes: None,
+            schema_metadata: None,
+            schema_metadata_delete_keys: None,
+            field_metadata: Some(HashMap::from([(
+                1,
+                HashMap::from([("f".to_string(), "2".to_string())]),
+            )])),
+        };
+
+        let merged = first.clone().try_merge_config(second).unwrap();
+        assert_eq!(
+            merged,
+            Operation::UpdateConfig {
+                upsert_values: Some(HashMap::from([
+                    ("a".to_string(), "1".to_string()),
+                    ("b".to_string(), "2".to_string()),
+                ])),
+                delete_keys: Some(vec!["c".to_string(), "d".to_string()]),
+                delete_key_prefixes: None,
+                schema_metadata: Some(HashMap::from([("s".to_string(), "1".to_string())])),
+                schema_metadata_delete_keys: None,
+                field_metadata: Some(HashMap::from([
+                    (0, HashMap::from([("f".to_string(), "1".to_string())])),
+                    (1, HashMap::from([("f".to_string(), "2".to_string())])),
+                ])),
+            }
+        );
+
+        // Deleting a key the other operation upserts is a contradiction
+        let contradicting = Operation::UpdateConfig {
+            upsert_values: None,
+            delete_keys: Some(vec!["a".to_string()]),
+            delete_key_prefixes: None,
+            schema_metadata: None,
+            schema_metadata_delete_keys: None,
+            field_metadata: None,
+        };
+        let err = first.clone().try_merge_config(contradicting).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        assert!(err.to_string().contains("key a"), "{}", err);
+
+        let err = first
+            .try_merge_config(Operation::ReserveFragments { num_fragments: 1 })
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_schema_metadata_delete_keys() {
+        let delete = |keys: &[&str], schema_metadata: Option<HashMap<String, String>>| {
+            Operation::UpdateConfig {
+                upsert_values: None,
+                delete_keys: None,
+                delete_key_prefixes: None,
+                schema_metadata,
+                schema_metadata_delete_keys: Some(keys.iter().map(|k| k.to_string()).collect()),
+                field_metadata: None,
+            }
+        };
+        let metadata = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+
+        let schema = sample_schema().with_metadata(metadata(&[("a", "1"), ("b", "2")]));
+        let manifest = Manifest::new(
+            schema,
+            Arc::new(vec![sample_fragment(1)]),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+        let build = |operation: Operation| {
+            Transaction::new(1, operation, None, None)
+                .build_manifest(
+                    Some(&manifest),
+                    vec![],
+                    "txn",
+                    &ManifestWriteConfig::default(),
+                    None,
+                )
+                .unwrap()
+                .0
+        };
+
+        let new_manifest = build(delete(&["a", "missing"], None));
+        assert_eq!(new_manifest.schema.metadata, metadata(&[("b", "2")]));
+
+        // Deletes are applied before the replacement
+        let new_manifest = build(delete(&["c"], Some(metadata(&[("c", "3")]))));
+        assert_eq!(new_manifest.schema.metadata, metadata(&[("c", "3")]));
+
+        // Protobuf round trip
+        let transaction = Transaction::new(1, delete(&["a", "b"], None), None, None);
+        let round_tripped = Transaction::try_from(pb::Transaction::from(&transaction)).unwrap();
+        assert_eq!(round_tripped.operation, transaction.operation);
+
+        // Deleting a key that another operation sets is a conflict
+        let set_a = Operation::UpdateConfig {
+            upsert_values: None,
+            delete_keys: None,
+            delete_key_prefixes: None,
+            schema_metadata: Some(metadata(&[("a", "10")])),
+            schema_metadata_delete_keys: None,
+            field_metadata: None,
+        };
+        assert!(delete(&["a"], None).modifies_same_metadata(&set_a));
+        assert!(set_a.modifies_same_metadata(&delete(&["a"], None)));
+        assert!(!delete(&["b"], None).modifies_same_metadata(&set_a));
+        assert!(!delete(&["a"], None).modifies_same_metadata(&delete(&["a"], None)));
+
+        // Merging keeps the effect of committing one after the other
+        let merged = set_a
+            .clone()
+            .try_merge_config(delete(&["a"], None))
+            .unwrap();
+        assert_eq!(build(merged).schema.metadata, HashMap::new());
+        let merged = delete(&["a"], None).try_merge_config(set_a).unwrap();
+        assert_eq!(build(merged).schema.metadata, metadata(&[("a", "10")]));
+    }
+
+    #[test]
+    fn test_operation_op_code() {
+        let operations = [
+            Operation::Append { fragments: vec![] },
+            Operation::Delete {
+                updated_fragments: vec![],
+                deleted_fragment_ids: vec![],
+                predicate: "x > 2".to_string(),
+            },
+            Operation::Overwrite {
+                fragments: vec![],
+                schema: Schema::default(),
+                config_upsert_values: None,
+            },
+            Operation::CreateIndex {
+                new_indices: vec![],
+                removed_indices: vec![],
+            },
+            Operation::compact(vec![]),
+            Operation::Merge {
+                fragments: vec![],
+                schema: Schema::default(),
+            },
+            Operation::Restore { version: 1 },
+            Operation::ReserveFragments { num_fragments: 1 },
+            Operation::Update {
+                removed_fragment_ids: vec![],
+                updated_fragments: vec![],
+                new_fragments: vec![],
+                fields_modified: vec![],
+                mem_wal_to_flush: None,
+            },
+            Operation::Project {
+                schema: Schema::default(),
+            },
+            Operation::UpdateConfig {
+                upsert_values: None,
+                delete_keys: None,
+                delete_key_prefixes: None,
+                schema_metadata: None,
+                schema_metadata_delete_keys: None,
+                field_metadata: None,
+            },
+            Operation::DataReplacement {
+                replacements: vec![],
+                new_index: None,
+            },
+            Operation::UpdateMemWalState {
+                added: vec![],
+                updated: vec![],
+                removed: vec![],
+            },
+            Operation::RelaxNullability { field_ids: vec![] },
+        ];
+
+        let codes = operations
+            .iter()
+            .map(|op| op.op_code())
+            .collect::<HashSet<_>>();
+        assert_eq!(codes.len(), operations.len());
+
+        // Codes match the protobuf field numbers
+        assert_eq!(Operation::Append { fragments: vec![] }.op_code(), 100);
+        assert_eq!(Operation::Restore { version: 1 }.op_code(), 106);
+    }
+
+    #[test]
+    fn test_operation_hash() {
+        fn hash(operation: &Operation) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            operation.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let append = Operation::Append {
+            fragments: vec![Fragment::new(1), Fragment::new(2), Fragment::new(3)],
+        };
+        let reordered = Operation::Append {
+            fragments: vec![Fragment::new(3), Fragment::new(1), Fragment::new(2)],
+        };
+        assert_eq!(append, reordered);
+        assert_eq!(hash(&append), hash(&reordered));
+
+        let different = Operation::Append {
+            fragments: vec![Fragment::new(1), Fragment::new(2), Fragment::new(4)],
+        };
+        assert_ne!(append, different);
+        assert_ne!(hash(&append), hash(&different));
+
+        let config = Operation::UpdateConfig {
+            upsert_values: Some(HashMap::from([
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "2".to_string()),
+            ])),
+            delete_keys: Some(vec!["c".to_string(), "d".to_string()]),
+            delete_key_prefixes: None,
+            schema_metadata: None,
+            schema_metadata_delete_keys: None,
+            field_metadata: None,
+        };
+        let reordered_config = Operation::UpdateConfig {
+            upsert_values: Some(HashMap::from([
+                ("b".to_string(), "2".to_string()),
+                ("a".to_string(), "1".to_string()),
+            ])),
+            delete_keys: Some(vec!["d".to_string(), "c".to_string()]),
+            delete_key_prefixes: None,
+            schema_metadata: None,
+            schema_metadata_delete_keys: None,
+            field_metadata: None,
+        };
+        assert_eq!(config, reordered_config);
+        assert_eq!(hash(&config), hash(&reordered_config));
+    }
+
+    #[test]
+    fn test_is_metadata_only() {
+        let cases = [
+            (
+                Operation::Append {
+                    fragments: vec![Fragment::new(0)],
+                },
+                false,
+            ),
+            (
+                Operation::Delete {
+                    updated_fragments: vec![],
+                    deleted_fragment_ids: vec![0],
+                    predicate: "x > 2".to_string(),
+                },
+                false,
+            ),
+            (
+                Operation::Overwrite {
+                    fragments: vec![Fragment::new(0)],
+                    schema: Schema::default(),
+                    config_upsert_values: None,
+                },
+                false,
+            ),
+            (
+                Operation::CreateIndex {
+                    new_indices: vec![],
+                    removed_indices: vec![],
+                },
+                false,
+            ),
+            (
+                Operation::Rewrite {
+                    groups: vec![],
+                    rewritten_indices: vec![],
+                    frag_reuse_index: None,
+                },
+                false,
+            ),
+            (
+                Operation::DataReplacement {
+                    replacements: vec![],
+                    new_index: None,
+                },
+                false,
+            ),
+            (
+                Operation::Merge {
+                    fragments: vec![Fragment::new(0)],
+                    schema: Schema::default(),
+                },
+                false,
+            ),
+            (Operation::Restore { version: 1 }, true),
+            (Operation::ReserveFragments { num_fragments: 3 }, true),
+            (Operation::RelaxNullability { field_ids: vec![0] }, true),
+            (
+                Operation::Update {
+                    removed_fragment_ids: vec![],
+                    updated_fragments: vec![],
+                    new_fragments: vec![],
+                    fields_modified: vec![],
+                    mem_wal_to_flush: None,
+                },
+                false,
+            ),
+            (
+                Operation::Project {
+                    schema: Schema::default(),
+                },
+                true,
+            ),
+            (
+                Operation::UpdateConfig {
+                    upsert_values: None,
+                    delete_keys: None,
+                    delete_key_prefixes: None,
+                    schema_metadata: None,
+                    schema_metadata_delete_keys: None,
+                    field_metadata: None,
+                },
+                true,
+            ),
+            (
+                Operation::UpdateMemWalState {
+                    added: vec![],
+                    updated: vec![],
+                    removed: vec![],
+                },
+                true,
+            ),
+        ];
+        for (operation, expected) in cases {
+            assert_eq!(operation.is_metadata_only(), expected, "{}", operation);
+        }
+    }
+
+    #[test]
+    fn test_is_destructive_and_additive() {
+        for operation in sample_operations() {
+            // (destructive, additive)
+            let expected = match operation {
+                Operation::Overwrite { .. }
+                | Operation::Delete { .. }
+                | Operation::Update { .. }
+                | Operation::Project { .. }
+                | Operation::Rewrite { .. }
+                | Operation::DataReplacement { .. }
+                | Operation::Restore { .. } => (true, false),
+                Operation::Append { .. }
+                | Operation::CreateIndex { .. }
+                | Operation::Merge { .. } => (false, true),
+                Operation::ReserveFragments { .. }
+                | Operation::RelaxNullability { .. }
+                | Operation::UpdateConfig { .. }
+                | Operation::UpdateMemWalState { .. } => (false, false),
+            };
+            assert_eq!(
+                (operation.is_destructive(), operation.is_additive()),
+                expected,
+                "{}",
+                operation
+            );
+        }
+    }
+
+    #[test]
+    fn test_preview_update_drops_index() {
+        use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("b", DataType::Int32, false),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let fragment = Fragment::with_file_legacy(0, "path1", &schema, Some(10));
+        let manifest = Manifest::new(
+            schema.clone(),
+            Arc::new(vec![fragment.clone()]),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+        let make_index = |name: &str, field: i32| Index {
+            uuid: Uuid::new_v4(),
+            name: name.to_string(),
+            fields: vec![field],
+            dataset_version: 1,
+            fragment_bitmap: Some(RoaringBitmap::from_iter([0])),
+            index_details: None,
+            index_version: 0,
+            created_at: None,
+        };
+        let indices = vec![make_index("a_idx", 0), make_index("b_idx", 1)];
+
+        // Update modifies field "a" in the only fragment covered by "a_idx"
+        let transaction = Transaction::new(
+            1,
+            Operation::Update {
+                removed_fragment_ids: vec![],
+                updated_fragments: vec![fragment],
+                new_fragments: vec![],
+                fields_modified: vec![0],
+                mem_wal_to_flush: None,
+            },
+            None,
+            None,
+        );
+        let preview = transaction.preview(Some(&manifest), &indices).unwrap();
+        assert_eq!(preview.num_fragments, 1);
+        assert_eq!(preview.retained_indices, vec!["b_idx".to_string()]);
+        assert_eq!(preview.dropped_indices, vec!["a_idx".to_string()]);
+        assert_eq!(preview.schema, schema);
+    }
+
+    #[test]
+    fn test_delete_many_fragments() {
+        let fragments = (0..1000).map(sample_fragment).collect::<Vec<_>>();
+        let manifest = Manifest::new(
+            sample_schema(),
+            Arc::new(fragments.clone()),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+
+        let updated_fragments = [3, 500, 998]
+            .into_iter()
+            .map(|id| {
+                let mut fragment = fragments[id].clone();
+                fragment.physical_rows = Some(5);
+                fragment
+            })
+            .collect::<Vec<_>>();
+        let deleted_fragment_ids = vec![0, 42, 999];
+        let transaction = Transaction::new(
+            1,
+            Operation::Delete {
+                updated_fragments: updated_fragments.clone(),
+                deleted_fragment_ids: deleted_fragment_ids.clone(),
+                predicate: "a > 0".to_string(),
+            },
+            None,
+            None,
+        );
+        let (new_manifest, _) = transaction
+            .build_manifest(
+                Some(&manifest),
+                vec![],
+                "txn",
+                &ManifestWriteConfig::default(),
+                None,
+            )
+            .unwrap();
+
+        let expected = fragments
+            .iter()
+            .filter(|f| !deleted_fragment_ids.contains(&f.id))
+            .map(|f| {
+                updated_fragments
+                    .iter()
+                    .find(|u| u.id == f.id)
+                    .unwrap_or(f)
+                    .clone()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(new_manifest.fragments.len(), 997);
+        assert_eq!(new_manifest.fragments.as_ref(), &expected);
+    }
+
+    #[test]
+    fn test_data_replacement_row_count() {
+        let schema = sample_schema();
+        let fragment = sample_fragment(0);
+        let manifest = Manifest::new(
+            schema,
+            Arc::new(vec![fragment.clone()]),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+        let replace = |num_rows: Option<u64>| {
+            let new_file =
+                DataFile::new_legacy_from_fields("replaced", fragment.files[0].fields.clone());
+            let group = DataReplacementGroup::new(0, new_file);
+            let group = match num_rows {
+                Some(num_rows) => group.with_num_rows(num_rows),
+                None => group,
+            };
+            Transaction::new(
+                1,
+                Operation::DataReplacement {
+                    replacements: vec![group],
+                    new_index: None,
+                },
+                None,
+                None,
+            )
+            .build_manifest(
+                Some(&manifest),
+                vec![],
+                "txn",
+                &ManifestWriteConfig::default(),
+                None,
+            )
+        };
+
+        // Same number of rows as the fragment, or unknown
+        for num_rows in [Some(10), None] {
+            let (new_manifest, _) = replace(num_rows).unwrap();
+            assert_eq!(new_manifest.fragments[0].files[0].path, "replaced");
+        }
+
+        let err = replace(Some(7)).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        assert!(err.to_string().contains("has 7 rows"), "{}", err);
+    }
+
+    #[test]
+    fn test_data_replacement_with_index() {
+        let fragment = sample_fragment(0);
+        let manifest = Manifest::new(
+            sample_schema(),
+            Arc::new(vec![fragment.clone()]),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+        let old_index = sample_index("a_idx");
+        let other_index = sample_index("other_idx");
+        let new_index = sample_index("a_idx");
+
+        let new_file =
+            DataFile::new_legacy_from_fields("replaced", fragment.files[0].fields.clone());
+        let (new_manifest, final_indices) = Transaction::new(
+            1,
+            Operation::DataReplacement {
+                replacements: vec![DataReplacementGroup::new(0, new_file)],
+                new_index: Some(new_index.clone()),
+            },
+            None,
+            None,
+        )
+        .build_manifest(
+            Some(&manifest),
+            vec![old_index, other_index.clone()],
+            "txn",
+            &ManifestWriteConfig::default(),
+            None,
+        )
+        .unwrap();
+
+        // The data and the index are committed together, and the new index
+        // replaces the existing one with the same name.
+        assert_eq!(new_manifest.fragments[0].files[0].path, "replaced");
+        assert_eq!(final_indices, vec![other_index, new_index]);
+    }
+
+    #[test]
+    fn test_relax_nullability() {
+        let schema = sample_schema();
+        assert!(!schema.all_fields_nullable());
+        let manifest = Manifest::new(
+            schema,
+            Arc::new(vec![sample_fragment(0)]),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+
+        // "a" and the nested "b.f2" are the non-nullable fields
+        let operation = Operation::RelaxNullability {
+            field_ids: vec![0, 3],
+        };
+        validate_operation(Some(&manifest), &operation).unwrap();
+        let transaction = Transaction::new(1, operation, None, None);
+        let (new_manifest, _) = transaction
+            .build_manifest(
+                Some(&manifest),
+                vec![],
+                "txn",
+                &ManifestWriteConfig::default(),
+                None,
+            )
+            .unwrap();
+        assert!(new_manifest.schema.all_fields_nullable());
+        assert_eq!(new_manifest.fragments, manifest.fragments);
+
+        // The field must exist
+        let operation = Operation::RelaxNullability { field_ids: vec![5] };
+        let err = validate_operation(Some(&manifest), &operation).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_restore_version() {
+        let mut manifest = Manifest::new(
+            sample_schema(),
+            Arc::new(vec![sample_fragment(0)]),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+        manifest.version = 3;
+
+        for version in [1, 3] {
+            validate_operation(Some(&manifest), &Operation::Restore { version }).unwrap();
+        }
+        let err =
+            validate_operation(Some(&manifest), &Operation::Restore { version: 4 }).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        assert!(
+            err.to_string()
+                .contains("Cannot restore version 4, the current version is 3"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_append_duplicate_fragments() {
+        let schema = sample_schema();
+        let manifest = Manifest::new(
+            schema,
+            Arc::new(vec![sample_fragment(1)]),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+        let append = |fragments: Vec<Fragment>| {
+            validate_operation(Some(&manifest), &Operation::Append { fragments })
+        };
+
+        append(vec![sample_fragment(0), sample_fragment(2)]).unwrap();
+
+        // The same new fragment twice
+        let err = append(vec![sample_fragment(0), sample_fragment(0)]).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        assert!(err.to_string().contains("Data file path0"), "{}", err);
+
+        // Two fragments with the same reserved id
+        let mut other = sample_fragment(2);
+        other.files[0].path = "other".to_string();
+        let err = append(vec![sample_fragment(2), other]).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        assert!(err.to_string().contains("Fragment id 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_project_must_not_add_fields() {
+        use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+
+        let schema = sample_schema();
+        let fragment = Fragment::with_file_legacy(0, "path1", &schema, Some(10));
+        let manifest = Manifest::new(
+            schema.clone(),
+            Arc::new(vec![fragment]),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+
+        // Dropping a column is fine
+        let dropped = schema.project_by_ids(&[0], true);
+        validate_operation(Some(&manifest), &Operation::Project { schema: dropped }).unwrap();
+
+        // Adding a column is not
+        let new_column = Schema::try_from(&ArrowSchema::new(vec![ArrowField::new(
+            "new",
+            DataType::Utf8,
+            true,
+        )]))
+        .unwrap();
+        let mut added = schema.merge(new_column).unwrap();
+        added.set_field_id(Some(manifest.max_field_id()));
+        let new_id = added.field("new").unwrap().id;
+        let operation = Operation::Project { schema: added };
+        let err = validate_operation(Some(&manifest), &operation).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        let expected = format!("field new (id={})", new_id);
+        assert!(err.to_string().contains(&expected), "{}", err);
+    }
+
+    #[test]
+    fn test_merge_must_keep_columns() {
+        use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+
+        let schema = sample_schema();
+        let manifest = Manifest::new(
+            schema.clone(),
+            Arc::new(vec![sample_fragment(0)]),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+        let new_column = Schema::try_from(&ArrowSchema::new(vec![ArrowField::new(
+            "c",
+            DataType::Utf8,
+            true,
+        )]))
+        .unwrap();
+        let merge = |base: Schema| {
+            let mut schema = base.merge(new_column.clone()).unwrap();
+            schema.set_field_id(Some(manifest.max_field_id()));
+            let fragments = vec![Fragment::with_file_legacy(0, "path2", &schema, Some(10))];
+            Operation::Merge { fragments, schema }
+        };
+
+        // Adding a column is fine
+        validate_operation(Some(&manifest), &merge(schema.clone())).unwrap();
+
+        // A column rewritten under a new id is still there
+        let mut recast = schema.clone();
+        recast.mut_field_by_id(0).unwrap().id = -1;
+        validate_operation(Some(&manifest), &merge(recast)).unwrap();
+
+        // So is a column renamed as well, top-level or nested, as long as it
+        // stays in the same place
+        for id in [0, 2] {
+            let mut renamed = schema.clone();
+            let field = renamed.mut_field_by_id(id).unwrap();
+            field.name = "renamed".to_string();
+            field.id = -1;
+            validate_operation(Some(&manifest), &merge(renamed)).unwrap();
+        }
+
+        // Dropping a column is not
+        let dropped = merge(schema.project(&["b"]).unwrap());
+        let err = validate_operation(Some(&manifest), &dropped).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        assert!(err.to_string().contains("remove column \"a\""), "{}", err);
+    }
+
+    #[test]
+    fn test_operation_schema() {
+        let schema = sample_schema();
+        for operation in sample_operations() {
+            match operation {
+                Operation::Overwrite { .. }
+                | Operation::Merge { .. }
+                | Operation::Project { .. } => {
+                    assert_eq!(operation.schema(), Some(&schema), "{}", operation.name());
+                }
+                _ => assert!(operation.schema().is_none(), "{}", operation.name()),
+            }
+        }
+
+        let operation = Operation::Append {
+            fragments: vec![sample_fragment(0)],
+        };
+        assert!(operation.schema().is_none());
+    }
+
+    #[test]
+    fn test_update_constructor() {
+        use lance_table::format::{DeletionFile, DeletionFileType};
+
+        let existing = vec![sample_fragment(0), sample_fragment(1), sample_fragment(2)];
+
+        // Vertical: rows of fragment 0 are partly and of fragment 1 entirely
+        // moved to a new fragment
+        let mut partly_deleted = sample_fragment(0);
+        partly_deleted.deletion_file = Some(DeletionFile {
+            read_version: 1,
+            id: 1,
+            file_type: DeletionFileType::Array,
+            num_deleted_rows: Some(5),
+        });
+        let operation = Operation::update(
+            &existing[..2],
+            vec![partly_deleted.clone()],
+            vec![sample_fragment(3)],
+            vec![],
+        );
+        let Operation::Update {
+            removed_fragment_ids,
+            updated_fragments,
+            new_fragments,
+            fields_modified,
+            mem_wal_to_flush,
+        } = operation
+        else {
+            panic!("Expected an Update operation");
+        };
+        assert_eq!(removed_fragment_ids, vec![1]);
+        assert_eq!(updated_fragments, vec![partly_deleted]);
+        assert_eq!(new_fragments, vec![sample_fragment(3)]);
+        assert!(fields_modified.is_empty());
+        assert!(mem_wal_to_flush.is_none());
+
+        // Horizontal: every fragment gets a new data file for an added column
+        let updated = existing
+            .iter()
+            .map(|fragment| {
+                let mut fragment = fragment.clone();
+                fragment.files.push(DataFile::new_legacy_from_fields(
+                    format!("new{}", fragment.id),
+                    vec![4],
+                ));
+                fragment
+            })
+            .collect::<Vec<_>>();
+        let operation = Operation::update(&existing, updated.clone(), vec![], vec![4]);
+        let Operation::Update {
+            removed_fragment_ids,
+            updated_fragments,
+            fields_modified,
+            ..
+        } = operation
+        else {
+            panic!("Expected an Update operation");
+        };
+        assert!(removed_fragment_ids.is_empty());
+        assert_eq!(updated_fragments, updated);
+        assert_eq!(fields_modified, vec![4]);
+    }
+
+    #[test]
+    fn test_indices_affected() {
+        let schema = sample_schema();
+        let fragments = vec![sample_fragment(0), sample_fragment(1)];
+        let mut struct_index = sample_index("b_f1_idx");
+        struct_index.fields = vec![2];
+        let current_indices = vec![sample_index("a_idx"), struct_index];
+
+        // Dropping the struct field drops the index over its child
+        let projected = schema.project(&["a"]).unwrap();
+        let operation = Operation::Project {
+            schema: projected.clone(),
+        };
+        let (retained, dropped) =
+            operation.indices_affected(&projected, &fragments, &current_indices);
+        assert_eq!(retained, vec!["a_idx".to_string()]);
+        assert_eq!(dropped, vec!["b_f1_idx".to_string()]);
+
+        // Modifying an indexed field in every covered fragment drops the index
+        let operation = Operation::Update {
+            removed_fragment_ids: vec![],
+            updated_fragments: fragments.clone(),
+            new_fragments: vec![],
+            fields_modified: vec![0],
+            mem_wal_to_flush: None,
+        };
+        let (retained, dropped) = operation.indices_affected(&schema, &fragments, &current_indices);
+        assert_eq!(retained, vec!["b_f1_idx".to_string()]);
+        assert_eq!(dropped, vec!["a_idx".to_string()]);
+
+        // Appending keeps every index
+        let operation = Operation::Append {
+            fragments: vec![sample_fragment(2)],
+        };
+        let (retained, dropped) = operation.indices_affected(&schema, &fragments, &current_indices);
+        assert_eq!(retained.len(), 2);
+        assert!(dropped.is_empty());
+
+        // Creating an index replaces the one of the same name, and drops removed ones
+        let operation = Operation::CreateIndex {
+            new_indices: vec![sample_index("a_idx")],
+            removed_indices: vec![current_indices[1].clone()],
+        };
+        let (retained, dropped) = operation.indices_affected(&schema, &fragments, &current_indices);
+        assert!(retained.is_empty());
+        assert_eq!(dropped, vec!["a_idx".to_string(), "b_f1_idx".to_string()]);
+
+        // So does the new index of a data replacement
+        let operation = Operation::DataReplacement {
+            replacements: vec![],
+            new_index: Some(sample_index("b_f1_idx")),
+        };
+        let (retained, dropped) = operation.indices_affected(&schema, &fragments, &current_indices);
+        assert_eq!(retained, vec!["a_idx".to_string()]);
+        assert_eq!(dropped, vec!["b_f1_idx".to_string()]);
+    }
+
+    #[test]
+    fn test_apply_metadata_to_schema() {
+        let original = sample_schema();
+        let metadata = HashMap::from([("key".to_string(), "new".to_string())]);
+
+        let mut schema = original.clone();
+        Operation::UpdateConfig {
+            upsert_values: None,
+            delete_keys: None,
+            delete_key_prefixes: None,
+            schema_metadata: Some(metadata.clone()),
+            schema_metadata_delete_keys: None,
+            field_metadata: Some(HashMap::from([(2, metadata.clone())])),
+        }
+        .apply_metadata_to_schema(&mut schema);
+        assert_eq!(schema.metadata, metadata);
+        assert_eq!(schema.field_by_id(2).unwrap().metadata, metadata);
+        assert_eq!(schema.field_by_id(0).unwrap().metadata, HashMap::new());
+        // Only metadata changes, the fields stay the same
+        let ids_and_names = |schema: &Schema| {
+            schema
+                .fields_pre_order()
+                .map(|f| (f.id, f.name.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids_and_names(&schema), ids_and_names(&original));
+
+        // Only the given kinds of metadata are replaced
+        let mut schema = original.clone();
+        Operation::UpdateConfig {
+            upsert_values: Some(metadata.clone()),
+            delete_keys: None,
+            delete_key_prefixes: None,
+            schema_metadata: None,
+            schema_metadata_delete_keys: None,
+            field_metadata: Some(HashMap::from([(0, metadata.clone())])),
+        }
+        .apply_metadata_to_schema(&mut schema);
+        assert_eq!(schema.metadata, original.metadata);
+        assert_eq!(schema.field_by_id(0).unwrap().metadata, metadata);
+
+        // Overwrite takes the metadata of the new schema
+        let mut new_schema = original.clone().with_metadata_entry("other", "value");
+        new_schema.field_by_id_mut(3).unwrap().metadata = metadata.clone();
+        let mut schema = original.clone();
+        Operation::Overwrite {
+            fragments: vec![],
+            schema: new_schema.clone(),
+            config_upsert_values: None,
+        }
+        .apply_metadata_to_schema(&mut schema);
+        assert_eq!(schema.metadata, new_schema.metadata);
+        assert_eq!(schema.field_by_id(3).unwrap().metadata, metadata);
+
+        // Other operations don't change metadata
+        let mut schema = original.clone();
+        Operation::Project {
+            schema: new_schema.clone(),
+        }
+        .apply_metadata_to_schema(&mut schema);
+        assert_eq!(schema.metadata, original.metadata);
+        assert!(schema.field_by_id(3).unwrap().metadata.is_empty());
+    }
+
+    #[test]
+    fn test_enable_stable_row_ids_on_existing_dataset() {
+        let manifest = Manifest::new(
+            sample_schema(),
+            Arc::new(vec![sample_fragment(0)]),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+        let config = ManifestWriteConfig {
+            use_move_stable_row_ids: true,
+            ..Default::default()
+        };
+        let transaction = Transaction::new(
+            1,
+            Operation::Append {
+                fragments: vec![sample_fragment(0)],
+            },
+            None,
+            None,
+        );
+
+        let err = transaction
+            .build_manifest(Some(&manifest), vec![], "txn", &config, None)
+            .unwrap_err();
+        assert!(err.is_stable_row_id_incompatibility(), "{}", err);
+
+        // Other failures are not mistaken for it
+        let err = transaction
+            .build_manifest(None, vec![], "txn", &config, None)
+            .unwrap_err();
+        assert!(!err.is_stable_row_id_incompatibility(), "{}", err);
+    }
+
+    #[test]
+    fn test_reserved_fragment_range() {
+        let schema = sample_schema();
+        let manifest = Manifest::new(
+            schema.clone(),
+            Arc::new(vec![sample_fragment(2)]),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+        let config = ManifestWriteConfig::default();
+
+        let reserve = Transaction::new(
+            1,
+            Operation::ReserveFragments { num_fragments: 5 },
+            None,
+            None,
+        );
+        let reserved = reserve.reserved_fragment_range(Some(&manifest));
+        assert_eq!(reserved, 3..8);
+        let (manifest, _) = reserve
+            .build_manifest(Some(&manifest), vec![], "txn", &config, None)
+            .unwrap();
+        assert_eq!(manifest.max_fragment_id(), Some(reserved.end - 1));
+
+        let append = Transaction::new(
+            2,
+            Operation::Append {
+                fragments: vec![sample_fragment(0), sample_fragment(0)],
+            },
+            None,
+            None,
+        );
+        assert!(append.reserved_fragment_range(Some(&manifest)).is_empty());
+        let (manifest, _) = append
+            .build_manifest(Some(&manifest), vec![], "txn", &config, None)
+            .unwrap();
+        let ids = manifest.fragments.iter().map(|f| f.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![2, 8, 9]);
+
+        // Reserving on an empty dataset skips the "unassigned" id 0
+        assert_eq!(reserve.reserved_fragment_range(None), 1..6);
+    }
+
+    #[test]
+    fn test_update_field_metadata_missing_field() {
+        let manifest = Manifest::new(
+            sample_schema(),
+            Arc::new(vec![sample_fragment(0)]),
+            DataStorageFormat::default(),
+            /*blob_dataset_version= */ None,
+        );
+
+        let build = |field_metadata: HashMap<u32, HashMap<String, String>>| {
+            let operation = Operation::UpdateConfig {
+                upsert_values: None,
+                delete_keys: None,
+                delete_key_prefixes: None,
+                schema_metadata: None,
+                schema_metadata_delete_keys: None,
+                field_metadata: Some(field_metadata),
+            };
+            validate_operation(Some(&manifest), &operation)?;
+            Transaction::new(1, operation, None, None)
+                .build_manifest(
+                    Some(&manifest),
+                    vec![],
+                    "txn",
+                    &ManifestWriteConfig::default(),
+                    None,
+                )
+                .map(|(manifest, _)| manifest)
+        };
+        let metadata = HashMap::from([("key".to_string(), "value".to_string())]);
+
+        let err = build(HashMap::from([
+            (1, metadata.clone()),
+            (42, metadata.clone()),
+        ]))
+        .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        assert!(err.to_string().contains("field 42"), "{}", err);
+
+        // The error is raised by build_manifest too, before any updates
+        let operation = Operation::UpdateConfig {
+            upsert_values: Some(HashMap::from([("k".to_string(), "v".to_string())])),
+            delete_keys: None,
+            delete_key_prefixes: None,
+            schema_metadata: None,
+            schema_metadata_delete_keys: None,
+            field_metadata: Some(HashMap::from([(42, metadata.clone())])),
+        };
+        let err = Transaction::new(1, operation, None, None)
+            .build_manifest(
+                Some(&manifest),
+                vec![],
+                "txn",
+                &ManifestWriteConfig::default(),
+                None,
+            )
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+
+        // Only the requested field's metadata changes
+        let new_manifest = build(HashMap::from([(1, metadata.clone())])).unwrap();
+        for field in new_manifest.schema.fields_pre_order() {
+            if field.id == 1 {
+                assert_eq!(field.metadata, metadata);
+            } else {
+                assert!(field.metadata.is_empty(), "{:?}", field);
+            }
+        }
+    }
+
+    #[test]
+    fn test_schema_fragments_valid_nested_message() {
+        use arrow_schema::{
+            DataType, Field as ArrowField, Fields as ArrowFields, Schema as ArrowSchema,
+        };
+
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let mut fragment = Fragment::new(7);
+        // Contains every field except b.f2 (id=3)
+        fragment.add_file_legacy("path1", &schema.project_by_ids(&[0, 1, 2], false));
+
+        let err = validate_schema_covers_fragments(&schema, &[fragment]).unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains("Fragment 7 missing field b.f2 (id=3)"),
+            "{}",
+            message
+        );
+        assert!(!message.contains("logical_type"), "{}", message);
+    }
+
+    #[test]
+    fn test_compaction_helpers() {
+        let groups = vec![RewriteGroup {
+            old_fragments: vec![Fragment::new(1), Fragment::new(2)],
+            new_fragments: vec![Fragment::new(3)],
+        }];
+        let expected = Operation::Rewrite {
+            groups: groups.clone(),
+            rewritten_indices: vec![],
+            frag_reuse_index: None,
+        };
+        assert_eq!(Operation::compact(groups.clone()), expected);
+
+        let transaction = Transaction::compaction(4, groups);
+        assert_eq!(transaction.read_version, 4);
+        assert_eq!(transaction.operation, expected);
+
+        // Serializes identically to the hand-built operation
+        let hand_built = Transaction {
+            operation: expected,
+            ..transaction.clone()
+        };
+        assert_eq!(
+            pb::Transaction::from(&transaction),
+            pb::Transaction::from(&hand_built)
+        );
+    }
+
+    #[test]
+    fn test_recalculate_fragment_bitmap_many_groups() {
+        // The straightforward implementation, used as a reference
+        fn reference(old: &RoaringBitmap, groups: &[RewriteGroup]) -> Result<RoaringBitmap> {
+            let mut new_bitmap = old.clone();
+            for group in groups {
+                let any_in_index = group
+                    .old_fragments
+                    .iter()
+                    .any(|frag| old.contains(frag.id as u32));
+                let all_in_index = group
+                    .old_fragments
+                    .iter()
+                    .all(|frag| old.contains(frag.id as u32));
+                if any_in_index {
+                    if all_in_index {
+                        for frag_id in group.old_fragments.iter().map(|frag| frag.id as u32) {
+                            new_bitmap.remove(frag_id);
+                        }
+                        new_bitmap.extend(group.new_fragments.iter().map(|frag| frag.id as u32));
+                    } else {
+                        return Err(Error::invalid_input("split", location!()));
+                    }
+                }
+            }
+            Ok(new_bitmap)
+        }
+
+        // 1000 groups of 4 fragments each, every other group is indexed
+        let num_groups = 1000;
+        let groups = (0..num_groups)
+            .map(|i| RewriteGroup {
+                old_fragments: (0..4).map(|j| Fragment::new(i * 4 + j)).collect(),
+                new_fragments: vec![Fragment::new(100_000 + i)],
+            })
+            .collect::<Vec<_>>();
+        let old = (0..num_groups)
+            .filter(|i| i % 2 == 0)
+            .flat_map(|i| (0..4).map(move |j| (i * 4 + j) as u32))
+            .collect::<RoaringBitmap>();
+
+        let expected = reference(&old, &groups).unwrap();
+        let actual = Transaction::recalculate_fragment_bitmap(&old, &groups).unwrap();
+        assert_eq!(actual, expected);
+        assert_eq!(actual.len(), num_groups / 2);
+
+        // A group that is only partially indexed is still an error
+        let mut split = old.clone();
+        split.remove(1);
+        assert!(reference(&split, &groups).is_err());
+        assert!(Transaction::recalculate_fragment_bitmap(&split, &groups).is_err());
+    }
+
+    #[test]
+    fn test_config_conflicts_with() {
+        let update_config = |upsert_keys: &[&str], delete_keys: &[&str]| Operation::UpdateConfig {
+            upsert_values: Some(
+                upsert_keys
+                    .iter()
+                    .map(|key| (key.to_string(), "v".to_string()))
+                    .collect(),
+            ),
+            delete_keys: Some(delete_keys.iter().map(|key| key.to_string()).collect()),
+            delete_key_prefixes: None,
+            schema_metadata: None,
+            schema_metadata_delete_keys: None,
+            field_metadata: None,
+        };
+
+        // Overlapping upserts
+        let a = update_config(&["a", "b"], &[]);
+        let b = update_config(&["b"], &[]);
+        assert!(a.config_conflicts_with(&b));
+        assert!(b.config_conflicts_with(&a));
+
+        // Deleting a key that the other upserts
+        let delete_b = update_config(&[], &["b"]);
+        assert!(a.config_conflicts_with(&delete_b));
+        assert!(delete_b.config_conflicts_with(&a));
+
+        // Disjoint keys
+        let c = update_config(&["c"], &["d"]);
+        assert!(!a.config_conflicts_with(&c));
+        assert!(!c.config_conflicts_with(&a));
+
+        // Both replacing the schema metadata
+        let with_metadata = Operation::UpdateConfig {
+            upsert_values: None,
+            delete_keys: None,
+            delete_key_prefixes: None,
+            schema_metadata: Some(HashMap::from([("k".to_string(), "v".to_string())])),
+            schema_metadata_delete_keys: None,
+            field_metadata: None,
+        };
+        assert!(with_metadata.config_conflicts_with(&with_metadata));
+        assert!(!with_metadata.config_conflicts_with(&a));
+    }
+
+    #[test]
+    fn test_classify_conflict() {
+        let delete = |fragment_id: u64| {
+            Transaction::new(
+                0,
+                Operation::Delete {
+                    updated_fragments: vec![sample_fragment(fragment_id)],
+                    deleted_fragment_ids: vec![],
+                    predicate: "x > 2".to_string(),
+                },
+                None,
+                None,
+            )
+        };
+        let update_config = |key: &str| {
+            Transaction::new(
+                0,
+                Operation::UpdateConfig {
+                    upsert_values: Some(HashMap::from([(key.to_string(), "v".to_string())])),
+                    delete_keys: None,
+                    delete_key_prefixes: None,
+                    schema_metadata: None,
+                    schema_metadata_delete_keys: None,
+                    field_metadata: None,
+                },
+                None,
+                None,
+            )
+        };
+        let append = Transaction::new(
+            0,
+            Operation::Append {
+                fragments: vec![sample_fragment(5)],
+            },
+            None,
+            None,
+        );
+        let overwrite = Transaction::new(
+            0,
+            Operation::Overwrite {
+                fragments: vec![sample_fragment(0)],
+                schema: sample_schema(),
+                config_upsert_values: None,
+            },
+            None,
+            None,
+        );
+        let restore = Transaction::new(0, Operation::Restore { version: 1 }, None, None);
+
+        assert_eq!(
+            delete(0).classify_conflict(&delete(0)),
+            Some(ConflictReason::SameFragments)
+        );
+        assert_eq!(
+            delete(0).classify_conflict(&delete(1)),
+            Some(ConflictReason::DisjointOk)
+        );
+        assert_eq!(
+            update_config("a").classify_conflict(&update_config("a")),
+            Some(ConflictReason::ConfigKeyOverlap)
+        );
+        assert_eq!(
+            update_config("a").classify_conflict(&update_config("b")),
+            Some(ConflictReason::DisjointOk)
+        );
+        assert_eq!(append.classify_conflict(&delete(0)), None);
+        assert_eq!(
+            append.classify_conflict(&overwrite),
+            Some(ConflictReason::Incompatible)
+        );
+        assert_eq!(overwrite.classify_conflict(&append), None);
+        assert_eq!(
+            append.classify_conflict(&restore),
+            Some(ConflictReason::Incompatible)
+        );
+        assert_eq!(
+            restore.classify_conflict(&restore),
+            Some(ConflictReason::DisjointOk)
+        );
+        let other_restore = Transaction::new(0, Operation::Restore { version: 2 }, None, None);
+        assert_eq!(
+            restore.classify_conflict(&other_restore),
+            Some(ConflictReason::Restore)
+        );
+
+        // Index creation conflicts with a rewrite of a fragment it covers
+        let create_index = Transaction::new(
+            0,
+            Operation::CreateIndex {
+                new_indices: vec![sample_index("idx")],
+                removed_indices: vec![],
+            },
+            None,
+            None,
+        );
+        let rewrite = |fragment_id: u64| {
+            Transaction::compaction(
+                0,
+                vec![RewriteGroup {
+                    old_fragments: vec![sample_fragment(fragment_id)],
+                    new_fragments: vec![sample_fragment(10)],
+                }],
+            )
+        };
+        assert_eq!(
+            create_index.classify_conflict(&rewrite(1)),
+            Some(ConflictReason::IndexVsRewrite)
+        );
+        assert_eq!(
+            rewrite(1).classify_conflict(&create_index),
+            Some(ConflictReason::IndexVsRewrite)
+        );
+        assert_eq!(
+            create_index.classify_conflict(&rewrite(2)),
+            Some(ConflictReason::DisjointOk)
+        );
+    }
 }
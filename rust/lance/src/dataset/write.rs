@@ -9,9 +9,7 @@ use chrono::TimeDelta;
 use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use datafusion::physical_plan::SendableRecordBatchStream;
 use futures::{Stream, StreamExt, TryStreamExt};
-use lance_core::datatypes::{
-    NullabilityComparison, OnMissing, OnTypeMismatch, SchemaCompareOptions, StorageClass,
-};
+use lance_core::datatypes::{OnMissing, OnTypeMismatch, StorageClass};
 use lance_core::error::LanceOptionExt;
 use lance_core::utils::tracing::{AUDIT_MODE_CREATE, AUDIT_TYPE_DATA, TRACE_FILE_AUDIT};
 use lance_core::{datatypes::Schema, Error, Result};
@@ -362,18 +360,9 @@ pub async fn write_fragments_internal(
         match params.mode {
             WriteMode::Append | WriteMode::Create => {
                 // Append mode, so we need to check compatibility
-                schema.check_compatible(
-                    dataset.schema(),
-                    &SchemaCompareOptions {
-                        // We don't care if the user claims their data is nullable / non-nullable.  We will
-                        // verify against the actual data.
-                        compare_nullability: NullabilityComparison::Ignore,
-                        allow_missing_if_nullable: true,
-                        ignore_field_order: true,
-                        compare_dictionary: dataset.is_legacy_storage(),
-                        ..Default::default()
-                    },
-                )?;
+                dataset
+                    .schema()
+                    .check_append_compatible_schema(&schema, dataset.is_legacy_storage())?;
                 // Project from the dataset schema, because it has the correct field ids.
                 let write_schema = dataset.schema().project_by_schema(
                     &schema,
@@ -765,7 +754,7 @@ impl Iterator for SpillStreamIter {
 mod tests {
     use super::*;
 
-    use arrow_array::{Int32Array, RecordBatchReader, StructArray};
+    use arrow_array::{Int32Array, RecordBatchIterator, RecordBatchReader, StructArray};
     use arrow_schema::{DataType, Field as ArrowField, Fields, Schema as ArrowSchema};
     use datafusion::{error::DataFusionError, physical_plan::stream::RecordBatchStreamAdapter};
     use futures::TryStreamExt;
@@ -854,6 +843,42 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_write_reserved_field_names() {
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            lance_core::ROW_ID,
+            DataType::Int32,
+            false,
+        )]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from_iter(0..10))])
+                .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let err = Dataset::write(reader, "memory://reserved_names", None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains(lance_core::ROW_ID), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_write_empty_struct() {
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "s",
+            DataType::Struct(Fields::empty()),
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StructArray::new_empty_fields(10, None))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let err = Dataset::write(reader, "memory://empty_struct", None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("has no children"), "{}", err);
+    }
+
     #[tokio::test]
     async fn test_file_size() {
         let reader_to_frags = |data_reader: Box<dyn RecordBatchReader + Send>| {
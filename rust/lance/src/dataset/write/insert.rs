@@ -294,6 +294,8 @@ impl<'a> InsertBuilder<'a> {
         }
 
         // Validate schema
+        data_schema.assert_no_row_id_fields()?;
+        data_schema.assert_no_empty_structs()?;
         if matches!(context.params.mode, WriteMode::Append) {
             if let WriteDestination::Dataset(dataset) = &context.dest {
                 // If the dataset is already using (or not using) move stable row ids, we need to match
@@ -310,25 +312,26 @@ impl<'a> InsertBuilder<'a> {
                         dataset.manifest.uses_move_stable_row_ids();
                 }
                 let m = dataset.manifest.as_ref();
-                let mut schema_cmp_opts = SchemaCompareOptions {
-                    // In the legacy format we stored the dictionary in the manifest and
-                    // all files must have identical dictionaries.
-                    //
-                    // In 2.0+ the dictionary is stored in the files and dictionaries may
-                    // fluctuate between files.
-                    compare_dictionary: m.should_use_legacy_format(),
-                    // array nullability is checked later, using actual data instead
-                    // of the schema
-                    compare_nullability: NullabilityComparison::Ignore,
-                    ..Default::default()
-                };
+                // In the legacy format we stored the dictionary in the manifest and
+                // all files must have identical dictionaries.
+                //
+                // In 2.0+ the dictionary is stored in the files and dictionaries may
+                // fluctuate between files.
+                let compare_dictionary = m.should_use_legacy_format();
                 if m.blob_dataset_version.is_none() {
+                    m.schema
+                        .check_append_compatible_schema(&data_schema, compare_dictionary)?;
+                } else {
                     // Balanced datasets don't yet support schema evolution
-                    schema_cmp_opts.ignore_field_order = true;
-                    schema_cmp_opts.allow_missing_if_nullable = true;
+                    let schema_cmp_opts = SchemaCompareOptions {
+                        compare_dictionary,
+                        // array nullability is checked later, using actual data instead
+                        // of the schema
+                        compare_nullability: NullabilityComparison::Ignore,
+                        ..Default::default()
+                    };
+                    data_schema.check_compatible(&m.schema, &schema_cmp_opts)?;
                 }
-
-                data_schema.check_compatible(&m.schema, &schema_cmp_opts)?;
             }
         }
 
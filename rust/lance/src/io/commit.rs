@@ -24,7 +24,7 @@ use std::num::NonZero;
 use std::sync::Arc;
 use std::time::Instant;
 
-use conflict_resolver::TransactionRebase;
+pub(crate) use conflict_resolver::TransactionRebase;
 use lance_core::utils::backoff::{Backoff, SlotBackoff};
 use lance_core::utils::mask::RowIdTreeMap;
 use lance_file::version::LanceFileVersion;
@@ -51,6 +51,7 @@ use prost::Message;
 use super::ObjectStore;
 use crate::dataset::cleanup::auto_cleanup_hook;
 use crate::dataset::fragment::FileFragment;
+use crate::dataset::rowids::{share_row_id_files, SharedRowIds};
 use crate::dataset::transaction::{Operation, Transaction};
 use crate::dataset::{
     load_new_transactions, write_manifest_file, ManifestWriteConfig, NewTransactionResult, BLOB_DIR,
@@ -97,6 +98,36 @@ async fn write_transaction_file(
     Ok(file_name)
 }
 
+/// Move the row id sequences of the fragments a transaction created into a
+/// single shared file, rather than one inline sequence per fragment.
+///
+/// `previous_manifest` is the manifest the new one was built on, if any.
+/// New fragments are assigned ids above its high water mark (or from zero on
+/// overwrite), and the built manifest keeps its fragments sorted by id.
+/// `shared` carries the file written by a previous attempt of the commit.
+async fn write_shared_row_ids(
+    object_store: &ObjectStore,
+    base_path: &Path,
+    transaction: &Transaction,
+    previous_manifest: Option<&Manifest>,
+    manifest: &mut Manifest,
+    shared: &mut SharedRowIds,
+) -> Result<()> {
+    if !manifest.uses_move_stable_row_ids() {
+        return Ok(());
+    }
+    let first_new_id = match transaction.operation {
+        Operation::Overwrite { .. } => 0,
+        Operation::Append { .. } | Operation::Update { .. } => previous_manifest
+            .and_then(|m| m.max_fragment_id())
+            .map_or(0, |id| id + 1),
+        _ => return Ok(()),
+    };
+    let fragments = Arc::make_mut(&mut manifest.fragments);
+    let start = fragments.partition_point(|f| f.id < first_new_id);
+    share_row_id_files(object_store, base_path, &mut fragments[start..], shared).await
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn do_commit_new_dataset(
     object_store: &ObjectStore,
@@ -112,6 +143,15 @@ async fn do_commit_new_dataset(
 
     let (mut manifest, indices) =
         transaction.build_manifest(None, vec![], &transaction_file, write_config, blob_version)?;
+    write_shared_row_ids(
+        object_store,
+        base_path,
+        transaction,
+        None,
+        &mut manifest,
+        &mut SharedRowIds::default(),
+    )
+    .await?;
 
     manifest.blob_dataset_version = blob_version;
 
@@ -554,6 +594,7 @@ pub(crate) async fn do_commit_detached_transaction(
 
     // We still do a loop since we may have conflicts in the random version we pick
     let mut backoff = Backoff::default();
+    let mut shared_row_ids = SharedRowIds::default();
     while backoff.attempt() < commit_config.num_retries {
         // Pick a random u64 with the highest bit set to indicate it is detached
         let random_version = thread_rng().gen::<u64>() | DETACHED_VERSION_MASK;
@@ -570,13 +611,25 @@ pub(crate) async fn do_commit_detached_transaction(
                 )
                 .await?
             }
-            _ => transaction.build_manifest(
-                Some(dataset.manifest.as_ref()),
-                dataset.load_indices().await?.as_ref().clone(),
-                &transaction_file,
-                write_config,
-                new_blob_version,
-            )?,
+            _ => {
+                let (mut manifest, indices) = transaction.build_manifest(
+                    Some(dataset.manifest.as_ref()),
+                    dataset.load_indices().await?.as_ref().clone(),
+                    &transaction_file,
+                    write_config,
+                    new_blob_version,
+                )?;
+                write_shared_row_ids(
+                    object_store,
+                    &dataset.base,
+                    transaction,
+                    Some(dataset.manifest.as_ref()),
+                    &mut manifest,
+                    &mut shared_row_ids,
+                )
+                .await?;
+                (manifest, indices)
+            }
         };
 
         manifest.version = random_version;
@@ -733,6 +786,8 @@ pub(crate) async fn commit_transaction(
     // Other transactions that may have been committed since the read_version.
     // We keep pair of (version, transaction). No other transactions to check initially
     let mut other_transactions: Vec<(u64, Arc<Transaction>)>;
+    // Reused across attempts while the row ids of the new fragments don't change
+    let mut shared_row_ids = SharedRowIds::default();
 
     while backoff.attempt() < num_attempts {
         // We are pessimistic here and assume there may be other transactions
@@ -786,13 +841,25 @@ pub(crate) async fn commit_transaction(
                 )
                 .await?
             }
-            _ => transaction.build_manifest(
-                Some(dataset.manifest.as_ref()),
-                dataset.load_indices().await?.as_ref().clone(),
-                &transaction_file,
-                write_config,
-                new_blob_version,
-            )?,
+            _ => {
+                let (mut manifest, indices) = transaction.build_manifest(
+                    Some(dataset.manifest.as_ref()),
+                    dataset.load_indices().await?.as_ref().clone(),
+                    &transaction_file,
+                    write_config,
+                    new_blob_version,
+                )?;
+                write_shared_row_ids(
+                    object_store,
+                    &dataset.base,
+                    &transaction,
+                    Some(dataset.manifest.as_ref()),
+                    &mut manifest,
+                    &mut shared_row_ids,
+                )
+                .await?;
+                (manifest, indices)
+            }
         };
 
         manifest.version = target_version;
@@ -4,7 +4,9 @@
 use crate::index::frag_reuse::{build_frag_reuse_index_metadata, load_frag_reuse_index_details};
 use crate::io::deletion::read_dataset_deletion_file;
 use crate::{
-    dataset::transaction::{Operation, Transaction},
+    dataset::transaction::{
+        index_covers, ConflictReason, DataReplacementGroup, Operation, RewriteGroup, Transaction,
+    },
     Dataset,
 };
 use futures::{StreamExt, TryStreamExt};
@@ -13,7 +15,6 @@ use lance_core::{
     Error, Result,
 };
 use lance_index::frag_reuse::FRAG_REUSE_INDEX_NAME;
-use lance_index::mem_wal::MemWal;
 use lance_table::format::Index;
 use lance_table::{format::Fragment, io::deletion::write_deletion_file};
 use snafu::{location, Location};
@@ -33,6 +34,10 @@ pub struct TransactionRebase<'a> {
     modified_fragment_ids: HashSet<u64>,
     affected_rows: Option<&'a RowIdTreeMap>,
     conflicting_frag_reuse_indices: Vec<Index>,
+    /// The overlap that decided whether the last transaction passed to
+    /// [`Self::check_txn`] conflicts, and whether it happened. `None` if they
+    /// conflict, or not, whatever they touch.
+    conflict_condition: Option<(ConflictReason, bool)>,
 }
 
 impl<'a> TransactionRebase<'a> {
@@ -48,6 +53,7 @@ impl<'a> TransactionRebase<'a> {
             | Operation::CreateIndex { .. }
             | Operation::ReserveFragments { .. }
             | Operation::Project { .. }
+            | Operation::RelaxNullability { .. }
             | Operation::UpdateConfig { .. }
             | Operation::Restore { .. }
             | Operation::UpdateMemWalState { .. } => Ok(Self {
@@ -56,22 +62,15 @@ impl<'a> TransactionRebase<'a> {
                 initial_fragments: HashMap::new(),
                 modified_fragment_ids: HashSet::new(),
                 conflicting_frag_reuse_indices: Vec::new(),
+                conflict_condition: None,
             }),
             Operation::Delete {
-                updated_fragments,
-                deleted_fragment_ids,
-                ..
+                updated_fragments, ..
             }
             | Operation::Update {
-                updated_fragments,
-                removed_fragment_ids: deleted_fragment_ids,
-                ..
+                updated_fragments, ..
             } => {
-                let modified_fragment_ids = updated_fragments
-                    .iter()
-                    .map(|f| f.id)
-                    .chain(deleted_fragment_ids.iter().copied())
-                    .collect::<HashSet<_>>();
+                let modified_fragment_ids = transaction.operation.touched_fragment_ids();
 
                 // short circuit for full fragment update or delete case
                 // set affected_rows as None with non-empty modified_fragment_ids
@@ -83,6 +82,7 @@ impl<'a> TransactionRebase<'a> {
                         modified_fragment_ids,
                         affected_rows: None,
                         conflicting_frag_reuse_indices: Vec::new(),
+                        conflict_condition: None,
                     });
                 }
 
@@ -95,13 +95,11 @@ impl<'a> TransactionRebase<'a> {
                     initial_fragments,
                     modified_fragment_ids,
                     conflicting_frag_reuse_indices: Vec::new(),
+                    conflict_condition: None,
                 })
             }
-            Operation::Rewrite { groups, .. } => {
-                let modified_fragment_ids = groups
-                    .iter()
-                    .flat_map(|f| f.old_fragments.iter().map(|f| f.id))
-                    .collect::<HashSet<_>>();
+            Operation::Rewrite { .. } => {
+                let modified_fragment_ids = transaction.operation.touched_fragment_ids();
 
                 let initial_fragments =
                     initial_fragments_for_rebase(dataset, &transaction, &modified_fragment_ids)
@@ -112,9 +110,10 @@ impl<'a> TransactionRebase<'a> {
                     initial_fragments,
                     modified_fragment_ids,
                     conflicting_frag_reuse_indices: Vec::new(),
+                    conflict_condition: None,
                 })
             }
-            Operation::DataReplacement { replacements } => {
+            Operation::DataReplacement { replacements, .. } => {
                 let modified_fragment_ids =
                     replacements.iter().map(|r| r.0).collect::<HashSet<_>>();
                 let initial_fragments =
@@ -126,6 +125,7 @@ impl<'a> TransactionRebase<'a> {
                     initial_fragments,
                     modified_fragment_ids,
                     conflicting_frag_reuse_indices: Vec::new(),
+                    conflict_condition: None,
                 })
             }
             Operation::Merge { fragments, .. } => {
@@ -139,11 +139,39 @@ impl<'a> TransactionRebase<'a> {
                     initial_fragments,
                     modified_fragment_ids,
                     conflicting_frag_reuse_indices: Vec::new(),
+                    conflict_condition: None,
                 })
             }
         }
     }
 
+    /// Create a rebase that doesn't look at the dataset, for checking whether
+    /// two transactions conflict. Without the affected rows, deletes and
+    /// updates of the same fragments always conflict.
+    pub(crate) fn without_dataset(transaction: Transaction) -> Self {
+        let modified_fragment_ids = match &transaction.operation {
+            Operation::DataReplacement { replacements, .. } => {
+                replacements.iter().map(|r| r.0).collect()
+            }
+            Operation::Merge { fragments, .. } => fragments.iter().map(|f| f.id).collect(),
+            operation => operation.touched_fragment_ids(),
+        };
+        Self {
+            transaction,
+            initial_fragments: HashMap::new(),
+            modified_fragment_ids,
+            affected_rows: None,
+            conflicting_frag_reuse_indices: Vec::new(),
+            conflict_condition: None,
+        }
+    }
+
+    /// The overlap that decided whether the last checked transaction
+    /// conflicts, see [`Transaction::classify_conflict`].
+    pub(crate) fn conflict_condition(&self) -> Option<(ConflictReason, bool)> {
+        self.conflict_condition
+    }
+
     fn retryable_conflict_err(
         &self,
         other_transaction: &Transaction,
@@ -159,6 +187,15 @@ impl<'a> TransactionRebase<'a> {
         }
     }
 
+    /// Whether `other` updates or removes any fragment that this transaction
+    /// also modifies. Delete, Update, and Rewrite are only compatible with each
+    /// other if this is false.
+    fn touches_modified_fragments(&self, other: &Operation) -> bool {
+        !other
+            .touched_fragment_ids()
+            .is_disjoint(&self.modified_fragment_ids)
+    }
+
     fn incompatible_conflict_err(
         &self,
         other_transaction: &Transaction,
@@ -183,6 +220,7 @@ impl<'a> TransactionRebase<'a> {
     /// Will return an error if the transaction is not valid. Otherwise, it will
     /// return Ok(()).
     pub fn check_txn(&mut self, other_transaction: &Transaction, other_version: u64) -> Result<()> {
+        self.conflict_condition = None;
         let op = &self.transaction.operation;
         match op {
             Operation::Delete { .. } => self.check_delete_txn(other_transaction, other_version),
@@ -204,6 +242,9 @@ impl<'a> TransactionRebase<'a> {
                 self.check_reserve_fragments_txn(other_transaction, other_version)
             }
             Operation::Project { .. } => self.check_project_txn(other_transaction, other_version),
+            Operation::RelaxNullability { .. } => {
+                self.check_relax_nullability_txn(other_transaction, other_version)
+            }
             Operation::UpdateConfig { .. } => {
                 self.check_update_config_txn(other_transaction, other_version)
             }
@@ -223,14 +264,13 @@ impl<'a> TransactionRebase<'a> {
                 Operation::CreateIndex { .. }
                 | Operation::ReserveFragments { .. }
                 | Operation::Project { .. }
+                | Operation::RelaxNullability { .. }
                 | Operation::Append { .. }
                 | Operation::UpdateConfig { .. } => Ok(()),
-                Operation::Rewrite { groups, .. } => {
-                    if groups
-                        .iter()
-                        .flat_map(|f| f.old_fragments.iter().map(|f| f.id))
-                        .any(|id| self.modified_fragment_ids.contains(&id))
-                    {
+                Operation::Rewrite { .. } => {
+                    let overlaps = self.touches_modified_fragments(&other_transaction.operation);
+                    self.conflict_condition = Some((ConflictReason::SameFragments, overlaps));
+                    if overlaps {
                         Err(self.retryable_conflict_err(
                             other_transaction,
                             other_version,
@@ -241,11 +281,12 @@ impl<'a> TransactionRebase<'a> {
                     }
                 }
                 Operation::DataReplacement { replacements, .. } => {
-                    if replacements
+                    let overlaps = replacements
                         .iter()
                         .map(|r| r.0)
-                        .any(|id| self.modified_fragment_ids.contains(&id))
-                    {
+                        .any(|id| self.modified_fragment_ids.contains(&id));
+                    self.conflict_condition = Some((ConflictReason::SameFragments, overlaps));
+                    if overlaps {
                         Err(self.retryable_conflict_err(
                             other_transaction,
                             other_version,
@@ -265,12 +306,9 @@ impl<'a> TransactionRebase<'a> {
                     deleted_fragment_ids: removed_fragment_ids,
                     ..
                 } => {
-                    if !updated_fragments
-                        .iter()
-                        .map(|f| f.id)
-                        .chain(removed_fragment_ids.iter().copied())
-                        .any(|id| self.modified_fragment_ids.contains(&id))
-                    {
+                    let overlaps = self.touches_modified_fragments(&other_transaction.operation);
+                    self.conflict_condition = Some((ConflictReason::SameFragments, overlaps));
+                    if !overlaps {
                         return Ok(());
                     }
 
@@ -334,22 +372,18 @@ impl<'a> TransactionRebase<'a> {
         other_transaction: &Transaction,
         other_version: u64,
     ) -> Result<()> {
-        if let Operation::Update {
-            mem_wal_to_flush, ..
-        } = &self.transaction.operation
-        {
+        if let Operation::Update { .. } = &self.transaction.operation {
             match &other_transaction.operation {
                 Operation::CreateIndex { .. }
                 | Operation::ReserveFragments { .. }
                 | Operation::Project { .. }
+                | Operation::RelaxNullability { .. }
                 | Operation::Append { .. }
                 | Operation::UpdateConfig { .. } => Ok(()),
-                Operation::Rewrite { groups, .. } => {
-                    if groups
-                        .iter()
-                        .flat_map(|f| f.old_fragments.iter().map(|f| f.id))
-                        .any(|id| self.modified_fragment_ids.contains(&id))
-                    {
+                Operation::Rewrite { .. } => {
+                    let overlaps = self.touches_modified_fragments(&other_transaction.operation);
+                    self.conflict_condition = Some((ConflictReason::SameFragments, overlaps));
+                    if overlaps {
                         Err(self.retryable_conflict_err(
                             other_transaction,
                             other_version,
@@ -360,11 +394,12 @@ impl<'a> TransactionRebase<'a> {
                     }
                 }
                 Operation::DataReplacement { replacements, .. } => {
-                    if replacements
+                    let overlaps = replacements
                         .iter()
                         .map(|r| r.0)
-                        .any(|id| self.modified_fragment_ids.contains(&id))
-                    {
+                        .any(|id| self.modified_fragment_ids.contains(&id));
+                    self.conflict_condition = Some((ConflictReason::SameFragments, overlaps));
+                    if overlaps {
                         Err(self.retryable_conflict_err(
                             other_transaction,
                             other_version,
@@ -384,12 +419,9 @@ impl<'a> TransactionRebase<'a> {
                     deleted_fragment_ids: removed_fragment_ids,
                     ..
                 } => {
-                    if !updated_fragments
-                        .iter()
-                        .map(|f| f.id)
-                        .chain(removed_fragment_ids.iter().copied())
-                        .any(|id| self.modified_fragment_ids.contains(&id))
-                    {
+                    let overlaps = self.touches_modified_fragments(&other_transaction.operation);
+                    self.conflict_condition = Some((ConflictReason::SameFragments, overlaps));
+                    if !overlaps {
                         return Ok(());
                     }
 
@@ -438,20 +470,8 @@ impl<'a> TransactionRebase<'a> {
                 Operation::Overwrite { .. } | Operation::Restore { .. } => Err(
                     self.incompatible_conflict_err(other_transaction, other_version, location!())
                 ),
-                Operation::UpdateMemWalState { added, updated, .. } => {
-                    self.check_update_mem_wal_state_not_modify_same_mem_wal(
-                        added,
-                        mem_wal_to_flush.as_slice(),
-                        other_transaction,
-                        other_version,
-                    )?;
-                    self.check_update_mem_wal_state_not_modify_same_mem_wal(
-                        updated,
-                        mem_wal_to_flush.as_slice(),
-                        other_transaction,
-                        other_version,
-                    )?;
-                    Ok(())
+                Operation::UpdateMemWalState { .. } => {
+                    self.check_not_modify_same_mem_wal(other_transaction, other_version)
                 }
             }
         } else {
@@ -500,7 +520,7 @@ impl<'a> TransactionRebase<'a> {
                 // Merge, reserve, and project don't change row ids, so this should be fine.
                 Operation::Merge { .. } => Ok(()),
                 Operation::ReserveFragments { .. } => Ok(()),
-                Operation::Project { .. } => Ok(()),
+                Operation::Project { .. } | Operation::RelaxNullability { .. } => Ok(()),
                 // Should be compatible with rewrite if it didn't move the rows
                 // we indexed. If it did, we could retry.
                 // TODO: this will change with stable row ids.
@@ -536,6 +556,7 @@ impl<'a> TransactionRebase<'a> {
                             Ok(())
                         }
                     } else {
+                        self.conflict_condition = Some((ConflictReason::IndexVsRewrite, true));
                         let mut affected_ids = HashSet::new();
                         for index in new_indices.iter() {
                             if let Some(frag_bitmap) = &index.fragment_bitmap {
@@ -549,11 +570,12 @@ impl<'a> TransactionRebase<'a> {
                             }
                         }
 
-                        if groups
+                        let overlaps = groups
                             .iter()
                             .flat_map(|f| f.old_fragments.iter().map(|f| f.id))
-                            .any(|id| affected_ids.contains(&(id as u32)))
-                        {
+                            .any(|id| affected_ids.contains(&(id as u32)));
+                        self.conflict_condition = Some((ConflictReason::IndexVsRewrite, overlaps));
+                        if overlaps {
                             Err(self.retryable_conflict_err(
                                 other_transaction,
                                 other_version,
@@ -565,9 +587,20 @@ impl<'a> TransactionRebase<'a> {
                     }
                 }
                 Operation::UpdateConfig { .. } => Ok(()),
-                Operation::DataReplacement { .. } => {
-                    // TODO(rmeng): check that the new indices isn't on the column being replaced
-                    Err(self.retryable_conflict_err(other_transaction, other_version, location!()))
+                Operation::DataReplacement { replacements, .. } => {
+                    // The new indices may have been built on the replaced data
+                    let overlaps = replacement_touches_indices(replacements, new_indices);
+                    self.conflict_condition =
+                        Some((ConflictReason::ReplacedRegionOverlap, overlaps));
+                    if overlaps {
+                        Err(self.retryable_conflict_err(
+                            other_transaction,
+                            other_version,
+                            location!(),
+                        ))
+                    } else {
+                        Ok(())
+                    }
                 }
                 Operation::Overwrite { .. }
                 | Operation::Restore { .. }
@@ -599,24 +632,13 @@ impl<'a> TransactionRebase<'a> {
                 Operation::Append { .. }
                 | Operation::ReserveFragments { .. }
                 | Operation::Project { .. }
+                | Operation::RelaxNullability { .. }
                 | Operation::UpdateConfig { .. }
                 | Operation::UpdateMemWalState { .. } => Ok(()),
-                Operation::Delete {
-                    updated_fragments,
-                    deleted_fragment_ids,
-                    ..
-                }
-                | Operation::Update {
-                    updated_fragments,
-                    removed_fragment_ids: deleted_fragment_ids,
-                    ..
-                } => {
-                    if updated_fragments
-                        .iter()
-                        .map(|f| f.id)
-                        .chain(deleted_fragment_ids.iter().copied())
-                        .any(|id| self.modified_fragment_ids.contains(&id))
-                    {
+                Operation::Delete { .. } | Operation::Update { .. } => {
+                    let overlaps = self.touches_modified_fragments(&other_transaction.operation);
+                    self.conflict_condition = Some((ConflictReason::SameFragments, overlaps));
+                    if overlaps {
                         Err(self.retryable_conflict_err(
                             other_transaction,
                             other_version,
@@ -627,15 +649,12 @@ impl<'a> TransactionRebase<'a> {
                     }
                 }
                 Operation::Rewrite {
-                    groups,
                     frag_reuse_index: committed_fri,
                     ..
                 } => {
-                    if groups
-                        .iter()
-                        .flat_map(|f| f.old_fragments.iter().map(|f| f.id))
-                        .any(|id| self.modified_fragment_ids.contains(&id))
-                    {
+                    let overlaps = self.touches_modified_fragments(&other_transaction.operation);
+                    self.conflict_condition = Some((ConflictReason::SameFragments, overlaps));
+                    if overlaps {
                         Err(self.retryable_conflict_err(
                             other_transaction,
                             other_version,
@@ -655,8 +674,34 @@ impl<'a> TransactionRebase<'a> {
                         Ok(())
                     }
                 }
-                Operation::DataReplacement { .. } | Operation::Merge { .. } => {
-                    // TODO(rmeng): check that the fragments being replaced are not part of the groups
+                Operation::DataReplacement {
+                    replacements,
+                    new_index,
+                } => {
+                    // The rewritten fragments would lose the replaced data, and
+                    // a new index over them would need remapping.
+                    let condition = if replacements
+                        .iter()
+                        .any(|r| self.modified_fragment_ids.contains(&r.0))
+                    {
+                        (ConflictReason::SameFragments, true)
+                    } else {
+                        let overlaps =
+                            rewrite_moves_indices(groups, frag_reuse_index, new_index.as_slice());
+                        (ConflictReason::IndexVsRewrite, overlaps)
+                    };
+                    self.conflict_condition = Some(condition);
+                    if condition.1 {
+                        Err(self.retryable_conflict_err(
+                            other_transaction,
+                            other_version,
+                            location!(),
+                        ))
+                    } else {
+                        Ok(())
+                    }
+                }
+                Operation::Merge { .. } => {
                     Err(self.retryable_conflict_err(other_transaction, other_version, location!()))
                 }
                 Operation::CreateIndex {
@@ -708,6 +753,7 @@ impl<'a> TransactionRebase<'a> {
                         // Rewrite with remapping will conflict with
                         // index creation that touches overlapping fragments.
                         (_, None) => {
+                            self.conflict_condition = Some((ConflictReason::IndexVsRewrite, true));
                             let mut affected_ids = HashSet::new();
                             for index in new_indices {
                                 if let Some(frag_bitmap) = &index.fragment_bitmap {
@@ -720,11 +766,13 @@ impl<'a> TransactionRebase<'a> {
                                     ));
                                 }
                             }
-                            if groups
+                            let overlaps = groups
                                 .iter()
                                 .flat_map(|f| f.old_fragments.iter().map(|f| f.id))
-                                .any(|id| affected_ids.contains(&(id as u32)))
-                            {
+                                .any(|id| affected_ids.contains(&(id as u32)));
+                            self.conflict_condition =
+                                Some((ConflictReason::IndexVsRewrite, overlaps));
+                            if overlaps {
                                 Err(self.retryable_conflict_err(
                                     other_transaction,
                                     other_version,
@@ -753,11 +801,12 @@ impl<'a> TransactionRebase<'a> {
         match &other_transaction.operation {
             // Overwrite only conflicts with another operation modifying the same update config
             Operation::Overwrite { .. } | Operation::UpdateConfig { .. } => {
-                if self
+                let overlaps = self
                     .transaction
                     .operation
-                    .upsert_key_conflict(&other_transaction.operation)
-                {
+                    .upsert_key_conflict(&other_transaction.operation);
+                self.conflict_condition = Some((ConflictReason::ConfigKeyOverlap, overlaps));
+                if overlaps {
                     Err(self.incompatible_conflict_err(
                         other_transaction,
                         other_version,
@@ -767,7 +816,7 @@ impl<'a> TransactionRebase<'a> {
                     Ok(())
                 }
             }
-            Operation::UpdateMemWalState { .. } => {
+            Operation::Restore { .. } | Operation::UpdateMemWalState { .. } => {
                 Err(self.incompatible_conflict_err(other_transaction, other_version, location!()))
             }
             Operation::Append { .. }
@@ -776,10 +825,10 @@ impl<'a> TransactionRebase<'a> {
             | Operation::Rewrite { .. }
             | Operation::DataReplacement { .. }
             | Operation::Merge { .. }
-            | Operation::Restore { .. }
             | Operation::ReserveFragments { .. }
             | Operation::Update { .. }
-            | Operation::Project { .. } => Ok(()),
+            | Operation::Project { .. }
+            | Operation::RelaxNullability { .. } => Ok(()),
         }
     }
 
@@ -790,10 +839,18 @@ impl<'a> TransactionRebase<'a> {
     ) -> Result<()> {
         match &other_transaction.operation {
             // Append is not compatible with any operation that completely
-            // overwrites the schema.
-            Operation::Overwrite { .. }
-            | Operation::Restore { .. }
-            | Operation::UpdateMemWalState { .. } => {
+            // overwrites the schema. An append based on a version from before
+            // an overwrite would add data to a dataset that no longer exists.
+            Operation::Overwrite { .. } => Err(Error::CommitConflict {
+                version: other_version,
+                source: format!(
+                    "This Append transaction was based on version {} but the dataset was overwritten at version {}.",
+                    self.transaction.read_version, other_version
+                )
+                .into(),
+                location: location!(),
+            }),
+            Operation::Restore { .. } | Operation::UpdateMemWalState { .. } => {
                 Err(self.incompatible_conflict_err(other_transaction, other_version, location!()))
             }
             Operation::Append { .. }
@@ -803,42 +860,91 @@ impl<'a> TransactionRebase<'a> {
             | Operation::Update { .. }
             | Operation::ReserveFragments { .. }
             | Operation::Project { .. }
+            | Operation::RelaxNullability { .. }
             | Operation::Merge { .. }
             | Operation::UpdateConfig { .. }
             | Operation::DataReplacement { .. } => Ok(()),
         }
     }
 
+    /// A replacement is compatible with another operation as long as that
+    /// operation doesn't touch the replaced columns of the replaced fragments.
+    /// A replacement that also creates an index must be compatible both as a
+    /// replacement and as an index creation.
     fn check_data_replacement_txn(
         &mut self,
         other_transaction: &Transaction,
         other_version: u64,
     ) -> Result<()> {
-        match &other_transaction.operation {
-            Operation::Append { .. }
-            | Operation::Delete { .. }
-            | Operation::Update { .. }
-            | Operation::Merge { .. }
-            | Operation::UpdateConfig { .. }
-            | Operation::ReserveFragments { .. }
-            | Operation::Project { .. } => Ok(()),
-            Operation::CreateIndex { .. } => {
-                // TODO(rmeng): check that the new indices isn't on the column being replaced
-                Err(self.incompatible_conflict_err(other_transaction, other_version, location!()))
-            }
-            Operation::Rewrite { .. } => {
-                // TODO(rmeng): check that the fragments being replaced are not part of the groups
-                Err(self.incompatible_conflict_err(other_transaction, other_version, location!()))
-            }
-            Operation::DataReplacement { .. } => {
-                // TODO(rmeng): check cell conflicts
-                Err(self.incompatible_conflict_err(other_transaction, other_version, location!()))
-            }
-            Operation::Overwrite { .. }
-            | Operation::Restore { .. }
-            | Operation::UpdateMemWalState { .. } => {
+        if let Operation::DataReplacement {
+            replacements,
+            new_index,
+        } = &self.transaction.operation
+        {
+            let (reason, overlaps) = match &other_transaction.operation {
+                Operation::Append { .. }
+                | Operation::Delete { .. }
+                | Operation::Update { .. }
+                | Operation::Merge { .. }
+                | Operation::UpdateConfig { .. }
+                | Operation::ReserveFragments { .. }
+                | Operation::RelaxNullability { .. } => return Ok(()),
+                // A dropped column can't be replaced
+                Operation::Project { schema } => (
+                    ConflictReason::ReplacedRegionOverlap,
+                    replacements
+                        .iter()
+                        .flat_map(|r| r.1.fields.iter())
+                        .any(|id| schema.field_by_id(*id).is_none()),
+                ),
+                // The index may have been built on the replaced data
+                Operation::CreateIndex { new_indices, .. } => (
+                    ConflictReason::ReplacedRegionOverlap,
+                    replacement_touches_indices(replacements, new_indices),
+                ),
+                // The replaced fragments were moved, as was the data under our
+                // new index unless the rewrite defers remapping indices.
+                Operation::Rewrite {
+                    groups,
+                    frag_reuse_index,
+                    ..
+                } => {
+                    if self.touches_modified_fragments(&other_transaction.operation) {
+                        (ConflictReason::SameFragments, true)
+                    } else {
+                        (
+                            ConflictReason::IndexVsRewrite,
+                            rewrite_moves_indices(groups, frag_reuse_index, new_index.as_slice()),
+                        )
+                    }
+                }
+                Operation::DataReplacement {
+                    replacements: other_replacements,
+                    new_index: other_index,
+                } => (
+                    ConflictReason::ReplacedRegionOverlap,
+                    replacements_overlap(replacements, other_replacements)
+                        || replacement_touches_indices(replacements, other_index.as_slice())
+                        || replacement_touches_indices(other_replacements, new_index.as_slice()),
+                ),
+                Operation::Overwrite { .. }
+                | Operation::Restore { .. }
+                | Operation::UpdateMemWalState { .. } => {
+                    return Err(self.incompatible_conflict_err(
+                        other_transaction,
+                        other_version,
+                        location!(),
+                    ))
+                }
+            };
+            self.conflict_condition = Some((reason, overlaps));
+            if overlaps {
                 Err(self.incompatible_conflict_err(other_transaction, other_version, location!()))
+            } else {
+                Ok(())
             }
+        } else {
+            Err(wrong_operation_err(&self.transaction.operation))
         }
     }
 
@@ -851,6 +957,9 @@ impl<'a> TransactionRebase<'a> {
             Operation::CreateIndex { .. }
             | Operation::ReserveFragments { .. }
             | Operation::UpdateConfig { .. } => Ok(()),
+            Operation::RelaxNullability { field_ids } => {
+                self.check_relaxed_fields(field_ids, other_transaction, other_version)
+            }
 
             Operation::Update { .. }
             | Operation::Append { .. }
@@ -874,22 +983,20 @@ impl<'a> TransactionRebase<'a> {
         other_transaction: &Transaction,
         other_version: u64,
     ) -> Result<()> {
-        match &other_transaction.operation {
-            Operation::Append { .. }
-            | Operation::Delete { .. }
-            | Operation::Overwrite { .. }
-            | Operation::CreateIndex { .. }
-            | Operation::Rewrite { .. }
-            | Operation::DataReplacement { .. }
-            | Operation::Merge { .. }
-            | Operation::Restore { .. }
-            | Operation::ReserveFragments { .. }
-            | Operation::Update { .. }
-            | Operation::Project { .. }
-            | Operation::UpdateConfig { .. } => Ok(()),
-            Operation::UpdateMemWalState { .. } => {
-                Err(self.incompatible_conflict_err(other_transaction, other_version, location!()))
-            }
+        // A restore rewrites history, so it is only compatible with another
+        // restore to the same version.
+        let conflicts = self
+            .transaction
+            .operation
+            .restore_conflicts_with(&other_transaction.operation)
+            .unwrap_or(true);
+        if let Operation::Restore { .. } = &other_transaction.operation {
+            self.conflict_condition = Some((ConflictReason::Restore, conflicts));
+        }
+        if conflicts {
+            Err(self.incompatible_conflict_err(other_transaction, other_version, location!()))
+        } else {
+            Ok(())
         }
     }
 
@@ -911,6 +1018,7 @@ impl<'a> TransactionRebase<'a> {
             | Operation::ReserveFragments { .. }
             | Operation::Update { .. }
             | Operation::Project { .. }
+            | Operation::RelaxNullability { .. }
             | Operation::UpdateConfig { .. }
             | Operation::UpdateMemWalState { .. } => Ok(()),
         }
@@ -935,6 +1043,9 @@ impl<'a> TransactionRebase<'a> {
                 // Need to recompute the schema
                 Err(self.retryable_conflict_err(other_transaction, other_version, location!()))
             }
+            Operation::RelaxNullability { field_ids } => {
+                self.check_relaxed_fields(field_ids, other_transaction, other_version)
+            }
             Operation::Overwrite { .. }
             | Operation::Restore { .. }
             | Operation::UpdateMemWalState { .. } => {
@@ -943,29 +1054,28 @@ impl<'a> TransactionRebase<'a> {
         }
     }
 
-    fn check_update_config_txn(
+    fn check_relax_nullability_txn(
         &mut self,
         other_transaction: &Transaction,
         other_version: u64,
     ) -> Result<()> {
-        if let Operation::UpdateConfig {
-            schema_metadata,
-            field_metadata,
-            ..
-        } = &self.transaction.operation
-        {
+        if let Operation::RelaxNullability { field_ids } = &self.transaction.operation {
             match &other_transaction.operation {
-                Operation::Overwrite { .. } => {
-                    // Updates to schema metadata or field metadata conflict with any kind
-                    // of overwrite.
-                    if schema_metadata.is_some()
-                        || field_metadata.is_some()
-                        || self
-                            .transaction
-                            .operation
-                            .upsert_key_conflict(&other_transaction.operation)
-                    {
-                        Err(self.incompatible_conflict_err(
+                Operation::Append { .. }
+                | Operation::Delete { .. }
+                | Operation::Update { .. }
+                | Operation::CreateIndex { .. }
+                | Operation::Rewrite { .. }
+                | Operation::DataReplacement { .. }
+                | Operation::ReserveFragments { .. }
+                | Operation::RelaxNullability { .. }
+                | Operation::UpdateConfig { .. }
+                | Operation::UpdateMemWalState { .. } => Ok(()),
+                Operation::Merge { schema, .. } | Operation::Project { schema } => {
+                    let overlaps = touches_relaxed_fields(field_ids, schema);
+                    self.conflict_condition = Some((ConflictReason::RelaxedFieldOverlap, overlaps));
+                    if overlaps {
+                        Err(self.retryable_conflict_err(
                             other_transaction,
                             other_version,
                             location!(),
@@ -974,16 +1084,70 @@ impl<'a> TransactionRebase<'a> {
                         Ok(())
                     }
                 }
-                Operation::UpdateConfig { .. } => {
-                    if self
-                        .transaction
-                        .operation
-                        .upsert_key_conflict(&other_transaction.operation)
-                        | self
-                            .transaction
-                            .operation
-                            .modifies_same_metadata(&other_transaction.operation)
-                    {
+                Operation::Overwrite { .. } | Operation::Restore { .. } => Err(
+                    self.incompatible_conflict_err(other_transaction, other_version, location!())
+                ),
+            }
+        } else {
+            Err(wrong_operation_err(&self.transaction.operation))
+        }
+    }
+
+    /// Check a [`Operation::Merge`] or [`Operation::Project`] against a
+    /// concurrent [`Operation::RelaxNullability`].
+    fn check_relaxed_fields(
+        &mut self,
+        field_ids: &[u32],
+        other_transaction: &Transaction,
+        other_version: u64,
+    ) -> Result<()> {
+        let overlaps = match &self.transaction.operation {
+            Operation::Merge { schema, .. } | Operation::Project { schema } => {
+                touches_relaxed_fields(field_ids, schema)
+            }
+            _ => false,
+        };
+        self.conflict_condition = Some((ConflictReason::RelaxedFieldOverlap, overlaps));
+        if overlaps {
+            // Our schema would undo the nullability change, so it needs
+            // to be recomputed.
+            Err(self.retryable_conflict_err(other_transaction, other_version, location!()))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_update_config_txn(
+        &mut self,
+        other_transaction: &Transaction,
+        other_version: u64,
+    ) -> Result<()> {
+        if let Operation::UpdateConfig {
+            schema_metadata,
+            schema_metadata_delete_keys,
+            field_metadata,
+            ..
+        } = &self.transaction.operation
+        {
+            match &other_transaction.operation {
+                Operation::Overwrite { .. } | Operation::UpdateConfig { .. } => {
+                    let operation = &self.transaction.operation;
+                    let condition = if operation.upsert_key_conflict(&other_transaction.operation) {
+                        (ConflictReason::ConfigKeyOverlap, true)
+                    } else if let Operation::Overwrite { .. } = &other_transaction.operation {
+                        // Updates to schema metadata or field metadata conflict with any kind
+                        // of overwrite.
+                        let overlaps = schema_metadata.is_some()
+                            || schema_metadata_delete_keys.is_some()
+                            || field_metadata.is_some();
+                        (ConflictReason::SchemaMetadataOverlap, overlaps)
+                    } else {
+                        let overlaps =
+                            operation.modifies_same_metadata(&other_transaction.operation);
+                        (ConflictReason::SchemaMetadataOverlap, overlaps)
+                    };
+                    self.conflict_condition = Some(condition);
+                    if condition.1 {
                         Err(self.incompatible_conflict_err(
                             other_transaction,
                             other_version,
@@ -993,16 +1157,21 @@ impl<'a> TransactionRebase<'a> {
                         Ok(())
                     }
                 }
+                Operation::Restore { .. } => Err(self.incompatible_conflict_err(
+                    other_transaction,
+                    other_version,
+                    location!(),
+                )),
                 Operation::Append { .. }
                 | Operation::Delete { .. }
                 | Operation::CreateIndex { .. }
                 | Operation::Rewrite { .. }
                 | Operation::DataReplacement { .. }
                 | Operation::Merge { .. }
-                | Operation::Restore { .. }
                 | Operation::ReserveFragments { .. }
                 | Operation::Update { .. }
                 | Operation::Project { .. }
+                | Operation::RelaxNullability { .. }
                 | Operation::UpdateMemWalState { .. } => Ok(()),
             }
         } else {
@@ -1015,62 +1184,18 @@ impl<'a> TransactionRebase<'a> {
         other_transaction: &Transaction,
         other_version: u64,
     ) -> Result<()> {
-        if let Operation::UpdateMemWalState {
-            added,
-            updated,
-            removed: _,
-            ..
-        } = &self.transaction.operation
-        {
+        if let Operation::UpdateMemWalState { .. } = &self.transaction.operation {
             match &other_transaction.operation {
-                Operation::UpdateMemWalState {
-                    added: committed_added,
-                    updated: committed_updated,
-                    removed: _,
-                } => {
-                    // 1. if the current or last committed job is trimming flushed MemWALs,
-                    // it is compatible with any other UpdateMemWalState commits
-                    if (committed_added.is_empty() && committed_updated.is_empty())
-                        || (added.is_empty() && updated.is_empty())
-                    {
-                        return Ok(());
-                    }
-
-                    // 2. MemWALs of different regions can be changed at the same time
-                    self.check_update_mem_wal_state_not_modify_same_mem_wal(
-                        committed_added,
-                        added,
-                        other_transaction,
-                        other_version,
-                    )?;
-                    self.check_update_mem_wal_state_not_modify_same_mem_wal(
-                        committed_added,
-                        updated,
-                        other_transaction,
-                        other_version,
-                    )?;
-                    self.check_update_mem_wal_state_not_modify_same_mem_wal(
-                        committed_updated,
-                        added,
-                        other_transaction,
-                        other_version,
-                    )?;
-                    self.check_update_mem_wal_state_not_modify_same_mem_wal(
-                        committed_updated,
-                        updated,
-                        other_transaction,
-                        other_version,
-                    )?;
-                    Ok(())
+                // MemWALs of different regions or generations can be changed at
+                // the same time.
+                Operation::UpdateMemWalState { .. } => {
+                    self.check_not_modify_same_mem_wal(other_transaction, other_version)
                 }
                 Operation::Update {
                     mem_wal_to_flush, ..
                 } => {
                     if mem_wal_to_flush.is_some() {
-                        // TODO: This check could be more detailed, there is an assumption that
-                        //  once a MemWAL is sealed, there is no other operation that could change
-                        //  the state back to open, and at that point it can always be flushed.
-                        Ok(())
+                        self.check_not_modify_same_mem_wal(other_transaction, other_version)
                     } else {
                         Err(self.incompatible_conflict_err(
                             other_transaction,
@@ -1080,6 +1205,7 @@ impl<'a> TransactionRebase<'a> {
                     }
                 }
                 Operation::UpdateConfig { .. }
+                | Operation::RelaxNullability { .. }
                 | Operation::Rewrite { .. }
                 | Operation::CreateIndex { .. }
                 | Operation::ReserveFragments { .. } => Ok(()),
@@ -1100,51 +1226,22 @@ impl<'a> TransactionRebase<'a> {
         }
     }
 
-    fn check_update_mem_wal_state_not_modify_same_mem_wal(
-        &self,
-        committed: &[MemWal],
-        to_commit: &[MemWal],
+    /// Check that this transaction and `other_transaction` don't both reference
+    /// the same MemWAL, whether by adding, updating, removing, or flushing it.
+    fn check_not_modify_same_mem_wal(
+        &mut self,
         other_transaction: &Transaction,
         other_version: u64,
     ) -> Result<()> {
-        if !committed.is_empty() {
-            if to_commit.is_empty() {
-                return Ok(());
-            }
-
-            if committed.len() > 1 {
-                return Err(Error::Internal {
-                    message: format!(
-                        "Committing multiple MemWALs is not supported, but found committed: {:?}",
-                        committed
-                    ),
-                    location: location!(),
-                });
-            }
-
-            if to_commit.len() > 1 {
-                return Err(Error::NotSupported {
-                    source: format!(
-                        "Committing multiple MemWALs is not supported, but found attempt to commit: {:?}",
-                        to_commit
-                    )
-                    .into(),
-                    location: location!(),
-                });
-            }
-
-            let committed_mem_wal = committed.first().unwrap();
-            let to_commit_mem_wal = to_commit.first().unwrap();
-            if committed_mem_wal.id == to_commit_mem_wal.id {
-                return Err(self.incompatible_conflict_err(
-                    other_transaction,
-                    other_version,
-                    location!(),
-                ));
-            }
+        let mem_wal_ids = self.transaction.operation.referenced_mem_wal_ids();
+        let overlaps =
+            !mem_wal_ids.is_disjoint(&other_transaction.operation.referenced_mem_wal_ids());
+        self.conflict_condition = Some((ConflictReason::SameMemWal, overlaps));
+        if overlaps {
+            Err(self.incompatible_conflict_err(other_transaction, other_version, location!()))
+        } else {
+            Ok(())
         }
-
-        Ok(())
     }
 
     /// Writes
@@ -1162,6 +1259,7 @@ impl<'a> TransactionRebase<'a> {
             | Operation::Restore { .. }
             | Operation::ReserveFragments { .. }
             | Operation::Project { .. }
+            | Operation::RelaxNullability { .. }
             | Operation::UpdateConfig { .. }
             | Operation::UpdateMemWalState { .. } => Ok(self.transaction),
         }
@@ -1510,6 +1608,50 @@ fn wrong_operation_err(op: &Operation) -> Error {
     }
 }
 
+/// Whether the schema of a [`Operation::Merge`] or [`Operation::Project`]
+/// touches any field made nullable by a [`Operation::RelaxNullability`], by
+/// either dropping it or keeping it non-nullable.
+fn touches_relaxed_fields(field_ids: &[u32], schema: &lance_core::datatypes::Schema) -> bool {
+    field_ids.iter().any(|id| {
+        schema
+            .field_by_id(*id as i32)
+            .map(|f| !f.nullable)
+            .unwrap_or(true)
+    })
+}
+
+/// Whether the replacements change a column of a fragment covered by one of
+/// the indices.
+fn replacement_touches_indices(replacements: &[DataReplacementGroup], indices: &[Index]) -> bool {
+    replacements.iter().any(|r| {
+        indices
+            .iter()
+            .any(|idx| index_covers(idx, r.0) && idx.fields.iter().any(|f| r.1.fields.contains(f)))
+    })
+}
+
+/// Whether a rewrite moves a fragment covered by one of the indices, without
+/// deferring the remapping to a fragment reuse index.
+fn rewrite_moves_indices(
+    groups: &[RewriteGroup],
+    frag_reuse_index: &Option<Index>,
+    indices: &[Index],
+) -> bool {
+    frag_reuse_index.is_none()
+        && groups
+            .iter()
+            .flat_map(|g| g.old_fragments.iter())
+            .any(|frag| indices.iter().any(|idx| index_covers(idx, frag.id)))
+}
+
+/// Whether two sets of replacements replace the same column of a fragment.
+fn replacements_overlap(a: &[DataReplacementGroup], b: &[DataReplacementGroup]) -> bool {
+    a.iter().any(|r| {
+        b.iter()
+            .any(|other| r.0 == other.0 && r.1.fields.iter().any(|f| other.1.fields.contains(f)))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::{num::NonZero, sync::Arc};
@@ -1518,12 +1660,12 @@ mod tests {
     use arrow_schema::{DataType, Field, Schema};
     use lance_core::Error;
     use lance_file::version::LanceFileVersion;
+    use lance_index::mem_wal::MemWalId;
     use lance_io::object_store::ObjectStoreParams;
-    use lance_table::format::Index;
+    use lance_table::format::{DataFile, Index};
     use lance_table::io::deletion::{deletion_file_path, read_deletion_file};
 
     use super::*;
-    use crate::dataset::transaction::RewriteGroup;
     use crate::session::caches::DeletionFileKey;
     use crate::{
         dataset::{CommitBuilder, InsertBuilder, WriteParams},
@@ -1991,10 +2133,12 @@ mod tests {
                     "value".to_string(),
                 )])),
                 delete_keys: Some(vec!["remove-key".to_string()]),
+                delete_key_prefixes: None,
                 schema_metadata: Some(HashMap::from_iter(vec![(
                     "schema-key".to_string(),
                     "schema-value".to_string(),
                 )])),
+                schema_metadata_delete_keys: None,
                 field_metadata: Some(HashMap::from_iter(vec![(
                     0,
                     HashMap::from_iter(vec![("field-key".to_string(), "field-value".to_string())]),
@@ -2197,7 +2341,9 @@ mod tests {
                         "new-value".to_string(),
                     )])),
                     delete_keys: None,
+                    delete_key_prefixes: None,
                     schema_metadata: None,
+                    schema_metadata_delete_keys: None,
                     field_metadata: None,
                 },
                 [Compatible; 9],
@@ -2210,7 +2356,9 @@ mod tests {
                         "new-value".to_string(),
                     )])),
                     delete_keys: None,
+                    delete_key_prefixes: None,
                     schema_metadata: None,
+                    schema_metadata_delete_keys: None,
                     field_metadata: None,
                 },
                 [
@@ -2233,7 +2381,9 @@ mod tests {
                         "new-value".to_string(),
                     )])),
                     delete_keys: None,
+                    delete_key_prefixes: None,
                     schema_metadata: None,
+                    schema_metadata_delete_keys: None,
                     field_metadata: None,
                 },
                 [
@@ -2253,7 +2403,9 @@ mod tests {
                 Operation::UpdateConfig {
                     upsert_values: None,
                     delete_keys: Some(vec!["remove-key".to_string()]),
+                    delete_key_prefixes: None,
                     schema_metadata: None,
+                    schema_metadata_delete_keys: None,
                     field_metadata: None,
                 },
                 [Compatible; 9],
@@ -2263,7 +2415,32 @@ mod tests {
                 Operation::UpdateConfig {
                     upsert_values: None,
                     delete_keys: Some(vec!["lance.test".to_string()]),
+                    delete_key_prefixes: None,
+                    schema_metadata: None,
+                    schema_metadata_delete_keys: None,
+                    field_metadata: None,
+                },
+                [
+                    Compatible,    // append
+                    Compatible,    // create index
+                    Compatible,    // delete
+                    Compatible,    // merge
+                    Compatible,    // overwrite
+                    Compatible,    // rewrite
+                    Compatible,    // reserve
+                    Compatible,    // update
+                    NotCompatible, // update config
+                ],
+            ),
+            (
+                // Deleting a key prefix conflicts with a key being upserted under that prefix
+                // by another UpdateConfig operation
+                Operation::UpdateConfig {
+                    upsert_values: None,
+                    delete_keys: None,
+                    delete_key_prefixes: Some(vec!["lance.".to_string()]),
                     schema_metadata: None,
+                    schema_metadata_delete_keys: None,
                     field_metadata: None,
                 },
                 [
@@ -2278,16 +2455,41 @@ mod tests {
                     NotCompatible, // update config
                 ],
             ),
+            (
+                // Deleting a key prefix conflicts with a key being upserted under that prefix
+                // by an overwrite
+                Operation::UpdateConfig {
+                    upsert_values: None,
+                    delete_keys: None,
+                    delete_key_prefixes: Some(vec!["overwrite-".to_string()]),
+                    schema_metadata: None,
+                    schema_metadata_delete_keys: None,
+                    field_metadata: None,
+                },
+                [
+                    Compatible,    // append
+                    Compatible,    // create index
+                    Compatible,    // delete
+                    Compatible,    // merge
+                    NotCompatible, // overwrite
+                    Compatible,    // rewrite
+                    Compatible,    // reserve
+                    Compatible,    // update
+                    Compatible,    // update config
+                ],
+            ),
             (
                 // Changing schema metadata conflicts with another update changing schema
                 // metadata or with an overwrite
                 Operation::UpdateConfig {
                     upsert_values: None,
                     delete_keys: None,
+                    delete_key_prefixes: None,
                     schema_metadata: Some(HashMap::from_iter(vec![(
                         "schema-key".to_string(),
                         "new-value".to_string(),
                     )])),
+                    schema_metadata_delete_keys: None,
                     field_metadata: None,
                 },
                 [
@@ -2302,13 +2504,60 @@ mod tests {
                     NotCompatible, // update config
                 ],
             ),
+            (
+                // Deleting a schema metadata key conflicts with another update setting that
+                // key or with an overwrite
+                Operation::UpdateConfig {
+                    upsert_values: None,
+                    delete_keys: None,
+                    delete_key_prefixes: None,
+                    schema_metadata: None,
+                    schema_metadata_delete_keys: Some(vec!["schema-key".to_string()]),
+                    field_metadata: None,
+                },
+                [
+                    Compatible,    // append
+                    Compatible,    // create index
+                    Compatible,    // delete
+                    Compatible,    // merge
+                    NotCompatible, // overwrite
+                    Compatible,    // rewrite
+                    Compatible,    // reserve
+                    Compatible,    // update
+                    NotCompatible, // update config
+                ],
+            ),
+            (
+                // Deleting a schema metadata key that no other update sets is allowed
+                Operation::UpdateConfig {
+                    upsert_values: None,
+                    delete_keys: None,
+                    delete_key_prefixes: None,
+                    schema_metadata: None,
+                    schema_metadata_delete_keys: Some(vec!["other-key".to_string()]),
+                    field_metadata: None,
+                },
+                [
+                    Compatible,    // append
+                    Compatible,    // create index
+                    Compatible,    // delete
+                    Compatible,    // merge
+                    NotCompatible, // overwrite
+                    Compatible,    // rewrite
+                    Compatible,    // reserve
+                    Compatible,    // update
+                    Compatible,    // update config
+                ],
+            ),
             (
                 // Changing field metadata conflicts with another update changing same field
                 // metadata or overwrite
                 Operation::UpdateConfig {
                     upsert_values: None,
                     delete_keys: None,
+                    delete_key_prefixes: None,
                     schema_metadata: None,
+                    schema_metadata_delete_keys: None,
                     field_metadata: Some(HashMap::from_iter(vec![(
                         0,
                         HashMap::from_iter(vec![(
@@ -2334,7 +2583,9 @@ mod tests {
                 Operation::UpdateConfig {
                     upsert_values: None,
                     delete_keys: None,
+                    delete_key_prefixes: None,
                     schema_metadata: None,
+                    schema_metadata_delete_keys: None,
                     field_metadata: Some(HashMap::from_iter(vec![(
                         1,
                         HashMap::from_iter(vec![(
@@ -2355,6 +2606,20 @@ mod tests {
                     Compatible,    // update config
                 ],
             ),
+            (
+                Operation::Restore { version: 1 },
+                [
+                    NotCompatible, // append
+                    NotCompatible, // create index
+                    NotCompatible, // delete
+                    NotCompatible, // merge
+                    NotCompatible, // overwrite
+                    NotCompatible, // rewrite
+                    NotCompatible, // reserve
+                    NotCompatible, // update
+                    NotCompatible, // update config
+                ],
+            ),
         ];
 
         for (operation, expected_conflicts) in &cases {
@@ -2365,6 +2630,7 @@ mod tests {
                 modified_fragment_ids: modified_fragment_ids(operation).collect::<HashSet<_>>(),
                 affected_rows: None,
                 conflicting_frag_reuse_indices: Vec::new(),
+                conflict_condition: None,
             };
 
             for (other, expected_conflict) in other_transactions.iter().zip(expected_conflicts) {
@@ -2407,6 +2673,632 @@ mod tests {
         }
     }
 
+    /// The rows and columns of the compatibility matrix documented in
+    /// [`crate::dataset::transaction`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum MatrixOp {
+        Append,
+        DeleteUpdate,
+        Overwrite,
+        CreateIndex,
+        Rewrite,
+        Merge,
+        Project,
+        UpdateConfig,
+        DataReplacement,
+    }
+
+    impl MatrixOp {
+        const ALL: [Self; 9] = [
+            Self::Append,
+            Self::DeleteUpdate,
+            Self::Overwrite,
+            Self::CreateIndex,
+            Self::Rewrite,
+            Self::Merge,
+            Self::Project,
+            Self::UpdateConfig,
+            Self::DataReplacement,
+        ];
+
+        /// The matrix entry an operation falls under, or `None` if the
+        /// operation is documented (and tested) separately.
+        ///
+        /// There is deliberately no wildcard here, so a new [`Operation`]
+        /// variant has to be placed in the matrix or explicitly left out.
+        fn of(operation: &Operation) -> Option<Self> {
+            match operation {
+                Operation::Append { .. } => Some(Self::Append),
+                Operation::Delete { .. } | Operation::Update { .. } => Some(Self::DeleteUpdate),
+                Operation::Overwrite { .. } => Some(Self::Overwrite),
+                Operation::CreateIndex { .. } => Some(Self::CreateIndex),
+                Operation::Rewrite { .. } => Some(Self::Rewrite),
+                Operation::Merge { .. } => Some(Self::Merge),
+                Operation::Project { .. } => Some(Self::Project),
+                Operation::UpdateConfig { .. } => Some(Self::UpdateConfig),
+                Operation::DataReplacement { .. } => Some(Self::DataReplacement),
+                // See test_restore_conflicts, test_relax_nullability_conflicts,
+                // and test_mem_wal_conflicts.
+                Operation::Restore { .. }
+                | Operation::ReserveFragments { .. }
+                | Operation::RelaxNullability { .. }
+                | Operation::UpdateMemWalState { .. } => None,
+            }
+        }
+
+        /// Representative operations of this kind, each tagged with a region.
+        ///
+        /// Operations in the same region touch the same fragments, config keys
+        /// and columns, while operations in different regions touch none in
+        /// common.
+        fn representatives(self) -> Vec<(u64, Operation)> {
+            let two_columns = lance_core::datatypes::Schema::try_from(&Schema::new(vec![
+                Field::new("a", DataType::Int32, false),
+                Field::new("b", DataType::Int32, false),
+            ]))
+            .unwrap();
+            (0..2)
+                .flat_map(|region| {
+                    let config = Some(HashMap::from([(
+                        format!("key{}", region),
+                        "value".to_string(),
+                    )]));
+                    let operations = match self {
+                        Self::Append => vec![Operation::Append {
+                            fragments: vec![Fragment::new(10 + region)],
+                        }],
+                        Self::DeleteUpdate => vec![
+                            Operation::Delete {
+                                updated_fragments: vec![Fragment::new(region)],
+                                deleted_fragment_ids: vec![],
+                                predicate: "x > 2".to_string(),
+                            },
+                            Operation::Update {
+                                removed_fragment_ids: vec![],
+                                updated_fragments: vec![Fragment::new(region)],
+                                new_fragments: vec![Fragment::new(20 + region)],
+                                fields_modified: vec![region as u32],
+                                mem_wal_to_flush: None,
+                            },
+                        ],
+                        Self::Overwrite => vec![Operation::Overwrite {
+                            fragments: vec![Fragment::new(0)],
+                            schema: lance_core::datatypes::Schema::default(),
+                            config_upsert_values: config,
+                        }],
+                        Self::CreateIndex => vec![Operation::CreateIndex {
+                            new_indices: vec![Index {
+                                uuid: uuid::Uuid::new_v4(),
+                                name: format!("index{}", region),
+                                fields: vec![region as i32],
+                                dataset_version: 1,
+                                fragment_bitmap: Some([region as u32].into_iter().collect()),
+                                index_details: None,
+                                index_version: 0,
+                                created_at: None,
+                            }],
+                            removed_indices: vec![],
+                        }],
+                        Self::Rewrite => vec![Operation::Rewrite {
+                            groups: vec![RewriteGroup {
+                                old_fragments: vec![Fragment::new(region)],
+                                new_fragments: vec![Fragment::new(30 + region)],
+                            }],
+                            rewritten_indices: vec![],
+                            frag_reuse_index: None,
+                        }],
+                        Self::Merge => vec![Operation::Merge {
+                            fragments: vec![Fragment::new(0), Fragment::new(1)],
+                            schema: lance_core::datatypes::Schema::default(),
+                        }],
+                        // Drops the column of its region
+                        Self::Project => vec![Operation::Project {
+                            schema: two_columns.project_by_ids(&[1 - region as i32], true),
+                        }],
+                        Self::UpdateConfig => vec![Operation::UpdateConfig {
+                            upsert_values: config,
+                            delete_keys: None,
+                            delete_key_prefixes: None,
+                            schema_metadata: None,
+                            schema_metadata_delete_keys: None,
+                            field_metadata: None,
+                        }],
+                        Self::DataReplacement => vec![Operation::DataReplacement {
+                            replacements: vec![DataReplacementGroup::new(
+                                region,
+                                DataFile::new_legacy_from_fields(
+                                    format!("replacement{}", region),
+                                    vec![region as i32],
+                                ),
+                            )],
+                            new_index: None,
+                        }],
+                    };
+                    operations.into_iter().map(move |op| (region, op))
+                })
+                .collect()
+        }
+    }
+
+    /// An entry of the documented compatibility matrix.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Documented {
+        /// ✅
+        Yes,
+        /// ❌
+        No,
+        /// 1️⃣, 2️⃣, 3️⃣ or 4️⃣: compatible only if the operations are in
+        /// different regions.
+        Footnote(u8),
+    }
+
+    // Rows are the transaction being checked, columns the transaction
+    // that has been applied, both in the order of `MatrixOp::ALL`. This
+    // must be kept in sync with the table in `dataset::transaction`.
+    #[rustfmt::skip]
+    const MATRIX: [[Documented; 9]; 9] = {
+        use Documented::*;
+        [
+            // Append  Del/Upd       Overwrite     CreateIdx     Rewrite       Merge  Project       UpdateCfg     DataRepl
+            [Yes,      Yes,          No,           Yes,          Yes,          Yes,   Yes,          Yes,          Yes],          // Append
+            [Yes,      Footnote(1),  No,           Yes,          Footnote(1),  No,    Yes,          Yes,          Footnote(1)],  // Delete / Update
+            [Yes,      Yes,          Footnote(2),  Yes,          Yes,          Yes,   Yes,          Footnote(2),  Yes],          // Overwrite
+            [Yes,      Yes,          No,           Yes,          Footnote(4),  Yes,   Yes,          Yes,          Footnote(3)],  // CreateIndex
+            [Yes,      Footnote(1),  No,           Footnote(4),  Footnote(1),  No,    Yes,          Yes,          Footnote(3)],  // Rewrite
+            [No,       No,           No,           Yes,          No,           No,    No,           Yes,          No],           // Merge
+            [Yes,      Yes,          No,           Yes,          Yes,          No,    No,           Yes,          Yes],          // Project
+            [Yes,      Yes,          Footnote(2),  Yes,          Yes,          Yes,   Yes,          Footnote(2),  Yes],          // UpdateConfig
+            [Yes,      Yes,          No,           Footnote(3),  Footnote(1),  Yes,   Footnote(3),  Yes,          Footnote(3)],  // DataReplacement
+        ]
+    };
+
+    /// Check every pair of representatives of a cell of [`MATRIX`] against
+    /// the resolver, returning a description of each mismatch.
+    fn matrix_cell_mismatches(candidate_kind: MatrixOp, applied_kind: MatrixOp) -> Vec<String> {
+        use Documented::*;
+
+        let documented = MATRIX[candidate_kind as usize][applied_kind as usize];
+        let mut mismatches = Vec::new();
+        for (candidate_region, candidate) in candidate_kind.representatives() {
+            assert_eq!(MatrixOp::of(&candidate), Some(candidate_kind));
+            for (applied_region, applied) in applied_kind.representatives() {
+                let expect_compatible = match documented {
+                    Yes => true,
+                    No => false,
+                    Footnote(_) => candidate_region != applied_region,
+                };
+
+                let mut rebase = TransactionRebase::without_dataset(Transaction::new(
+                    0,
+                    candidate.clone(),
+                    None,
+                    None,
+                ));
+                let applied = Transaction::new(0, applied, None, None);
+                let result = rebase.check_txn(&applied, 1);
+                assert!(
+                    matches!(
+                        result,
+                        Ok(())
+                            | Err(Error::CommitConflict { .. })
+                            | Err(Error::RetryableCommitConflict { .. })
+                    ),
+                    "{:?} after {:?} failed with an unexpected error: {:?}",
+                    candidate,
+                    applied.operation,
+                    result
+                );
+
+                if result.is_ok() != expect_compatible {
+                    let expected = if expect_compatible {
+                        "compatible"
+                    } else {
+                        "conflict"
+                    };
+                    let note = match documented {
+                        Footnote(footnote) => format!(" (footnote {})", footnote),
+                        Yes | No => String::new(),
+                    };
+                    mismatches.push(format!(
+                        "{:?} after {:?}: expected {}{}, got {:?}",
+                        candidate, applied.operation, expected, note, result
+                    ));
+                }
+            }
+        }
+        mismatches
+    }
+
+    #[test]
+    fn test_conflict_matrix() {
+        let mismatches = MatrixOp::ALL
+            .into_iter()
+            .flat_map(|candidate_kind| {
+                MatrixOp::ALL.into_iter().flat_map(move |applied_kind| {
+                    matrix_cell_mismatches(candidate_kind, applied_kind)
+                })
+            })
+            .collect::<Vec<_>>();
+        assert!(
+            mismatches.is_empty(),
+            "Conflict resolution doesn't match the documented matrix:\n{}",
+            mismatches.join("\n")
+        );
+    }
+
+    #[test]
+    fn test_append_after_overwrite() {
+        let append = Transaction::new(
+            1,
+            Operation::Append {
+                fragments: vec![Fragment::new(0)],
+            },
+            None,
+            None,
+        );
+        let overwrite = Transaction::new(
+            1,
+            Operation::Overwrite {
+                fragments: vec![Fragment::new(0)],
+                schema: lance_core::datatypes::Schema::default(),
+                config_upsert_values: None,
+            },
+            None,
+            None,
+        );
+        let err = TransactionRebase::without_dataset(append)
+            .check_txn(&overwrite, 2)
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::CommitConflict { version: 2, .. }),
+            "{:?}",
+            err
+        );
+        let message = err.to_string();
+        assert!(message.contains("based on version 1"), "{}", message);
+        assert!(message.contains("overwritten at version 2"), "{}", message);
+    }
+
+    #[test]
+    fn test_relax_nullability_conflicts() {
+        let arrow_schema = Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+        ]);
+        let schema = lance_core::datatypes::Schema::try_from(&arrow_schema).unwrap();
+        let relax = Transaction::new(
+            0,
+            Operation::RelaxNullability { field_ids: vec![0] },
+            None,
+            None,
+        );
+        let append = Transaction::new(
+            0,
+            Operation::Append {
+                fragments: vec![Fragment::new(0)],
+            },
+            None,
+            None,
+        );
+
+        // A project that keeps field 0 non-nullable would undo the change
+        let project = Operation::Project {
+            schema: schema.clone(),
+        };
+        let result = rebase(project.clone()).check_txn(&relax, 1);
+        assert!(
+            matches!(result, Err(Error::RetryableCommitConflict { .. })),
+            "{:?}",
+            result
+        );
+        let result =
+            rebase(relax.operation.clone()).check_txn(&Transaction::new(0, project, None, None), 1);
+        assert!(
+            matches!(result, Err(Error::RetryableCommitConflict { .. })),
+            "{:?}",
+            result
+        );
+
+        // A project that already has field 0 as nullable does not touch it
+        let mut relaxed_schema = schema.clone();
+        relaxed_schema.field_by_id_mut(0).unwrap().nullable = true;
+        let project = Operation::Project {
+            schema: relaxed_schema,
+        };
+        assert!(rebase(project).check_txn(&relax, 1).is_ok());
+
+        // Data changes are unaffected
+        assert!(rebase(relax.operation.clone())
+            .check_txn(&append, 1)
+            .is_ok());
+        assert!(rebase(append.operation.clone())
+            .check_txn(&relax, 1)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_rewrite_create_index_conflicts() {
+        let rewrite = Operation::Rewrite {
+            groups: vec![RewriteGroup {
+                old_fragments: vec![Fragment::new(0)],
+                new_fragments: vec![Fragment::new(2)],
+            }],
+            rewritten_indices: vec![],
+            frag_reuse_index: None,
+        };
+        let create_index = |fragment_bitmap: Option<Vec<u32>>| Operation::CreateIndex {
+            new_indices: vec![Index {
+                uuid: uuid::Uuid::new_v4(),
+                name: "index".to_string(),
+                fields: vec![0],
+                dataset_version: 1,
+                fragment_bitmap: fragment_bitmap.map(|ids| ids.into_iter().collect()),
+                index_details: None,
+                index_version: 0,
+                created_at: None,
+            }],
+            removed_indices: vec![],
+        };
+
+        // An index over fragments that weren't rewritten is unaffected, in
+        // both directions.
+        let disjoint = create_index(Some(vec![1]));
+        assert!(rebase(rewrite.clone())
+            .check_txn(&Transaction::new(0, disjoint.clone(), None, None), 1)
+            .is_ok());
+        assert!(rebase(disjoint)
+            .check_txn(&Transaction::new(0, rewrite.clone(), None, None), 1)
+            .is_ok());
+
+        // An index covering a rewritten fragment must be retried, as must an
+        // index without a fragment bitmap.
+        for overlapping in [create_index(Some(vec![0, 1])), create_index(None)] {
+            let result = rebase(rewrite.clone())
+                .check_txn(&Transaction::new(0, overlapping.clone(), None, None), 1);
+            assert!(
+                matches!(result, Err(Error::RetryableCommitConflict { .. })),
+                "{:?}",
+                result
+            );
+            let result =
+                rebase(overlapping).check_txn(&Transaction::new(0, rewrite.clone(), None, None), 1);
+            assert!(
+                matches!(result, Err(Error::RetryableCommitConflict { .. })),
+                "{:?}",
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_data_replacement_with_index_conflicts() {
+        let index = |name: &str| Index {
+            uuid: uuid::Uuid::new_v4(),
+            name: name.to_string(),
+            fields: vec![0],
+            dataset_version: 1,
+            fragment_bitmap: Some([0].into_iter().collect()),
+            index_details: None,
+            index_version: 0,
+            created_at: None,
+        };
+        let replace_and_index = Operation::DataReplacement {
+            replacements: vec![DataReplacementGroup::new(
+                0,
+                DataFile::new_legacy_from_fields("replacement", vec![0]),
+            )],
+            new_index: Some(index("a_idx")),
+        };
+        let create_index = Operation::CreateIndex {
+            new_indices: vec![index("b_idx")],
+            removed_indices: vec![],
+        };
+        let append = Operation::Append {
+            fragments: vec![Fragment::new(1)],
+        };
+
+        // Conflicts with index creation in either direction
+        let result = rebase(replace_and_index.clone())
+            .check_txn(&Transaction::new(0, create_index.clone(), None, None), 1);
+        assert!(result.is_err(), "{:?}", result);
+        let result = rebase(create_index).check_txn(
+            &Transaction::new(0, replace_and_index.clone(), None, None),
+            1,
+        );
+        assert!(result.is_err(), "{:?}", result);
+
+        // Still compatible with appends
+        assert!(rebase(replace_and_index.clone())
+            .check_txn(&Transaction::new(0, append.clone(), None, None), 1)
+            .is_ok());
+        assert!(rebase(append)
+            .check_txn(&Transaction::new(0, replace_and_index, None, None), 1)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_restore_conflicts() {
+        let append = Transaction::new(
+            0,
+            Operation::Append {
+                fragments: vec![Fragment::new(0)],
+            },
+            None,
+            None,
+        );
+        let restore = Transaction::new(0, Operation::Restore { version: 1 }, None, None);
+
+        // Restore vs append conflicts in both directions.
+        let result = rebase(Operation::Restore { version: 1 }).check_txn(&append, 1);
+        assert!(
+            matches!(result, Err(Error::CommitConflict { .. })),
+            "{:?}",
+            result
+        );
+        let result = rebase(append.operation.clone()).check_txn(&restore, 1);
+        assert!(
+            matches!(result, Err(Error::CommitConflict { .. })),
+            "{:?}",
+            result
+        );
+
+        // Two restores to the same version are compatible.
+        let result = rebase(Operation::Restore { version: 1 }).check_txn(&restore, 1);
+        assert!(result.is_ok(), "{:?}", result);
+
+        // Two restores to different versions are not.
+        let result = rebase(Operation::Restore { version: 2 }).check_txn(&restore, 1);
+        assert!(
+            matches!(result, Err(Error::CommitConflict { .. })),
+            "{:?}",
+            result
+        );
+
+        assert_eq!(
+            append.operation.restore_conflicts_with(&append.operation),
+            None
+        );
+    }
+
+    #[test]
+    fn test_update_distinct_fragments() {
+        fn update(fragment_ids: &[u64]) -> Transaction {
+            Transaction::new(
+                0,
+                Operation::Update {
+                    removed_fragment_ids: vec![],
+                    updated_fragments: fragment_ids.iter().map(|id| Fragment::new(*id)).collect(),
+                    new_fragments: vec![],
+                    fields_modified: vec![],
+                    mem_wal_to_flush: None,
+                },
+                None,
+                None,
+            )
+        }
+        assert_eq!(
+            update(&[0, 1]).operation.touched_fragment_ids(),
+            HashSet::from([0, 1])
+        );
+
+        // Updates on disjoint fragments are compatible.
+        let result = rebase(update(&[0, 1]).operation).check_txn(&update(&[2, 3]), 1);
+        assert!(result.is_ok(), "{:?}", result);
+
+        // Updates on overlapping fragments conflict.
+        let result = rebase(update(&[0, 1]).operation).check_txn(&update(&[1, 2]), 1);
+        assert!(
+            matches!(result, Err(Error::RetryableCommitConflict { .. })),
+            "{:?}",
+            result
+        );
+
+        // The same rule applies between Update, Delete and Rewrite.
+        let delete = Transaction::new(
+            0,
+            Operation::Delete {
+                updated_fragments: vec![],
+                deleted_fragment_ids: vec![3],
+                predicate: "x > 2".to_string(),
+            },
+            None,
+            None,
+        );
+        let rewrite = Transaction::new(
+            0,
+            Operation::Rewrite {
+                groups: vec![RewriteGroup {
+                    old_fragments: vec![Fragment::new(0)],
+                    new_fragments: vec![Fragment::new(4)],
+                }],
+                rewritten_indices: vec![],
+                frag_reuse_index: None,
+            },
+            None,
+            None,
+        );
+        assert!(rebase(update(&[2]).operation).check_txn(&delete, 1).is_ok());
+        assert!(rebase(delete.operation.clone())
+            .check_txn(&update(&[3]), 1)
+            .is_err());
+        assert!(rebase(rewrite.operation.clone())
+            .check_txn(&update(&[1]), 1)
+            .is_ok());
+        assert!(rebase(rewrite.operation)
+            .check_txn(&update(&[0]), 1)
+            .is_err());
+    }
+
+    #[test]
+    fn test_mem_wal_conflicts() {
+        use lance_index::mem_wal::MemWal;
+
+        fn mem_wal(generation: u64) -> MemWal {
+            MemWal::new_empty(
+                MemWalId::new("GLOBAL", generation),
+                "mem_table_location",
+                "wal_location",
+                "owner",
+            )
+        }
+        fn update_state(removed: Vec<MemWal>, updated: Vec<MemWal>) -> Transaction {
+            Transaction::new(
+                0,
+                Operation::UpdateMemWalState {
+                    added: vec![],
+                    updated,
+                    removed,
+                },
+                None,
+                None,
+            )
+        }
+        fn flush(generation: u64) -> Transaction {
+            Transaction::new(
+                0,
+                Operation::Update {
+                    removed_fragment_ids: vec![],
+                    updated_fragments: vec![],
+                    new_fragments: vec![],
+                    fields_modified: vec![],
+                    mem_wal_to_flush: Some(mem_wal(generation)),
+                },
+                None,
+                None,
+            )
+        }
+        fn assert_conflict(result: Result<()>) {
+            assert!(
+                matches!(result, Err(Error::CommitConflict { .. })),
+                "{:?}",
+                result
+            );
+        }
+
+        // Two updates of the same MemWAL conflict.
+        let update0 = update_state(vec![mem_wal(0)], vec![mem_wal(0)]);
+        let update1 = update_state(vec![mem_wal(1)], vec![mem_wal(1)]);
+        assert_conflict(rebase(update0.operation.clone()).check_txn(&update0, 1));
+        // Updates of different MemWALs are compatible.
+        assert!(rebase(update0.operation.clone())
+            .check_txn(&update1, 1)
+            .is_ok());
+
+        // Trimming the same MemWAL twice conflicts, but different ones don't.
+        let trim0 = update_state(vec![mem_wal(0)], vec![]);
+        let trim1 = update_state(vec![mem_wal(1)], vec![]);
+        assert_conflict(rebase(trim0.operation.clone()).check_txn(&trim0, 1));
+        assert!(rebase(trim0.operation.clone()).check_txn(&trim1, 1).is_ok());
+        assert_conflict(rebase(update0.operation.clone()).check_txn(&trim0, 1));
+
+        // An update flushing a MemWAL conflicts with a concurrent change to the
+        // same MemWAL, in either direction.
+        assert_conflict(rebase(flush(0).operation).check_txn(&update0, 1));
+        assert_conflict(rebase(update0.operation.clone()).check_txn(&flush(0), 1));
+        assert!(rebase(flush(0).operation).check_txn(&update1, 1).is_ok());
+        assert!(rebase(update1.operation).check_txn(&flush(0), 1).is_ok());
+    }
+
     /// Returns the IDs of fragments that have been modified by this operation.
     ///
     /// This does not include new fragments.
@@ -2418,6 +3310,7 @@ mod tests {
             | Operation::CreateIndex { .. }
             | Operation::ReserveFragments { .. }
             | Operation::Project { .. }
+            | Operation::RelaxNullability { .. }
             | Operation::UpdateConfig { .. }
             | Operation::Restore { .. }
             | Operation::UpdateMemWalState { .. } => Box::new(std::iter::empty()),
@@ -2447,9 +3340,14 @@ mod tests {
                     .map(|f| f.id)
                     .chain(removed_fragment_ids.iter().copied()),
             ),
-            Operation::DataReplacement { replacements } => {
+            Operation::DataReplacement { replacements, .. } => {
                 Box::new(replacements.iter().map(|r| r.0))
             }
         }
     }
+
+    /// Rebase a transaction with the given operation, without a dataset.
+    fn rebase(operation: Operation) -> TransactionRebase<'static> {
+        TransactionRebase::without_dataset(Transaction::new(0, operation, None, None))
+    }
 }